@@ -4,7 +4,7 @@ use std::str::FromStr;
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-use crate::{process_decode, process_encode, CmdExecutor};
+use crate::{process_decode, process_encode, write_decoded, write_encoded, CmdExecutor};
 
 use super::verify_file;
 
@@ -24,6 +24,9 @@ pub struct Base64EncodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
 
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
     #[arg(long, value_parser = parse_bae64_format, default_value = "standard")]
     pub format: Base64Format,
 }
@@ -31,8 +34,7 @@ pub struct Base64EncodeOpts {
 impl CmdExecutor for Base64EncodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let encoded = process_encode(&self.input, self.format)?;
-        println!("{}", encoded);
-        Ok(())
+        write_encoded(&encoded, &self.output)
     }
 }
 
@@ -42,24 +44,36 @@ pub struct Base64DecodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
 
+    // 解码后的原始字节写入的文件，"-" 表示 stdout，输出到终端时二进制数据显示十六进制预览
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
     #[arg(long, value_parser = parse_bae64_format, default_value = "standard")]
     pub format: Base64Format,
+
+    // 忽略空白和换行，允许缺少 padding，standard 和 urlsafe 的字符可以混用
+    #[arg(long)]
+    pub lenient: bool,
 }
 
 impl CmdExecutor for Base64DecodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let decoded = process_decode(&self.input, self.format)?;
-        // TODO: decoded data might not be string (but for this example, we assume it is)
-        let decoded = String::from_utf8(decoded)?;
-        println!("{}", decoded);
-        Ok(())
+        let decoded = process_decode(&self.input, self.format, self.lenient)?;
+        write_decoded(&decoded, &self.output)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Base64Format {
     Standard,
+    StandardNoPad,
+    // 不带 padding，和 JWT 等场景一致
     UrlSafe,
+    UrlSafePad,
+    // 每 76 个字符用 CRLF 换行
+    Mime,
+    Bcrypt,
+    Crypt,
 }
 
 fn parse_bae64_format(format: &str) -> Result<Base64Format, anyhow::Error> {
@@ -72,7 +86,12 @@ impl FromStr for Base64Format {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
             "standard" => Ok(Base64Format::Standard),
-            "urlsafe" => Ok(Base64Format::UrlSafe),
+            "standard-no-pad" | "nopad" => Ok(Base64Format::StandardNoPad),
+            "urlsafe" | "urlsafe-no-pad" => Ok(Base64Format::UrlSafe),
+            "urlsafe-pad" => Ok(Base64Format::UrlSafePad),
+            "mime" => Ok(Base64Format::Mime),
+            "bcrypt" => Ok(Base64Format::Bcrypt),
+            "crypt" => Ok(Base64Format::Crypt),
             v => anyhow::bail!("Unsupported format: {}", v),
         }
     }
@@ -82,7 +101,12 @@ impl From<Base64Format> for &'static str {
     fn from(format: Base64Format) -> Self {
         match format {
             Base64Format::Standard => "standard",
+            Base64Format::StandardNoPad => "standard-no-pad",
             Base64Format::UrlSafe => "urlsafe",
+            Base64Format::UrlSafePad => "urlsafe-pad",
+            Base64Format::Mime => "mime",
+            Base64Format::Bcrypt => "bcrypt",
+            Base64Format::Crypt => "crypt",
         }
     }
 }
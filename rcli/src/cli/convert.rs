@@ -0,0 +1,114 @@
+use core::fmt;
+use std::{path::Path, str::FromStr};
+
+use clap::Parser;
+
+use super::verify_file;
+use crate::{process_convert, strip_compression_ext, CmdExecutor, ConvertOpt};
+
+// 通用的文档格式，rcli convert / query / schema / diff 共用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Json,
+    Json5,
+    Yaml,
+    Toml,
+    // 每行一个 json，整体视为数组
+    Ndjson,
+}
+
+#[derive(Debug, Parser)]
+pub struct ConvertOpts {
+    // "-" 表示input是从stdin里面读取的数据
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 输入的格式，不指定时根据扩展名判断，没有扩展名时根据内容判断
+    #[arg(long, value_parser = parse_doc_format)]
+    pub from: Option<DocFormat>,
+
+    // 输出的格式，不指定时根据输出文件的扩展名判断
+    #[arg(long, value_parser = parse_doc_format)]
+    pub to: Option<DocFormat>,
+
+    // 紧凑输出，json 输出为一行
+    #[arg(long)]
+    pub compact: bool,
+
+    // 对象的 key 按字母顺序排序，默认保持原来的顺序
+    #[arg(long)]
+    pub sort_keys: bool,
+}
+
+impl CmdExecutor for ConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let to = match self.to {
+            Some(to) => to,
+            None => DocFormat::from_path(&self.output).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Can not detect output format of {}, please specify --to",
+                    self.output
+                )
+            })?,
+        };
+        let opt = ConvertOpt {
+            from: self.from,
+            compact: self.compact,
+            sort_keys: self.sort_keys,
+        };
+        process_convert(&self.input, &self.output, to, opt)
+    }
+}
+
+impl DocFormat {
+    // 根据扩展名判断格式，忽略 .gz 这类压缩格式的扩展名
+    pub fn from_path(path: &str) -> Option<Self> {
+        if path == "-" {
+            return None;
+        }
+        let ext = Path::new(strip_compression_ext(path))
+            .extension()?
+            .to_str()?;
+        ext.parse().ok()
+    }
+}
+
+pub(crate) fn parse_doc_format(format: &str) -> Result<DocFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<DocFormat> for &'static str {
+    fn from(format: DocFormat) -> Self {
+        match format {
+            DocFormat::Json => "json",
+            DocFormat::Json5 => "json5",
+            DocFormat::Yaml => "yaml",
+            DocFormat::Toml => "toml",
+            DocFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl FromStr for DocFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "json" => Ok(DocFormat::Json),
+            "json5" => Ok(DocFormat::Json5),
+            "yaml" | "yml" => Ok(DocFormat::Yaml),
+            "toml" => Ok(DocFormat::Toml),
+            "ndjson" | "jsonl" => Ok(DocFormat::Ndjson),
+            v => anyhow::bail!("Unsupported format: {}", v),
+        }
+    }
+}
+
+impl fmt::Display for DocFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -1,59 +1,776 @@
-use clap::Parser;
+use clap::{ArgGroup, Args, Parser};
 use core::fmt;
-use std::str::FromStr;
+use encoding_rs::Encoding;
+use enum_dispatch::enum_dispatch;
+use std::{path::Path, str::FromStr};
 
-use crate::{process_csv, CmdExecutor};
+use crate::{
+    process_csv, process_csv_cat, process_csv_dedupe, process_csv_diff, process_csv_from_json,
+    process_csv_from_parquet, process_csv_query, process_csv_sort, process_csv_split,
+    process_csv_stats, process_csv_to_xlsx, process_csv_validate, strip_compression_ext,
+    CmdExecutor, CsvConvertOpt, CsvFromJsonOpt, CsvQueryOpt, CsvReaderOpt, CsvSortOpt, CsvStatsOpt,
+    SortKey, SplitMode, WriterOpt,
+};
 
 use super::verify_file;
 
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
     Json,
+    Csv,
+    // 每行一个 json 对象，适合流式处理
+    Ndjson,
     Yaml,
-    // Toml, 暂时不支持
+    // TOML 不支持顶层数组，所以输出为 [[rows]] 这样的 array of tables
+    Toml,
+    // 终端里对齐的表格
+    Table,
+    Markdown,
+    Html,
+    // 列式存储，按 row group 写出
+    Parquet,
+    // Arrow IPC 文件格式
+    Arrow,
+}
+
+// parquet / arrow 的压缩算法，arrow 只支持 lz4 和 zstd
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Snappy,
+    Gzip,
+    Zstd,
+    Lz4,
+    Brotli,
 }
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_file)]
-    pub input: String,
+    // 不带子命令时执行 csv 转换，如 rcli csv -i input.csv
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    #[command(flatten)]
+    pub convert: Box<CsvConvertOpts>,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum CsvSubCommand {
+    #[command(
+        name = "from-json",
+        about = "Convert JSON/YAML/NDJSON/TOML records to CSV"
+    )]
+    FromJson(CsvFromJsonOpts),
+    #[command(name = "query", about = "Run a SQL query over CSV files")]
+    Query(CsvQueryOpts),
+    #[command(name = "stats", about = "Show per-column statistics of a CSV file")]
+    Stats(CsvStatsOpts),
+    #[command(name = "validate", about = "Validate a CSV file against a schema")]
+    Validate(CsvValidateOpts),
+    #[command(
+        name = "diff",
+        about = "Compare two CSV files row by row using key columns"
+    )]
+    Diff(CsvDiffOpts),
+    #[command(name = "sort", about = "Sort CSV rows by columns")]
+    Sort(CsvSortOpts),
+    #[command(name = "dedupe", about = "Remove duplicate CSV rows")]
+    Dedupe(CsvDedupeOpts),
+    #[command(
+        name = "split",
+        about = "Split a CSV file by row count or column value"
+    )]
+    Split(CsvSplitOpts),
+    #[command(name = "cat", about = "Concatenate CSV files, aligning their headers")]
+    Cat(CsvCatOpts),
+    #[command(name = "to-xlsx", about = "Write a CSV file to an XLSX workbook")]
+    ToXlsx(CsvToXlsxOpts),
+    #[command(name = "from-parquet", about = "Convert a Parquet file to CSV")]
+    FromParquet(CsvFromParquetOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvConvertOpts {
+    // "-" 表示input是从stdin里面读取的数据
+    // 使用子命令时不需要 input，所以这里是 Option，由 clap 保证不带子命令时必填
+    #[arg(short, long, value_parser = verify_file, required = true)]
+    pub input: Option<String>,
 
     // short 短名称 如-c=xx，long 长名称 如 --name=xx
+    // "-" 表示输出到 stdout
     #[arg(short, long)]
     pub output: Option<String>,
 
     #[arg(short, long, value_parser = parse_format, default_value = "json")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value = ",")]
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+
+    #[command(flatten)]
+    pub writer: WriterArgs,
+
+    // 根据样本推断每一列的类型（整数、浮点数、布尔值、日期），空单元格输出为 null
+    #[arg(long)]
+    pub infer_types: bool,
+
+    #[arg(long, default_value_t = 1000)]
+    pub infer_sample: usize,
+
+    // 通过 yaml 文件强制指定列类型，如 `columns: { Kit Number: integer }`，列名是 --rename 之前的原始列名
+    #[arg(long, value_parser = verify_file)]
+    pub schema: Option<String>,
+
+    // 把 address.city, tags[0] 这样的表头构造成嵌套的对象和数组，数组下标最大为 10000
+    #[arg(long)]
+    pub nest: bool,
+
+    #[arg(long, default_value = ".")]
+    pub nest_sep: String,
+
+    // 只输出这些列，按给定的顺序
+    #[arg(long, value_delimiter = ',')]
+    pub select: Option<Vec<String>>,
+
+    #[arg(long, value_delimiter = ',')]
+    pub exclude: Vec<String>,
+
+    // 重命名列，如 --rename "Kit Number=kit"
+    #[arg(long, value_parser = parse_rename, value_delimiter = ',')]
+    pub rename: Vec<(String, String)>,
+
+    // 行过滤，如 --where "Nationality == 'Italy' && Kit Number > 10"
+    #[arg(long = "where")]
+    pub filter: Option<String>,
+}
+
+// 读取 csv 的通用参数，各个 csv 子命令共用
+#[derive(Debug, Args)]
+pub struct CsvReaderArgs {
+    // 制表符可以写成 '\t' 或者 tab
+    #[arg(short, long, value_parser = parse_char, default_value = ",")]
     pub delimiter: char,
 
     // header 不能使用 short，因为clap里面默认-h是 help，会冲突
-    #[arg(long, default_value_t = true)]
+    // --header false 表示文件没有表头，列名由 --columns 指定，否则自动生成 col1, col2, ...
+    #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
     pub header: bool,
+
+    // 自定义列名，有表头时会覆盖文件里的表头
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Option<Vec<String>>,
+
+    #[arg(long, value_parser = parse_char, default_value = "\"")]
+    pub quote: char,
+
+    #[arg(long, value_parser = parse_char)]
+    pub escape: Option<char>,
+
+    // 以该字符开头的行会被忽略
+    #[arg(long, value_parser = parse_char)]
+    pub comment: Option<char>,
+
+    // 输入的编码，如 gbk、gb18030、shift_jis、utf-16le，auto 表示自动检测，BOM 总是会被去掉
+    #[arg(long, value_parser = parse_encoding, default_value = "auto")]
+    pub encoding: InputEncoding,
+
+    // 输入为 xlsx/xlsm/xlsb/xls/ods 时读取的工作表，默认为第一个
+    #[arg(long)]
+    pub sheet: Option<String>,
+}
+
+// 输出记录的通用参数
+#[derive(Debug, Args)]
+pub struct WriterArgs {
+    // 输出为 toml 时，数据放在 [[<toml-key>]] 下面
+    #[arg(long, default_value = "rows")]
+    pub toml_key: String,
+
+    // table / markdown / html 输出时单元格的最大显示宽度，超出部分截断为 …
+    #[arg(long)]
+    pub max_width: Option<usize>,
+
+    // table / markdown / html 输出时在第一列显示行号
+    #[arg(long)]
+    pub row_numbers: bool,
+
+    // parquet / arrow 的压缩算法，默认 parquet 使用 snappy，arrow 不压缩
+    #[arg(long, value_parser = parse_compression)]
+    pub compression: Option<Compression>,
+
+    // parquet / arrow 每个 row group（record batch）的行数，写满一组就写出，不会缓存所有数据
+    #[arg(long, default_value_t = 65536)]
+    pub row_group_size: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromJsonOpts {
+    // "-" 表示input是从stdin里面读取的数据
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "output.csv")]
+    pub output: String,
+
+    // 输入的格式，不指定时根据文件扩展名判断，stdin 默认为 json
+    #[arg(short, long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    #[arg(short, long, value_parser = parse_char, default_value = ",")]
+    pub delimiter: char,
+
+    // 嵌套对象展开后列名的分隔符，如 address.city
+    #[arg(long, default_value = ".")]
+    pub separator: String,
+
+    // 数组的处理方式：json 序列化为 json 字符串，explode 展开为多行
+    #[arg(long, value_parser = parse_array_mode, default_value = "json")]
+    pub arrays: ArrayMode,
+
+    // 输入为 toml 时，从 [[<toml-key>]] 读取数据
+    #[arg(long, default_value = "rows")]
+    pub toml_key: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvQueryOpts {
+    // 每个文件是一张表，表名为文件名去掉扩展名，如 -i data/players.csv => players
+    pub sql: String,
+
+    #[arg(short, long = "input", value_parser = verify_file, required = true)]
+    pub inputs: Vec<String>,
+
+    // "-" 表示输出到 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, default_value = "table")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+
+    #[command(flatten)]
+    pub writer: WriterArgs,
+
+    // 根据前 N 行推断列类型，数字列可以按数值比较、排序和聚合
+    #[arg(long, default_value_t = 1000)]
+    pub infer_sample: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    // "-" 表示input是从stdin里面读取的数据
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, default_value = "table")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+
+    #[command(flatten)]
+    pub writer: WriterArgs,
+
+    // 每列显示出现次数最多的前 N 个值
+    // 不同值超过 10000 个的列，distinct、top 和 median 是估算值，approximate 列为 true
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    // "-" 表示input是从stdin里面读取的数据
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // 声明列的类型、是否必须、正则、枚举、范围、唯一性和是否可为空的 yaml 文件
+    #[arg(long, value_parser = verify_file)]
+    pub schema: String,
+
+    // 报告输出的位置，"-" 表示输出到 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_report_format, default_value = "text")]
+    pub format: ReportFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+
+    // 用来匹配两个文件里同一行的列，多个列用逗号分隔，如 --key id,region
+    #[arg(short, long, value_delimiter = ',', required = true)]
+    pub key: Vec<String>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // text 输出到终端时带颜色，csv 为第一列是 _op 的 patch 文件
+    #[arg(short, long, value_parser = parse_diff_format, default_value = "text")]
+    pub format: DiffFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSortOpts {
+    // "-" 表示input是从stdin里面读取的数据
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 排序的列，默认升序，如 --by team,age:desc
+    #[arg(long, value_parser = parse_sort_key, value_delimiter = ',', required = true)]
+    pub by: Vec<SortKey>,
+
+    // 每次在内存里排序的行数，文件更大时使用外部归并排序
+    #[arg(long, default_value_t = 100_000)]
+    pub chunk_rows: usize,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDedupeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 判断重复的列，不指定时比较整行
+    #[arg(short, long, value_delimiter = ',')]
+    pub key: Vec<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvToXlsxOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "output.xlsx")]
+    pub output: String,
+
+    // 工作表的名称
+    #[arg(long = "sheet-name", default_value = "Sheet1")]
+    pub sheet_name: String,
+
+    // 根据样本推断每一列的类型，数字、布尔值和日期写成对应类型的单元格
+    #[arg(long, default_value_t = 1000)]
+    pub infer_sample: usize,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromParquetOpts {
+    // parquet 需要随机读取文件的结尾，所以不支持 stdin
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_char, default_value = ",")]
+    pub delimiter: char,
+
+    // 每次读取的行数
+    #[arg(long, default_value_t = 8192)]
+    pub batch_size: usize,
+}
+
+#[derive(Debug, Parser)]
+#[command(group(ArgGroup::new("mode").required(true).args(["rows", "by"])))]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    // 每个文件最多的行数（不含表头），至少为 1
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub rows: Option<usize>,
+
+    // 按照该列的值拆分，每个值一个文件
+    #[arg(long)]
+    pub by: Option<String>,
+
+    #[arg(short, long, default_value = ".")]
+    pub output_dir: String,
+
+    // 输出文件名的前缀，默认为输入文件名
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvCatOpts {
+    #[arg(value_parser = verify_file, required = true)]
+    pub inputs: Vec<String>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEncoding {
+    Auto,
+    Fixed(&'static Encoding),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayMode {
+    Json,
+    Explode,
 }
 
 impl CmdExecutor for CsvOpts {
     async fn execute(self) -> anyhow::Result<()> {
+        match self.cmd {
+            Some(cmd) => cmd.execute().await,
+            None => self.convert.execute().await,
+        }
+    }
+}
+
+impl CmdExecutor for CsvConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let Some(input) = self.input else {
+            anyhow::bail!("The following required argument was not provided: --input");
+        };
         let output = if let Some(output) = self.output {
             output
         } else {
-            format!("output.{}", self.format)
+            self.format.default_output()
+        };
+        let opt = CsvConvertOpt {
+            reader: self.reader.into(),
+            writer: self.writer.into(),
+            infer_types: self.infer_types,
+            infer_sample: self.infer_sample,
+            schema: self.schema,
+            nest: self.nest.then_some(self.nest_sep),
+            select: self.select,
+            exclude: self.exclude,
+            rename: self.rename,
+            filter: self.filter,
+        };
+        process_csv(&input, output, self.format, opt)
+    }
+}
+
+impl CmdExecutor for CsvFromJsonOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let format = match self.format {
+            Some(format) => format,
+            None => OutputFormat::from_path(&self.input)?,
+        };
+        let opt = CsvFromJsonOpt {
+            delimiter: self.delimiter,
+            separator: self.separator,
+            arrays: self.arrays,
+            toml_key: self.toml_key,
+        };
+        process_csv_from_json(&self.input, &self.output, format, opt)
+    }
+}
+
+impl CmdExecutor for CsvQueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opt = CsvQueryOpt {
+            reader: self.reader.into(),
+            writer: self.writer.into(),
+            infer_sample: self.infer_sample,
+        };
+        process_csv_query(&self.sql, &self.inputs, &self.output, self.format, opt)
+    }
+}
+
+impl CmdExecutor for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opt = CsvStatsOpt {
+            reader: self.reader.into(),
+            writer: self.writer.into(),
+            top: self.top,
+        };
+        process_csv_stats(&self.input, &self.output, self.format, opt)
+    }
+}
+
+impl CmdExecutor for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_validate(
+            &self.input,
+            &self.schema,
+            &self.output,
+            self.format,
+            self.reader.into(),
+        )
+    }
+}
+
+impl CmdExecutor for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_diff(
+            &self.old,
+            &self.new,
+            &self.key,
+            &self.output,
+            self.format,
+            self.reader.into(),
+        )
+    }
+}
+
+impl CmdExecutor for CsvSortOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opt = CsvSortOpt {
+            reader: self.reader.into(),
+            chunk_rows: self.chunk_rows,
         };
-        process_csv(&self.input, output, self.format)
+        process_csv_sort(&self.input, &self.output, &self.by, opt)
+    }
+}
+
+impl CmdExecutor for CsvDedupeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_dedupe(&self.input, &self.output, &self.key, self.reader.into())
+    }
+}
+
+impl CmdExecutor for CsvSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mode = match (self.rows, self.by) {
+            (Some(rows), _) => SplitMode::Rows(rows),
+            (None, Some(by)) => SplitMode::By(by),
+            (None, None) => anyhow::bail!("Either --rows or --by is required"),
+        };
+        let prefix = match self.prefix {
+            Some(prefix) => prefix,
+            None => Path::new(strip_compression_ext(&self.input))
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("split")
+                .to_string(),
+        };
+        let files = process_csv_split(
+            &self.input,
+            &self.output_dir,
+            &prefix,
+            mode,
+            self.reader.into(),
+        )?;
+        for file in files {
+            println!("{}", file);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecutor for CsvCatOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_cat(&self.inputs, &self.output, self.reader.into())
+    }
+}
+
+impl CmdExecutor for CsvToXlsxOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_to_xlsx(
+            &self.input,
+            &self.output,
+            &self.sheet_name,
+            self.infer_sample,
+            self.reader.into(),
+        )
+    }
+}
+
+impl CmdExecutor for CsvFromParquetOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_from_parquet(&self.input, &self.output, self.delimiter, self.batch_size)
+    }
+}
+
+impl From<WriterArgs> for WriterOpt {
+    fn from(args: WriterArgs) -> Self {
+        Self {
+            toml_key: args.toml_key,
+            max_width: args.max_width,
+            row_numbers: args.row_numbers,
+            compression: args.compression,
+            row_group_size: args.row_group_size,
+        }
+    }
+}
+
+impl From<CsvReaderArgs> for CsvReaderOpt {
+    fn from(args: CsvReaderArgs) -> Self {
+        Self {
+            delimiter: args.delimiter,
+            header: args.header,
+            columns: args.columns,
+            quote: args.quote,
+            escape: args.escape,
+            comment: args.comment,
+            encoding: args.encoding,
+            sheet: args.sheet,
+        }
+    }
+}
+
+impl OutputFormat {
+    // table / markdown / html 是给人看的表格，其他是结构化的数据
+    pub fn is_tabular(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Table | OutputFormat::Markdown | OutputFormat::Html
+        )
+    }
+
+    // parquet / arrow 按列写出，需要固定的列类型
+    pub fn is_columnar(&self) -> bool {
+        matches!(self, OutputFormat::Parquet | OutputFormat::Arrow)
+    }
+
+    // 不指定输出文件时的默认输出，表格默认输出到 stdout
+    pub fn default_output(&self) -> String {
+        match self {
+            OutputFormat::Table => "-".to_string(),
+            OutputFormat::Markdown => "output.md".to_string(),
+            format => format!("output.{}", format),
+        }
+    }
+
+    // 根据文件扩展名判断格式，忽略 .gz 这类压缩格式的扩展名，stdin 默认为 json
+    pub fn from_path(path: &str) -> anyhow::Result<Self> {
+        if path == "-" {
+            return Ok(OutputFormat::Json);
+        }
+        match Path::new(strip_compression_ext(path))
+            .extension()
+            .and_then(|ext| ext.to_str())
+        {
+            Some("yml") => Ok(OutputFormat::Yaml),
+            Some(ext) => ext.parse(),
+            None => anyhow::bail!("Can not detect format of {}, please specify --format", path),
+        }
     }
 }
 
-fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
+pub(crate) fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse::<OutputFormat>()
 }
 
+fn parse_rename(value: &str) -> Result<(String, String), anyhow::Error> {
+    match value.split_once('=') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() => {
+            Ok((from.to_string(), to.to_string()))
+        }
+        _ => anyhow::bail!("Invalid rename, expect OLD=NEW, got: {}", value),
+    }
+}
+
+fn parse_array_mode(mode: &str) -> Result<ArrayMode, anyhow::Error> {
+    mode.parse()
+}
+
+pub(crate) fn parse_report_format(format: &str) -> Result<ReportFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_diff_format(format: &str) -> Result<DiffFormat, anyhow::Error> {
+    format.parse()
+}
+
+// age:desc => SortKey { column: age, desc: true }
+fn parse_sort_key(value: &str) -> Result<SortKey, anyhow::Error> {
+    let (column, desc) = match value.rsplit_once(':') {
+        Some((column, "desc")) => (column, true),
+        Some((column, "asc")) => (column, false),
+        _ => (value, false),
+    };
+    if column.is_empty() {
+        anyhow::bail!("Invalid sort key: {}", value);
+    }
+    Ok(SortKey {
+        column: column.to_string(),
+        desc,
+    })
+}
+
+fn parse_compression(compression: &str) -> Result<Compression, anyhow::Error> {
+    compression.parse()
+}
+
+fn parse_encoding(encoding: &str) -> Result<InputEncoding, anyhow::Error> {
+    encoding.parse()
+}
+
+fn parse_char(value: &str) -> Result<char, anyhow::Error> {
+    match value {
+        "\\t" | "tab" => return Ok('\t'),
+        _ => {}
+    }
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii() => Ok(c),
+        _ => anyhow::bail!("Expect a single ASCII character, got: {}", value),
+    }
+}
+
 impl From<OutputFormat> for &'static str {
     fn from(format: OutputFormat) -> Self {
         match format {
             OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Ndjson => "ndjson",
             OutputFormat::Yaml => "yaml",
-            // OutputFormat::Toml => "toml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Table => "table",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Arrow => "arrow",
         }
     }
 }
@@ -64,8 +781,15 @@ impl FromStr for OutputFormat {
     fn from_str(format: &str) -> Result<Self, Self::Err> {
         match format.to_lowercase().as_str() {
             "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
             "yaml" => Ok(OutputFormat::Yaml),
-            // "toml" => Ok(OutputFormat::Toml),
+            "toml" => Ok(OutputFormat::Toml),
+            "table" => Ok(OutputFormat::Table),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" | "htm" => Ok(OutputFormat::Html),
+            "parquet" | "pq" => Ok(OutputFormat::Parquet),
+            "arrow" | "ipc" | "feather" => Ok(OutputFormat::Arrow),
             v => anyhow::bail!("Unsupported format: {}", v),
         }
     }
@@ -76,3 +800,90 @@ impl fmt::Display for OutputFormat {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+impl From<Compression> for &'static str {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => "none",
+            Compression::Snappy => "snappy",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+            Compression::Brotli => "brotli",
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(compression: &str) -> Result<Self, Self::Err> {
+        match compression.to_lowercase().as_str() {
+            "none" | "uncompressed" => Ok(Compression::None),
+            "snappy" => Ok(Compression::Snappy),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            "lz4" => Ok(Compression::Lz4),
+            "brotli" => Ok(Compression::Brotli),
+            v => anyhow::bail!("Unsupported compression: {}", v),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for ArrayMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "json" => Ok(ArrayMode::Json),
+            "explode" => Ok(ArrayMode::Explode),
+            v => anyhow::bail!("Unsupported array mode: {}", v),
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            v => anyhow::bail!("Unsupported report format: {}", v),
+        }
+    }
+}
+
+impl FromStr for DiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            "csv" => Ok(DiffFormat::Csv),
+            v => anyhow::bail!("Unsupported diff format: {}", v),
+        }
+    }
+}
+
+impl FromStr for InputEncoding {
+    type Err = anyhow::Error;
+
+    // 支持 WHATWG 定义的编码名称，如 gbk、gb2312、shift_jis、utf-16le
+    fn from_str(encoding: &str) -> Result<Self, Self::Err> {
+        if encoding.eq_ignore_ascii_case("auto") {
+            return Ok(InputEncoding::Auto);
+        }
+        match Encoding::for_label(encoding.as_bytes()) {
+            Some(encoding) => Ok(InputEncoding::Fixed(encoding)),
+            None => anyhow::bail!("Unsupported encoding: {}", encoding),
+        }
+    }
+}
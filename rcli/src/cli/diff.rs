@@ -0,0 +1,111 @@
+use core::fmt;
+use std::str::FromStr;
+
+use clap::Parser;
+
+use super::{convert::parse_doc_format, verify_file, DocFormat};
+use crate::{process_diff, process_patch, CmdExecutor, DocDiffOpt, PatchOpt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocDiffFormat {
+    // 按文档结构缩进显示的变化
+    Tree,
+    // RFC 6902 JSON Patch
+    Patch,
+}
+
+#[derive(Debug, Parser)]
+pub struct DiffOpts {
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 两个文件的格式，不指定时根据扩展名判断，没有扩展名时根据内容判断
+    #[arg(long, value_parser = parse_doc_format)]
+    pub from: Option<DocFormat>,
+
+    // tree 输出到终端时带颜色，patch 可以用 rcli patch 应用
+    #[arg(short, long, value_parser = parse_doc_diff_format, default_value = "tree")]
+    pub format: DocDiffFormat,
+
+    // 忽略数组元素的顺序
+    #[arg(long)]
+    pub ignore_order: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct PatchOpts {
+    // "-" 表示input是从stdin里面读取的数据
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // RFC 6902 JSON Patch 文件，例如 rcli diff -f patch 的输出
+    #[arg(short, long, value_parser = verify_file)]
+    pub patch: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_doc_format)]
+    pub from: Option<DocFormat>,
+
+    // 输出的格式，不指定时根据输出文件的扩展名判断，否则和输入的格式相同
+    #[arg(long, value_parser = parse_doc_format)]
+    pub to: Option<DocFormat>,
+}
+
+impl CmdExecutor for DiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opt = DocDiffOpt {
+            from: self.from,
+            ignore_order: self.ignore_order,
+        };
+        process_diff(&self.old, &self.new, &self.output, self.format, opt)
+    }
+}
+
+impl CmdExecutor for PatchOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opt = PatchOpt {
+            from: self.from,
+            to: self.to,
+        };
+        process_patch(&self.input, &self.patch, &self.output, opt)
+    }
+}
+
+fn parse_doc_diff_format(format: &str) -> Result<DocDiffFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<DocDiffFormat> for &'static str {
+    fn from(format: DocDiffFormat) -> Self {
+        match format {
+            DocDiffFormat::Tree => "tree",
+            DocDiffFormat::Patch => "patch",
+        }
+    }
+}
+
+impl FromStr for DocDiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "tree" | "text" => Ok(DocDiffFormat::Tree),
+            "patch" | "json-patch" => Ok(DocDiffFormat::Patch),
+            v => anyhow::bail!("Unsupported diff format: {}", v),
+        }
+    }
+}
+
+impl fmt::Display for DocDiffFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
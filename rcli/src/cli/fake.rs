@@ -0,0 +1,44 @@
+use clap::Parser;
+
+use super::{csv::parse_format, verify_file, OutputFormat, WriterArgs};
+use crate::{process_fake, CmdExecutor, FakeOpt};
+
+#[derive(Debug, Parser)]
+pub struct FakeOpts {
+    // 描述表和列的 yaml 文件，格式见 fixtures/people.yaml
+    #[arg(short, long, value_parser = verify_file)]
+    pub schema: String,
+
+    // 输出的行数，默认使用 schema 里的 rows，没有时为 10
+    #[arg(short, long)]
+    pub rows: Option<usize>,
+
+    // schema 里有多个表时输出的表，默认为最后一个
+    #[arg(short, long)]
+    pub table: Option<String>,
+
+    #[arg(short, long, value_parser = parse_format, default_value = "csv")]
+    pub format: OutputFormat,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 相同的种子生成相同的数据
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[command(flatten)]
+    pub writer: WriterArgs,
+}
+
+impl CmdExecutor for FakeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opt = FakeOpt {
+            writer: self.writer.into(),
+            table: self.table,
+            rows: self.rows,
+            seed: self.seed,
+        };
+        process_fake(&self.schema, &self.output, self.format, opt)
+    }
+}
@@ -1,13 +1,22 @@
 mod base64;
+mod convert;
 mod csv;
+mod diff;
+mod fake;
 mod genpass;
 mod http;
 mod jwt;
+mod query;
+mod schema;
 mod text;
+mod time;
 
 use std::path::{Path, PathBuf};
 
-pub use self::{base64::*, csv::*, genpass::*, http::*, jwt::*, text::*};
+pub use self::{
+    base64::*, convert::*, csv::*, diff::*, fake::*, genpass::*, http::*, jwt::*, query::*,
+    schema::*, text::*, time::*,
+};
 
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
@@ -22,9 +31,33 @@ pub struct Opts {
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExecutor)]
 pub enum Subcommand {
-    #[command(name = "csv", about = "Convert CSV to JSON")]
+    #[command(name = "csv", about = "Convert, query and process CSV files")]
     Csv(CsvOpts),
 
+    #[command(
+        name = "convert",
+        about = "Convert between JSON, YAML, TOML, JSON5 and NDJSON"
+    )]
+    Convert(ConvertOpts),
+
+    #[command(name = "query", about = "Query JSON/YAML/TOML with a jq-like filter")]
+    Query(QueryOpts),
+
+    #[command(
+        subcommand,
+        about = "Validate documents against a JSON Schema or infer one"
+    )]
+    Schema(SchemaSubCommand),
+
+    #[command(name = "diff", about = "Compare JSON/YAML/TOML documents structurally")]
+    Diff(DiffOpts),
+
+    #[command(name = "patch", about = "Apply an RFC 6902 JSON Patch to a document")]
+    Patch(PatchOpts),
+
+    #[command(name = "fake", about = "Generate fake data from a schema")]
+    Fake(FakeOpts),
+
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
 
@@ -39,6 +72,9 @@ pub enum Subcommand {
 
     #[command(subcommand, about = "Json web token")]
     Jwt(JwtSubCommand),
+
+    #[command(subcommand, about = "Timestamp conversion")]
+    Time(TimeSubCommand),
 }
 
 // 使用 enum_dispatch 实现CmdExecutor, 等价于下面的代码
@@ -81,4 +117,13 @@ mod tests {
         assert_eq!(verify_file("Cargo.toml"), Ok("Cargo.toml".to_string()));
         assert_eq!(verify_file("not-exist"), Err("File does not exist"));
     }
+
+    #[test]
+    fn test_csv_split_rows_must_be_positive() {
+        let parse = |rows: &str| {
+            Opts::try_parse_from(["rcli", "csv", "split", "-i", "Cargo.toml", "--rows", rows])
+        };
+        assert!(parse("2").is_ok());
+        assert!(parse("0").is_err());
+    }
 }
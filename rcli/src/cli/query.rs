@@ -0,0 +1,66 @@
+use clap::Parser;
+use serde_json::Value;
+
+use super::{convert::parse_doc_format, verify_file, DocFormat};
+use crate::{process_query, CmdExecutor, JsonQueryOpt};
+
+#[derive(Debug, Parser)]
+pub struct QueryOpts {
+    // jq 语法的过滤器，例如 '.players[] | select(.kit > 10) | .name'
+    // 只支持 jq 的一个子集，不支持 try / catch、def、del 和 paths
+    pub filter: String,
+
+    // "-" 表示input是从stdin里面读取的数据，可以直接接 rcli csv 的输出
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 输入的格式，不指定时根据扩展名判断，没有扩展名时根据内容判断
+    #[arg(long, value_parser = parse_doc_format)]
+    pub from: Option<DocFormat>,
+
+    // 结果的输出格式，ndjson 表示每个结果输出为一行 json
+    #[arg(long, value_parser = parse_doc_format, default_value = "json")]
+    pub to: DocFormat,
+
+    // 字符串结果直接输出，不加引号
+    #[arg(short, long)]
+    pub raw_output: bool,
+
+    // 紧凑输出，json 输出为一行
+    #[arg(short, long)]
+    pub compact: bool,
+
+    // 以字符串传入变量，过滤器里用 $name 引用，例如 --arg name Dybala
+    #[arg(long, num_args = 2, value_names = ["NAME", "VALUE"])]
+    pub arg: Vec<String>,
+
+    // 以 json 传入变量，例如 --argjson min 10
+    #[arg(long, num_args = 2, value_names = ["NAME", "JSON"])]
+    pub argjson: Vec<String>,
+}
+
+impl CmdExecutor for QueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut vars: Vec<(String, Value)> = self
+            .arg
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), Value::String(pair[1].clone())))
+            .collect();
+        for pair in self.argjson.chunks(2) {
+            let value = serde_json::from_str(&pair[1])
+                .map_err(|e| anyhow::anyhow!("Invalid JSON for --argjson {}: {}", pair[0], e))?;
+            vars.push((pair[0].clone(), value));
+        }
+        let opt = JsonQueryOpt {
+            from: self.from,
+            to: self.to,
+            raw_output: self.raw_output,
+            compact: self.compact,
+            vars,
+        };
+        process_query(&self.input, &self.output, &self.filter, opt)
+    }
+}
@@ -0,0 +1,80 @@
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use super::{
+    convert::parse_doc_format,
+    csv::{parse_report_format, ReportFormat},
+    verify_file, DocFormat,
+};
+use crate::{process_schema_infer, process_schema_validate, CmdExecutor};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum SchemaSubCommand {
+    #[command(
+        name = "validate",
+        about = "Validate a JSON/YAML/TOML document against a JSON Schema"
+    )]
+    Validate(SchemaValidateOpts),
+
+    #[command(name = "infer", about = "Infer a JSON Schema from sample documents")]
+    Infer(SchemaInferOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct SchemaValidateOpts {
+    // "-" 表示input是从stdin里面读取的数据，ndjson 的每一行单独校验
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // JSON Schema 文件，也可以用 yaml / toml 编写，默认按 draft 2020-12 校验
+    #[arg(long, value_parser = verify_file)]
+    pub schema: String,
+
+    // 报告输出的位置，"-" 表示输出到 stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 输入的格式，不指定时根据扩展名判断，没有扩展名时根据内容判断
+    #[arg(long, value_parser = parse_doc_format)]
+    pub from: Option<DocFormat>,
+
+    #[arg(short, long, value_parser = parse_report_format, default_value = "text")]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct SchemaInferOpts {
+    // "-" 表示input是从stdin里面读取的数据，ndjson 的每一行是一个样本
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 输入的格式，不指定时根据扩展名判断，没有扩展名时根据内容判断
+    #[arg(long, value_parser = parse_doc_format)]
+    pub from: Option<DocFormat>,
+
+    // schema 的输出格式
+    #[arg(long, value_parser = parse_doc_format, default_value = "json")]
+    pub to: DocFormat,
+}
+
+impl CmdExecutor for SchemaValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_schema_validate(
+            &self.input,
+            &self.schema,
+            &self.output,
+            self.format,
+            self.from,
+        )
+    }
+}
+
+impl CmdExecutor for SchemaInferOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_schema_infer(&self.input, &self.output, self.from, self.to)
+    }
+}
@@ -0,0 +1,157 @@
+use core::fmt;
+use std::str::FromStr;
+
+use chrono::FixedOffset;
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{process_time_convert, process_time_now, CmdExecutor};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum TimeSubCommand {
+    #[command(about = "Show the current time")]
+    Now(TimeNowOpts),
+
+    #[command(about = "Convert a timestamp or date between formats")]
+    Convert(TimeConvertOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct TimeNowOpts {
+    #[arg(short, long, value_parser = parse_time_format, default_value = "rfc3339")]
+    pub format: TimeFormat,
+
+    // 时区：local, utc, +08:00 或者 IANA 名称如 Asia/Shanghai
+    #[arg(long, value_parser = parse_time_zone, default_value = "local")]
+    pub tz: TimeZoneSpec,
+}
+
+impl CmdExecutor for TimeNowOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let now = process_time_now(self.format, self.tz)?;
+        println!("{}", now);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TimeConvertOpts {
+    // 支持 unix 时间戳、RFC 3339、RFC 2822、"2024-05-01 12:00:00" 以及 "in 3 hours", "2 days ago", "in 1 month" 等
+    pub input: String,
+
+    // 指定数字输入的单位，不指定时根据数字的位数自动判断
+    #[arg(long, value_parser = parse_time_format)]
+    pub from: Option<TimeFormat>,
+
+    #[arg(short, long, value_parser = parse_time_format, default_value = "rfc3339")]
+    pub format: TimeFormat,
+
+    #[arg(long, value_parser = parse_time_zone, default_value = "local")]
+    pub tz: TimeZoneSpec,
+}
+
+impl CmdExecutor for TimeConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let converted = process_time_convert(&self.input, self.from, self.format, self.tz)?;
+        println!("{}", converted);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    Unix,
+    UnixMs,
+    UnixUs,
+    UnixNs,
+    Rfc3339,
+    Rfc2822,
+    Relative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeZoneSpec {
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+    Named(chrono_tz::Tz),
+}
+
+fn parse_time_format(format: &str) -> Result<TimeFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_time_zone(tz: &str) -> Result<TimeZoneSpec, anyhow::Error> {
+    tz.parse()
+}
+
+impl FromStr for TimeFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "unix" | "unix-s" => Ok(TimeFormat::Unix),
+            "unix-ms" => Ok(TimeFormat::UnixMs),
+            "unix-us" => Ok(TimeFormat::UnixUs),
+            "unix-ns" => Ok(TimeFormat::UnixNs),
+            "rfc3339" => Ok(TimeFormat::Rfc3339),
+            "rfc2822" => Ok(TimeFormat::Rfc2822),
+            "relative" => Ok(TimeFormat::Relative),
+            v => anyhow::bail!("Unsupported format: {}", v),
+        }
+    }
+}
+
+impl From<TimeFormat> for &'static str {
+    fn from(format: TimeFormat) -> Self {
+        match format {
+            TimeFormat::Unix => "unix",
+            TimeFormat::UnixMs => "unix-ms",
+            TimeFormat::UnixUs => "unix-us",
+            TimeFormat::UnixNs => "unix-ns",
+            TimeFormat::Rfc3339 => "rfc3339",
+            TimeFormat::Rfc2822 => "rfc2822",
+            TimeFormat::Relative => "relative",
+        }
+    }
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for TimeZoneSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "local" => return Ok(TimeZoneSpec::Local),
+            "utc" | "z" => return Ok(TimeZoneSpec::Utc),
+            _ => {}
+        }
+        if s.starts_with('+') || s.starts_with('-') {
+            let offset = s
+                .parse::<FixedOffset>()
+                .map_err(|_| anyhow::anyhow!("Invalid utc offset: {}", s))?;
+            return Ok(TimeZoneSpec::Fixed(offset));
+        }
+        match s.parse::<chrono_tz::Tz>() {
+            Ok(tz) => Ok(TimeZoneSpec::Named(tz)),
+            Err(_) => anyhow::bail!("Unsupported time zone: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for TimeZoneSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeZoneSpec::Local => write!(f, "local"),
+            TimeZoneSpec::Utc => write!(f, "utc"),
+            TimeZoneSpec::Fixed(offset) => write!(f, "{}", offset),
+            TimeZoneSpec::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
@@ -1,36 +1,170 @@
-use crate::{get_reader, Base64Format};
+use std::io::{IsTerminal, Write};
+
+use crate::{get_reader, get_writer, Base64Format};
 use anyhow::Result;
 use base64::{
-    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    alphabet,
+    engine::{
+        general_purpose::{
+            GeneralPurpose, GeneralPurposeConfig, NO_PAD, STANDARD, STANDARD_NO_PAD, URL_SAFE,
+            URL_SAFE_NO_PAD,
+        },
+        DecodePaddingMode,
+    },
     Engine as _,
 };
 
+// MIME (RFC 2045) 每行最多 76 个字符，用 CRLF 换行
+const MIME_LINE_WIDTH: usize = 76;
+
+const BCRYPT: GeneralPurpose = GeneralPurpose::new(&alphabet::BCRYPT, NO_PAD);
+const CRYPT: GeneralPurpose = GeneralPurpose::new(&alphabet::CRYPT, NO_PAD);
+
+// 宽松模式下有没有 padding 都可以解码
+const LENIENT: GeneralPurposeConfig = GeneralPurposeConfig::new()
+    .with_encode_padding(false)
+    .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+    .with_decode_allow_trailing_bits(true);
+const LENIENT_STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, LENIENT);
+const LENIENT_BCRYPT: GeneralPurpose = GeneralPurpose::new(&alphabet::BCRYPT, LENIENT);
+const LENIENT_CRYPT: GeneralPurpose = GeneralPurpose::new(&alphabet::CRYPT, LENIENT);
+
 pub fn process_encode(input: &str, format: Base64Format) -> Result<String> {
     let mut reader = get_reader(input)?;
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
-
-    let encoded = match format {
-        Base64Format::Standard => STANDARD.encode(buf),
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.encode(buf),
-    };
-    Ok(encoded)
+    Ok(encode(&buf, format))
 }
 
-pub fn process_decode(input: &str, format: Base64Format) -> Result<Vec<u8>> {
+pub fn process_decode(input: &str, format: Base64Format, lenient: bool) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
-
     let mut buf = String::new();
     reader.read_to_string(&mut buf)?;
-    // avoid accidental newlines
-    let buf = buf.trim();
+    decode(&buf, format, lenient)
+}
+
+pub fn encode(data: &[u8], format: Base64Format) -> String {
+    let encoded = engine(format).encode(data);
+    if !matches!(format, Base64Format::Mime) {
+        return encoded;
+    }
+    // base64 的结果都是 ASCII，可以直接按字节切分
+    encoded
+        .as_bytes()
+        .chunks(MIME_LINE_WIDTH)
+        .map(|line| std::str::from_utf8(line).expect("base64 output is ASCII"))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
 
-    let decoded = match format {
-        Base64Format::Standard => STANDARD.decode(buf)?,
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.decode(buf)?,
+// lenient 时忽略所有空白字符，允许缺少 padding，standard 和 urlsafe 两种字母表可以混用
+pub fn decode(text: &str, format: Base64Format, lenient: bool) -> Result<Vec<u8>> {
+    let decoded = if lenient {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        match format {
+            Base64Format::Bcrypt => LENIENT_BCRYPT.decode(text),
+            Base64Format::Crypt => LENIENT_CRYPT.decode(text),
+            _ => LENIENT_STANDARD.decode(text.replace('-', "+").replace('_', "/")),
+        }
+    } else if matches!(format, Base64Format::Mime) {
+        // MIME 的换行不属于编码的内容
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        STANDARD.decode(text)
+    } else {
+        // avoid accidental newlines
+        engine(format).decode(text.trim())
     };
+    decoded.map_err(|e| {
+        anyhow::anyhow!(
+            "Invalid {} base64: {}, try --lenient for wrapped or unpadded input",
+            format,
+            e
+        )
+    })
+}
+
+fn engine(format: Base64Format) -> &'static GeneralPurpose {
+    match format {
+        Base64Format::Standard | Base64Format::Mime => &STANDARD,
+        Base64Format::StandardNoPad => &STANDARD_NO_PAD,
+        // urlsafe 保持原来不带 padding 的行为
+        Base64Format::UrlSafe => &URL_SAFE_NO_PAD,
+        Base64Format::UrlSafePad => &URL_SAFE,
+        Base64Format::Bcrypt => &BCRYPT,
+        Base64Format::Crypt => &CRYPT,
+    }
+}
+
+// 输出到终端时最多显示的字节数
+const PREVIEW_BYTES: usize = 256;
 
-    Ok(decoded)
+// 编码结果是文本，"-" 输出到 stdout
+pub fn write_encoded(encoded: &str, output: &str) -> Result<()> {
+    let mut writer = get_writer(output)?;
+    writeln!(writer, "{}", encoded)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// 解码结果可能是二进制数据：写文件时原样写入，不按扩展名压缩；
+// stdout 不是终端时原样输出，方便重定向或者接管道；
+// 输出到终端时文本直接显示，二进制数据显示十六进制预览
+pub fn write_decoded(decoded: &[u8], output: &str) -> Result<()> {
+    if output != "-" {
+        std::fs::write(output, decoded)?;
+        return Ok(());
+    }
+    let mut stdout = std::io::stdout().lock();
+    if !stdout.is_terminal() {
+        stdout.write_all(decoded)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+    match std::str::from_utf8(decoded) {
+        Ok(text) if is_printable(text) => writeln!(stdout, "{}", text)?,
+        _ => stdout.write_all(hexdump(decoded, PREVIEW_BYTES).as_bytes())?,
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn is_printable(text: &str) -> bool {
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+}
+
+// 和 xxd 一样每行 16 个字节：偏移、按两个字节分组的十六进制、可打印的 ASCII
+pub fn hexdump(bytes: &[u8], limit: usize) -> String {
+    let mut out = String::new();
+    for (i, chunk) in bytes[..bytes.len().min(limit)].chunks(16).enumerate() {
+        let hex: Vec<String> = chunk
+            .chunks(2)
+            .map(|pair| pair.iter().map(|b| format!("{:02x}", b)).collect())
+            .collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:08x}: {:<40} {}\n",
+            i * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    if bytes.len() > limit {
+        out.push_str(&format!(
+            "... {} more bytes, use -o to save the decoded data\n",
+            bytes.len() - limit
+        ));
+    }
+    out
 }
 
 #[cfg(test)]
@@ -44,10 +178,92 @@ mod tests {
         process_encode(input, format).unwrap();
     }
 
+    #[test]
+    fn test_hexdump() {
+        let bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\x01";
+        assert_eq!(
+            hexdump(bytes, 256),
+            "00000000: 8950 4e47 0d0a 1a0a 0000 000d 4948 4452  .PNG........IHDR\n\
+             00000010: 0001                                     ..\n"
+        );
+        assert_eq!(
+            hexdump(bytes, 4),
+            "00000000: 8950 4e47                                .PNG\n\
+             ... 14 more bytes, use -o to save the decoded data\n"
+        );
+    }
+
+    #[test]
+    fn test_process_decode_keeps_raw_bytes() {
+        // 以 BZh 开头的 base64 文本不会被当成 bzip2
+        assert_eq!(
+            process_decode("fixtures/bzh.b64", Base64Format::Standard, false).unwrap(),
+            b"\x05\x98yrli"
+        );
+
+        // 压缩文件编码后再解码得到原来的压缩数据，而不是解压后的内容
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.txt.gz");
+        let path = path.to_str().unwrap();
+        let mut writer = crate::get_writer(path).unwrap();
+        writer.write_all(b"hello world\n").unwrap();
+        writer.finish().unwrap();
+        let encoded = process_encode(path, Base64Format::Standard).unwrap();
+        assert_eq!(
+            decode(&encoded, Base64Format::Standard, false).unwrap(),
+            std::fs::read(path).unwrap()
+        );
+    }
+
     #[test]
     fn test_process_decode() {
         let input = "fixtures/b64.txt";
         let format = Base64Format::UrlSafe;
-        process_decode(input, format).unwrap();
+        process_decode(input, format, false).unwrap();
+    }
+
+    #[test]
+    fn test_base64_variants() {
+        let data = b"\xfb\xff\xfe rcli?";
+        let cases = [
+            (Base64Format::Standard, "+//+IHJjbGk/"),
+            (Base64Format::StandardNoPad, "+//+IHJjbGk/"),
+            (Base64Format::UrlSafe, "-__-IHJjbGk_"),
+            (Base64Format::UrlSafePad, "-__-IHJjbGk_"),
+            (Base64Format::Bcrypt, "8998GFHhZEi9"),
+            (Base64Format::Crypt, "yzzy657XP4Yz"),
+        ];
+        for (format, expected) in cases {
+            let encoded = encode(data, format);
+            assert_eq!(encoded, expected, "{}", format);
+            assert_eq!(decode(&encoded, format, false).unwrap(), data);
+        }
+        assert_eq!(encode(b"rc", Base64Format::Standard), "cmM=");
+        assert_eq!(encode(b"rc", Base64Format::StandardNoPad), "cmM");
+        assert_eq!(encode(b"rc", Base64Format::UrlSafe), "cmM");
+        assert_eq!(encode(b"rc", Base64Format::UrlSafePad), "cmM=");
+        assert!(decode("cmM", Base64Format::Standard, false).is_err());
+    }
+
+    #[test]
+    fn test_base64_mime_and_lenient() {
+        let data: Vec<u8> = (0..=255).collect();
+        let encoded = encode(&data, Base64Format::Mime);
+        let lines: Vec<&str> = encoded.split("\r\n").collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[..4].iter().all(|line| line.len() == 76));
+        assert_eq!(decode(&encoded, Base64Format::Mime, false).unwrap(), data);
+
+        // 类似 PEM 的正文：换行、缩进、缺少 padding、混用 urlsafe 字符
+        let wrapped = "  +//+IHJj\n  bGk_\r\n";
+        assert!(decode(wrapped, Base64Format::Standard, false).is_err());
+        assert_eq!(
+            decode(wrapped, Base64Format::Standard, true).unwrap(),
+            b"\xfb\xff\xfe rcli?"
+        );
+        assert_eq!(
+            decode("cmM", Base64Format::UrlSafePad, true).unwrap(),
+            b"rc"
+        );
     }
 }
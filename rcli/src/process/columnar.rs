@@ -0,0 +1,487 @@
+use std::{fs::File, sync::Arc};
+
+use anyhow::Result;
+use arrow_array::{
+    builder::{
+        BooleanBuilder, Date32Builder, Float64Builder, Int64Builder, StringBuilder,
+        TimestampMillisecondBuilder,
+    },
+    ArrayRef, RecordBatch, RecordBatchReader,
+};
+use arrow_cast::display::{ArrayFormatter, FormatOptions};
+use arrow_ipc::{
+    writer::{FileWriter, IpcWriteOptions},
+    CompressionType,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::NaiveDate;
+use csv::WriterBuilder;
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::{BrotliLevel, Compression as ParquetCompression, GzipLevel, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde_json::Value;
+
+use super::{
+    csv_convert::ascii_byte,
+    csv_from_json::cell_to_string,
+    csv_types::{parse_date, parse_datetime, ColumnType},
+    record_writer::{RecordWriter, WriterOpt},
+};
+use crate::{
+    cli::{Compression, OutputFormat},
+    finish_csv, get_writer, OutputWriter,
+};
+
+// 按 row group 写出 parquet / arrow，每攒够 row_group_size 行就写出一个 record batch
+pub(crate) struct ColumnarWriter {
+    format: OutputFormat,
+    opt: WriterOpt,
+    // 声明的列类型，None 时根据第一个 row group 推断
+    columns: Option<Vec<(String, ColumnType)>>,
+    // 第一次写出时才创建，之前输出先放在 output 里
+    output: Option<OutputWriter>,
+    sink: Option<OpenSink>,
+    rows: Vec<Value>,
+}
+
+enum Sink {
+    Parquet(ArrowWriter<OutputWriter>),
+    Arrow(FileWriter<OutputWriter>),
+}
+
+// 写出的文件和它的 schema
+struct OpenSink {
+    sink: Sink,
+    schema: SchemaRef,
+}
+
+impl ColumnarWriter {
+    pub(crate) fn new(
+        format: OutputFormat,
+        output: OutputWriter,
+        columns: Option<Vec<(String, ColumnType)>>,
+        opt: &WriterOpt,
+    ) -> Self {
+        Self {
+            format,
+            opt: opt.clone(),
+            columns,
+            output: Some(output),
+            sink: None,
+            rows: Vec::new(),
+        }
+    }
+
+    fn flush_rows(&mut self) -> Result<()> {
+        if self.sink.is_none() {
+            let fields = self.resolve_fields();
+            self.sink = Some(self.open_sink(fields)?);
+        }
+        let Some(OpenSink { sink, schema }) = self.sink.as_mut() else {
+            unreachable!("sink is created above");
+        };
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let batch = build_batch(schema, &self.rows)?;
+        self.rows.clear();
+        match sink {
+            Sink::Parquet(writer) => {
+                writer.write(&batch)?;
+                // 每个 batch 单独成为一个 row group
+                writer.flush()?;
+            }
+            Sink::Arrow(writer) => writer.write(&batch)?,
+        }
+        Ok(())
+    }
+
+    fn resolve_fields(&self) -> Vec<Field> {
+        let columns = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => infer_columns(&self.rows),
+        };
+        columns
+            .into_iter()
+            .map(|(name, ty)| {
+                let data_type = arrow_type(&name, ty, &self.rows);
+                Field::new(name, data_type, true)
+            })
+            .collect()
+    }
+
+    fn open_sink(&mut self, fields: Vec<Field>) -> Result<OpenSink> {
+        let schema = Arc::new(Schema::new(fields));
+        let output = self
+            .output
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Output is already closed"))?;
+        let sink = match self.format {
+            OutputFormat::Parquet => {
+                let props = WriterProperties::builder()
+                    .set_compression(parquet_compression(self.opt.compression))
+                    .set_max_row_group_row_count(Some(self.opt.row_group_size.max(1)))
+                    .build();
+                Sink::Parquet(ArrowWriter::try_new(output, schema.clone(), Some(props))?)
+            }
+            OutputFormat::Arrow => {
+                let options = IpcWriteOptions::default()
+                    .try_with_compression(ipc_compression(self.opt.compression)?)?;
+                Sink::Arrow(FileWriter::try_new_with_options(output, &schema, options)?)
+            }
+            format => anyhow::bail!("{} is not a columnar format", format),
+        };
+        Ok(OpenSink { sink, schema })
+    }
+}
+
+impl RecordWriter for ColumnarWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        self.rows.push(record.clone());
+        if self.rows.len() >= self.opt.row_group_size.max(1) {
+            self.flush_rows()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        // 没有数据时也要写出只有 schema 的文件
+        self.flush_rows()?;
+        let output = match self.sink.take().map(|s| s.sink) {
+            Some(Sink::Parquet(writer)) => writer.into_inner()?,
+            Some(Sink::Arrow(writer)) => writer.into_inner()?,
+            None => unreachable!("sink is created by flush_rows"),
+        };
+        output.finish()
+    }
+}
+
+// 没有声明类型时，按第一个 row group 里 json 值的类型推断，嵌套的对象和数组写成 json 字符串
+fn infer_columns(rows: &[Value]) -> Vec<(String, ColumnType)> {
+    let mut columns: Vec<(String, Option<ColumnType>)> = Vec::new();
+    for row in rows {
+        let Value::Object(map) = row else {
+            continue;
+        };
+        for (key, value) in map {
+            let ty = match value {
+                Value::Null => None,
+                Value::Bool(_) => Some(ColumnType::Boolean),
+                Value::Number(n) if n.is_i64() => Some(ColumnType::Integer),
+                Value::Number(_) => Some(ColumnType::Float),
+                _ => Some(ColumnType::String),
+            };
+            match columns.iter_mut().find(|(name, _)| name == key) {
+                Some((_, current)) => {
+                    if let Some(ty) = ty {
+                        *current = Some(current.map_or(ty, |t| t.merge(ty)));
+                    }
+                }
+                None => columns.push((key.clone(), ty)),
+            }
+        }
+    }
+    columns
+        .into_iter()
+        .map(|(name, ty)| (name, ty.unwrap_or(ColumnType::String)))
+        .collect()
+}
+
+// 日期列在第一个 row group 里全部只有日期时用 Date32，否则用 Timestamp
+fn arrow_type(name: &str, ty: ColumnType, rows: &[Value]) -> DataType {
+    match ty {
+        ColumnType::Integer => DataType::Int64,
+        ColumnType::Float => DataType::Float64,
+        ColumnType::Boolean => DataType::Boolean,
+        ColumnType::Date => {
+            let date_only = rows
+                .iter()
+                .filter_map(|row| row.get(name).and_then(Value::as_str))
+                .all(|cell| parse_date(cell.trim()).is_some());
+            if date_only {
+                DataType::Date32
+            } else {
+                DataType::Timestamp(TimeUnit::Millisecond, None)
+            }
+        }
+        ColumnType::String => DataType::Utf8,
+    }
+}
+
+fn build_batch(schema: &SchemaRef, rows: &[Value]) -> Result<RecordBatch> {
+    let arrays = schema
+        .fields()
+        .iter()
+        .map(|field| build_array(field, rows))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), arrays)?)
+}
+
+fn build_array(field: &Field, rows: &[Value]) -> Result<ArrayRef> {
+    let name = field.name();
+    let values = rows
+        .iter()
+        .map(|row| row.get(name).filter(|v| !v.is_null()));
+    // 推断的类型基于样本，之后的行可能无法转换
+    let mismatch = |value: &Value| {
+        anyhow::anyhow!(
+            "Can not write {} to {} column {}, try a larger --infer-sample or declare the type in --schema",
+            value,
+            field.data_type(),
+            name
+        )
+    };
+    let array: ArrayRef = match field.data_type() {
+        DataType::Int64 => {
+            let mut builder = Int64Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Some(v) => builder.append_value(v.as_i64().ok_or_else(|| mismatch(v))?),
+                    None => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Float64 => {
+            let mut builder = Float64Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Some(v) => builder.append_value(v.as_f64().ok_or_else(|| mismatch(v))?),
+                    None => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Boolean => {
+            let mut builder = BooleanBuilder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Some(v) => builder.append_value(v.as_bool().ok_or_else(|| mismatch(v))?),
+                    None => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Date32 => {
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+            let mut builder = Date32Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Some(v) => {
+                        let date = v
+                            .as_str()
+                            .and_then(|s| parse_date(s.trim()))
+                            .ok_or_else(|| mismatch(v))?;
+                        builder.append_value((date - epoch).num_days() as i32);
+                    }
+                    None => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, None) => {
+            let mut builder = TimestampMillisecondBuilder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Some(v) => {
+                        let s = v.as_str().map(str::trim).ok_or_else(|| mismatch(v))?;
+                        let datetime = parse_datetime(s)
+                            .or_else(|| parse_date(s).and_then(|d| d.and_hms_opt(0, 0, 0)))
+                            .ok_or_else(|| mismatch(v))?;
+                        builder.append_value(datetime.and_utc().timestamp_millis());
+                    }
+                    None => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        _ => {
+            let mut builder = StringBuilder::with_capacity(rows.len(), rows.len() * 16);
+            for value in values {
+                match value {
+                    Some(v) => builder.append_value(cell_to_string(v)),
+                    None => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    };
+    Ok(array)
+}
+
+// parquet 默认使用 snappy
+fn parquet_compression(compression: Option<Compression>) -> ParquetCompression {
+    match compression.unwrap_or(Compression::Snappy) {
+        Compression::None => ParquetCompression::UNCOMPRESSED,
+        Compression::Snappy => ParquetCompression::SNAPPY,
+        Compression::Gzip => ParquetCompression::GZIP(GzipLevel::default()),
+        Compression::Zstd => ParquetCompression::ZSTD(ZstdLevel::default()),
+        Compression::Lz4 => ParquetCompression::LZ4_RAW,
+        Compression::Brotli => ParquetCompression::BROTLI(BrotliLevel::default()),
+    }
+}
+
+// arrow 默认不压缩
+fn ipc_compression(compression: Option<Compression>) -> Result<Option<CompressionType>> {
+    match compression {
+        None | Some(Compression::None) => Ok(None),
+        Some(Compression::Lz4) => Ok(Some(CompressionType::LZ4_FRAME)),
+        Some(Compression::Zstd) => Ok(Some(CompressionType::ZSTD)),
+        Some(c) => anyhow::bail!("Arrow only supports lz4 and zstd compression, got: {}", c),
+    }
+}
+
+// 按 record batch 读取 parquet 写成 csv，不会一次读入整个文件
+pub fn process_csv_from_parquet(
+    input: &str,
+    output: &str,
+    delimiter: char,
+    batch_size: usize,
+) -> Result<()> {
+    if input == "-" {
+        anyhow::bail!("Reading parquet from stdin is not supported");
+    }
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(input)?)?
+        .with_batch_size(batch_size.max(1))
+        .build()?;
+    let mut writer = WriterBuilder::new()
+        .delimiter(ascii_byte(delimiter, "delimiter")?)
+        .from_writer(get_writer(output)?);
+    let headers: Vec<String> = reader
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    writer.write_record(&headers)?;
+
+    let options = FormatOptions::default().with_null("");
+    for batch in reader {
+        let batch = batch?;
+        let formatters = batch
+            .columns()
+            .iter()
+            .map(|array| ArrayFormatter::try_new(array.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()?;
+        for row in 0..batch.num_rows() {
+            writer.write_record(formatters.iter().map(|f| f.value(row).to_string()))?;
+        }
+    }
+    finish_csv(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Array, Date32Array, Int64Array};
+    use arrow_ipc::reader::FileReader;
+    use serde_json::json;
+
+    fn rows() -> Vec<Value> {
+        vec![
+            json!({"name": "Alice", "kit": 10, "height": 1.72, "active": true, "dob": "1990-04-18"}),
+            json!({"name": "Bob", "kit": null, "height": 1.8, "active": false, "dob": "1992-11-10"}),
+            json!({"name": "Carol", "kit": 7, "height": 2, "active": null, "dob": null}),
+        ]
+    }
+
+    fn columns() -> Vec<(String, ColumnType)> {
+        vec![
+            ("name".to_string(), ColumnType::String),
+            ("kit".to_string(), ColumnType::Integer),
+            ("height".to_string(), ColumnType::Float),
+            ("active".to_string(), ColumnType::Boolean),
+            ("dob".to_string(), ColumnType::Date),
+        ]
+    }
+
+    fn write(format: OutputFormat, path: &str, opt: &WriterOpt) {
+        let mut writer = Box::new(ColumnarWriter::new(
+            format,
+            get_writer(path).unwrap(),
+            Some(columns()),
+            opt,
+        ));
+        for row in rows() {
+            writer.write_record(&row).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let parquet = dir.path().join("players.parquet");
+        let csv = dir.path().join("players.csv");
+        let opt = WriterOpt {
+            compression: Some(Compression::Zstd),
+            row_group_size: 2,
+            ..Default::default()
+        };
+        write(OutputFormat::Parquet, parquet.to_str().unwrap(), &opt);
+
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&parquet).unwrap()).unwrap();
+        // 每 2 行一个 row group
+        assert_eq!(builder.metadata().num_row_groups(), 2);
+        let schema = builder.schema().clone();
+        assert_eq!(schema.field(1).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(4).data_type(), &DataType::Date32);
+
+        process_csv_from_parquet(parquet.to_str().unwrap(), csv.to_str().unwrap(), ',', 1).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&csv).unwrap(),
+            "name,kit,height,active,dob\nAlice,10,1.72,true,1990-04-18\nBob,,1.8,false,1992-11-10\nCarol,7,2.0,,\n"
+        );
+    }
+
+    #[test]
+    fn test_arrow_with_inferred_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("players.arrow");
+        let opt = WriterOpt {
+            compression: Some(Compression::Lz4),
+            ..Default::default()
+        };
+        let mut writer = Box::new(ColumnarWriter::new(
+            OutputFormat::Arrow,
+            get_writer(path.to_str().unwrap()).unwrap(),
+            None,
+            &opt,
+        ));
+        for row in rows() {
+            writer.write_record(&row).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        let batch = &batches[0];
+        let kit = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(kit.value(2), 7);
+        assert!(kit.is_null(1));
+        // 没有声明类型时日期只是字符串
+        assert_eq!(batch.schema().field(4).data_type(), &DataType::Utf8);
+    }
+
+    #[test]
+    fn test_mismatched_value() {
+        let schema = Arc::new(Schema::new(vec![Field::new("dob", DataType::Date32, true)]));
+        let batch = build_batch(&schema, &[json!({"dob": "2020-01-02"})]).unwrap();
+        let dob = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Date32Array>()
+            .unwrap();
+        assert_eq!(dob.value(0), 18263);
+        assert!(build_batch(&schema, &[json!({"dob": "soon"})]).is_err());
+        assert!(ipc_compression(Some(Compression::Snappy)).is_err());
+    }
+}
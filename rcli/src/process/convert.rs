@@ -0,0 +1,215 @@
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use serde_json::{Map, Number, Value};
+
+use super::record_writer::strip_nulls;
+use crate::{cli::DocFormat, get_data_reader, get_writer};
+
+pub struct ConvertOpt {
+    // 输入的格式，None 表示根据扩展名或者内容判断
+    pub from: Option<DocFormat>,
+    pub compact: bool,
+    pub sort_keys: bool,
+}
+
+pub fn process_convert(input: &str, output: &str, to: DocFormat, opt: ConvertOpt) -> Result<()> {
+    let value = load_document(input, opt.from)?;
+    let value = if opt.sort_keys {
+        sort_keys(value)
+    } else {
+        value
+    };
+    let content = to_document(&value, to, !opt.compact)?;
+    let mut writer = get_writer(output)?;
+    writer.write_all(content.as_bytes())?;
+    writer.finish()?;
+    Ok(())
+}
+
+// 读取 json / json5 / yaml / toml / ndjson 文档，format 为 None 时根据扩展名或者内容判断
+pub fn load_document(input: &str, format: Option<DocFormat>) -> Result<Value> {
+    Ok(load_document_with_format(input, format)?.0)
+}
+
+// 同时返回实际使用的格式，ndjson 的每一行需要单独处理时使用
+pub fn load_document_with_format(
+    input: &str,
+    format: Option<DocFormat>,
+) -> Result<(Value, DocFormat)> {
+    let mut content = String::new();
+    get_data_reader(input)?.read_to_string(&mut content)?;
+    let format = format
+        .or_else(|| DocFormat::from_path(input))
+        .unwrap_or_else(|| detect_format(&content));
+    Ok((parse_document(&content, format)?, format))
+}
+
+pub fn parse_document(content: &str, format: DocFormat) -> Result<Value> {
+    let value = match format {
+        DocFormat::Json => serde_json::from_str(content)?,
+        DocFormat::Json5 => json5::from_str(content)?,
+        DocFormat::Yaml => serde_yaml::from_str(content)?,
+        DocFormat::Toml => toml_to_json(toml::from_str(content)?),
+        DocFormat::Ndjson => Value::Array(
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()?,
+        ),
+    };
+    Ok(value)
+}
+
+// 按照 json、ndjson、toml、json5、yaml 的顺序尝试，yaml 几乎能解析任何文本，所以放在最后
+pub fn detect_format(content: &str) -> DocFormat {
+    if serde_json::from_str::<Value>(content).is_ok() {
+        return DocFormat::Json;
+    }
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    if lines.clone().count() > 1 && lines.all(|line| serde_json::from_str::<Value>(line).is_ok()) {
+        return DocFormat::Ndjson;
+    }
+    if toml::from_str::<toml::Value>(content).is_ok() {
+        return DocFormat::Toml;
+    }
+    if json5::from_str::<Value>(content).is_ok() {
+        return DocFormat::Json5;
+    }
+    DocFormat::Yaml
+}
+
+// pretty 为 false 时 json / json5 / ndjson 输出为一行，toml 的数组不换行，yaml 没有紧凑格式
+pub fn to_document(value: &Value, format: DocFormat, pretty: bool) -> Result<String> {
+    let mut content = match format {
+        DocFormat::Json if pretty => serde_json::to_string_pretty(value)?,
+        DocFormat::Json => serde_json::to_string(value)?,
+        // json 是合法的 json5，json5 的序列化没有缩进
+        DocFormat::Json5 if pretty => serde_json::to_string_pretty(value)?,
+        DocFormat::Json5 => json5::to_string(value)?,
+        DocFormat::Yaml => serde_yaml::to_string(value)?,
+        DocFormat::Toml => {
+            // TOML 没有 null，顶层必须是 table
+            let value = strip_nulls(value.clone());
+            if !value.is_object() {
+                anyhow::bail!("TOML requires a table at the top level");
+            }
+            if pretty {
+                toml::to_string_pretty(&value)?
+            } else {
+                toml::to_string(&value)?
+            }
+        }
+        DocFormat::Ndjson => match value {
+            Value::Array(items) => items
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n"),
+            value => serde_json::to_string(value)?,
+        },
+    };
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+// 递归地按 key 排序
+pub fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, sort_keys(v)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        v => v,
+    }
+}
+
+// toml 的日期时间转换为字符串，其他类型一一对应
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(r#"{"a": 1}"#), DocFormat::Json);
+        assert_eq!(detect_format("{\"a\": 1}\n{\"a\": 2}\n"), DocFormat::Ndjson);
+        assert_eq!(detect_format("a = 1\n[b]\nc = \"x\"\n"), DocFormat::Toml);
+        assert_eq!(
+            detect_format("{a: 1, // comment\n b: 'x',}"),
+            DocFormat::Json5
+        );
+        assert_eq!(detect_format("a: 1\nb:\n  - x\n"), DocFormat::Yaml);
+        assert_eq!(
+            DocFormat::from_path("data.jsonl.gz"),
+            Some(DocFormat::Ndjson)
+        );
+        assert_eq!(DocFormat::from_path("Makefile"), None);
+    }
+
+    #[test]
+    fn test_convert_between_formats() {
+        let yaml =
+            "name: rcli\nversion: 1\nserver:\n  port: 8080\n  hosts: [a, b]\nnothing: null\n";
+        let value = parse_document(yaml, DocFormat::Yaml).unwrap();
+
+        let toml = to_document(&value, DocFormat::Toml, true).unwrap();
+        let back = parse_document(&toml, DocFormat::Toml).unwrap();
+        assert_eq!(
+            back,
+            json!({"name": "rcli", "version": 1, "server": {"port": 8080, "hosts": ["a", "b"]}})
+        );
+
+        assert_eq!(
+            to_document(&value, DocFormat::Json, false).unwrap(),
+            "{\"name\":\"rcli\",\"version\":1,\"server\":{\"port\":8080,\"hosts\":[\"a\",\"b\"]},\"nothing\":null}\n"
+        );
+        let json5 = to_document(&value, DocFormat::Json5, false).unwrap();
+        assert_eq!(parse_document(&json5, DocFormat::Json5).unwrap(), value);
+
+        let rows = json!([{"a": 1}, {"a": 2}]);
+        let ndjson = to_document(&rows, DocFormat::Ndjson, true).unwrap();
+        assert_eq!(ndjson, "{\"a\":1}\n{\"a\":2}\n");
+        assert_eq!(parse_document(&ndjson, DocFormat::Ndjson).unwrap(), rows);
+        assert!(to_document(&rows, DocFormat::Toml, true).is_err());
+    }
+
+    #[test]
+    fn test_toml_datetime_and_sort_keys() {
+        let value = parse_document("b = 1979-05-27T07:32:00Z\na = 1\n", DocFormat::Toml).unwrap();
+        assert_eq!(value["b"], "1979-05-27T07:32:00Z");
+        let sorted = sort_keys(json!({"b": {"d": 1, "c": 2}, "a": [{"z": 1, "y": 2}]}));
+        assert_eq!(
+            serde_json::to_string(&sorted).unwrap(),
+            r#"{"a":[{"y":2,"z":1}],"b":{"c":2,"d":1}}"#
+        );
+    }
+}
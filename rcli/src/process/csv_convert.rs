@@ -1,38 +1,540 @@
-use std::fs;
+use std::io::Read;
 
-use csv::Reader;
-use serde::{Deserialize, Serialize};
+use csv::{Reader, ReaderBuilder, StringRecord};
 use serde_json::Value;
 
-use crate::cli::OutputFormat;
-
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> anyhow::Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        ret.push(json_value);
-    }
-    let contents = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
-        // OutputFormat::Toml => toml::to_string(&ret)?,
+use super::{
+    columnar::ColumnarWriter,
+    csv_filter::RowFilter,
+    csv_nest::{nest_record, parse_header_paths, PathSegment},
+    csv_types::{infer_column_types, ColumnType, CsvSchema},
+    encoding::decode_reader,
+    record_writer::{new_record_writer, RecordWriter, WriterOpt},
+    spreadsheet::{is_spreadsheet, read_sheet, sheet_reader_builder},
+};
+use crate::{
+    cli::{InputEncoding, OutputFormat},
+    get_data_reader, get_writer,
+};
+
+pub(crate) type CsvReader = Reader<Box<dyn Read>>;
+
+pub struct CsvReaderOpt {
+    pub delimiter: char,
+    pub header: bool,
+    pub columns: Option<Vec<String>>,
+    pub quote: char,
+    pub escape: Option<char>,
+    pub comment: Option<char>,
+    pub encoding: InputEncoding,
+    // 输入为 xlsx/ods 时读取的工作表，None 表示第一个
+    pub sheet: Option<String>,
+}
+
+pub struct CsvConvertOpt {
+    pub reader: CsvReaderOpt,
+    pub writer: WriterOpt,
+    pub infer_types: bool,
+    // 用于推断类型的样本行数
+    pub infer_sample: usize,
+    // 强制指定列类型的 schema 文件
+    pub schema: Option<String>,
+    // 按分隔符把表头构造成嵌套的对象，None 表示输出扁平的对象
+    pub nest: Option<String>,
+    pub select: Option<Vec<String>>,
+    pub exclude: Vec<String>,
+    // (原列名, 新列名)
+    pub rename: Vec<(String, String)>,
+    // 行过滤表达式，语法见 RowFilter
+    pub filter: Option<String>,
+}
+
+// 列的选择、排除和重命名
+struct Projection {
+    indices: Vec<usize>,
+    headers: StringRecord,
+}
+
+// 单元格的输出方式：原样输出字符串 / 按推断的类型转换 / 按 schema 声明的类型转换
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CellType {
+    Raw,
+    Inferred(ColumnType),
+    Declared(ColumnType),
+}
+
+impl Default for CsvConvertOpt {
+    fn default() -> Self {
+        Self {
+            reader: CsvReaderOpt::default(),
+            writer: WriterOpt::default(),
+            infer_types: false,
+            infer_sample: 1000,
+            schema: None,
+            nest: None,
+            select: None,
+            exclude: Vec::new(),
+            rename: Vec::new(),
+            filter: None,
+        }
+    }
+}
+
+impl Default for CsvReaderOpt {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header: true,
+            columns: None,
+            quote: '"',
+            escape: None,
+            comment: None,
+            encoding: InputEncoding::Auto,
+            sheet: None,
+        }
+    }
+}
+
+pub fn process_csv(
+    input: &str,
+    output: String,
+    format: OutputFormat,
+    opt: CsvConvertOpt,
+) -> anyhow::Result<()> {
+    let (mut reader, sheet_types) = open_reader(input, &opt.reader)?;
+    let source_headers = resolve_headers(&mut reader, &opt.reader)?;
+    // 过滤表达式基于原始的列名，所以在选择列之前执行
+    let filter = match &opt.filter {
+        Some(expr) => Some(RowFilter::compile(expr, &source_headers)?),
+        None => None,
+    };
+    let projection = Projection::new(&source_headers, &opt)?;
+    let headers = projection.headers.clone();
+    let sheet_types = sheet_types.map(|types| projection.select_types(&types));
+    let mut records = reader.into_records().filter_map(|record| match record {
+        Ok(record) if filter.as_ref().is_none_or(|f| f.matches(&record)) => {
+            Some(Ok(projection.apply(&record)))
+        }
+        Ok(_) => None,
+        Err(e) => Some(Err(e)),
+    });
+    // 推断类型只需要前 infer_sample 行，先缓存起来，其余的行边读边写
+    let sample = if opt.infer_types {
+        records
+            .by_ref()
+            .take(opt.infer_sample)
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+    let mut types = resolve_cell_types(&source_headers, &projection, &sample, sheet_types, &opt)?;
+    let paths = match &opt.nest {
+        Some(separator) => Some(parse_header_paths(headers.iter(), separator)?),
+        None => None,
+    };
+
+    let mut writer: Box<dyn RecordWriter> = if format.is_columnar() {
+        if paths.is_some() {
+            anyhow::bail!("--nest is not supported for {} output", format);
+        }
+        // 列式格式使用推断或者 schema 声明的列类型，没有类型的列写成字符串
+        let columns = headers
+            .iter()
+            .zip(&types)
+            .map(|(header, ty)| {
+                let ty = match *ty {
+                    CellType::Raw => ColumnType::String,
+                    CellType::Inferred(ty) | CellType::Declared(ty) => ty,
+                };
+                (header.to_string(), ty)
+            })
+            .collect();
+        Box::new(ColumnarWriter::new(
+            format,
+            get_writer(&output)?,
+            Some(columns),
+            &opt.writer,
+        ))
+    } else {
+        new_record_writer(format, &output, &opt.writer)?
     };
+    for record in sample.into_iter().map(Ok).chain(records) {
+        let record = record?;
+        let cells = convert_cells(&headers, &record, &mut types)?;
+        let value = build_value(&headers, cells, paths.as_deref());
+        writer.write_record(&value)?;
+    }
+    writer.finish()
+}
+
+impl Projection {
+    fn new(headers: &StringRecord, opt: &CsvConvertOpt) -> anyhow::Result<Self> {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| anyhow::anyhow!("Column not found in csv: {}", name))
+        };
+        let mut indices = match &opt.select {
+            Some(columns) => columns
+                .iter()
+                .map(|c| find(c))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            None => (0..headers.len()).collect(),
+        };
+        for column in &opt.exclude {
+            let idx = find(column)?;
+            indices.retain(|&i| i != idx);
+        }
+
+        let mut names: Vec<String> = indices.iter().map(|&i| headers[i].to_string()).collect();
+        for (from, to) in &opt.rename {
+            let idx = find(from)?;
+            match indices.iter().position(|&i| i == idx) {
+                Some(pos) => names[pos] = to.clone(),
+                None => anyhow::bail!("Renamed column is not selected: {}", from),
+            }
+        }
+        Ok(Self {
+            indices,
+            headers: StringRecord::from(names),
+        })
+    }
+
+    // 工作表的列类型也要按选择的列调整
+    fn select_types(&self, types: &[ColumnType]) -> Vec<ColumnType> {
+        self.indices
+            .iter()
+            .map(|&i| types.get(i).copied().unwrap_or(ColumnType::String))
+            .collect()
+    }
+
+    fn apply(&self, record: &StringRecord) -> StringRecord {
+        let mut projected: StringRecord = self
+            .indices
+            .iter()
+            .map(|&i| record.get(i).unwrap_or_default())
+            .collect();
+        // 保留行号，用于报错
+        projected.set_position(record.position().cloned());
+        projected
+    }
+}
+
+fn resolve_cell_types(
+    source_headers: &StringRecord,
+    projection: &Projection,
+    sample: &[StringRecord],
+    sheet_types: Option<Vec<ColumnType>>,
+    opt: &CsvConvertOpt,
+) -> anyhow::Result<Vec<CellType>> {
+    // 工作表的单元格本身带有类型，不需要再推断
+    let mut types = if let Some(sheet_types) = sheet_types {
+        sheet_types.into_iter().map(CellType::Inferred).collect()
+    } else if opt.infer_types {
+        infer_column_types(projection.headers.len(), sample)
+            .into_iter()
+            .map(CellType::Inferred)
+            .collect()
+    } else {
+        vec![CellType::Raw; projection.headers.len()]
+    };
+
+    // 和 --where 一样，schema 使用原始的列名，不受 --rename 影响，没有选择的列直接忽略
+    if let Some(schema) = &opt.schema {
+        let schema = CsvSchema::load(schema)?;
+        for (column, spec) in schema.columns {
+            let Some(idx) = source_headers.iter().position(|h| h == column) else {
+                if spec.rule().required {
+                    anyhow::bail!("Schema column not found in csv: {}", column);
+                }
+                continue;
+            };
+            let Some(pos) = projection.indices.iter().position(|&i| i == idx) else {
+                continue;
+            };
+            if let Some(ty) = spec.column_type() {
+                types[pos] = CellType::Declared(ty);
+            }
+        }
+    }
+    Ok(types)
+}
+
+// 推断的类型基于样本，样本之外的行无法转换时给出警告，这一列之后的单元格都按字符串输出
+fn convert_cells(
+    headers: &StringRecord,
+    record: &StringRecord,
+    types: &mut [CellType],
+) -> anyhow::Result<Vec<Value>> {
+    let line = record.position().map(|p| p.line()).unwrap_or_default();
+    let mut cells = Vec::with_capacity(headers.len());
+    for ((header, cell), ty) in headers.iter().zip(record.iter()).zip(types.iter_mut()) {
+        let value = match *ty {
+            CellType::Raw => Value::String(cell.to_string()),
+            CellType::Inferred(inferred) => match inferred.convert(cell) {
+                Some(value) => value,
+                None => {
+                    eprintln!(
+                        "Warning: line {}, column {}: can not parse {:?} as inferred {}, \
+                         writing the rest of the column as string, \
+                         try a larger --infer-sample or declare the type in --schema",
+                        line, header, cell, inferred
+                    );
+                    *ty = CellType::Raw;
+                    Value::String(cell.to_string())
+                }
+            },
+            CellType::Declared(ty) => ty.convert(cell).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Line {}, column {}: can not parse {:?} as {}",
+                    line,
+                    header,
+                    cell,
+                    ty
+                )
+            })?,
+        };
+        cells.push(value);
+    }
+    Ok(cells)
+}
 
-    fs::write(output, contents)?;
-    Ok(())
+fn build_value(
+    headers: &StringRecord,
+    cells: Vec<Value>,
+    paths: Option<&[Vec<PathSegment>]>,
+) -> Value {
+    match paths {
+        Some(paths) => nest_record(cells, paths),
+        None => Value::Object(
+            headers
+                .iter()
+                .zip(cells)
+                .map(|(h, v)| (h.to_string(), v))
+                .collect(),
+        ),
+    }
+}
+
+pub(crate) fn build_reader(input: &str, opt: &CsvReaderOpt) -> anyhow::Result<CsvReader> {
+    Ok(open_reader(input, opt)?.0)
+}
+
+// xlsx/ods 等工作表先转换为 csv，同时返回单元格的类型
+pub(crate) fn open_reader(
+    input: &str,
+    opt: &CsvReaderOpt,
+) -> anyhow::Result<(CsvReader, Option<Vec<ColumnType>>)> {
+    let mut builder = ReaderBuilder::new();
+    builder
+        .delimiter(ascii_byte(opt.delimiter, "delimiter")?)
+        .has_headers(opt.header)
+        .quote(ascii_byte(opt.quote, "quote")?);
+    if let Some(escape) = opt.escape {
+        builder.escape(Some(ascii_byte(escape, "escape")?));
+    }
+    if let Some(comment) = opt.comment {
+        builder.comment(Some(ascii_byte(comment, "comment")?));
+    }
+    if is_spreadsheet(input) {
+        let (reader, types) = read_sheet(input, opt)?;
+        return Ok((sheet_reader_builder(opt).from_reader(reader), Some(types)));
+    }
+    let reader = decode_reader(get_data_reader(input)?, opt.encoding)?;
+    Ok((builder.from_reader(reader), None))
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct Player {
-    name: String,
-    position: String,
-    #[serde(rename = "DOB")]
-    dob: String,
-    nationality: String,
-    #[serde(rename = "Kit Number")]
-    kit: u8,
+// 列名的优先级：--columns > 文件表头 > 自动生成的 col1, col2, ...
+pub(crate) fn resolve_headers<R: Read>(
+    reader: &mut Reader<R>,
+    opt: &CsvReaderOpt,
+) -> anyhow::Result<StringRecord> {
+    if let Some(columns) = &opt.columns {
+        return Ok(StringRecord::from(columns.clone()));
+    }
+    // has_headers(false) 时 headers() 返回的是第一行数据，但并不会消费它
+    let headers = reader.headers()?;
+    if opt.header {
+        return Ok(headers.clone());
+    }
+    Ok((1..=headers.len()).map(|i| format!("col{}", i)).collect())
+}
+
+pub(crate) fn ascii_byte(c: char, name: &str) -> anyhow::Result<u8> {
+    match u8::try_from(c) {
+        Ok(b) if b.is_ascii() => Ok(b),
+        _ => anyhow::bail!("The {} must be a single ASCII character, got: {}", name, c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_headers(input: &str, opt: &CsvReaderOpt) -> (StringRecord, Vec<StringRecord>) {
+        let mut reader = build_reader(input, opt).unwrap();
+        let headers = resolve_headers(&mut reader, opt).unwrap();
+        let records = reader.records().collect::<Result<Vec<_>, _>>().unwrap();
+        (headers, records)
+    }
+
+    #[test]
+    fn test_read_csv_with_header() {
+        let (headers, records) = read_headers("assets/juventus.csv", &CsvReaderOpt::default());
+        assert_eq!(
+            headers,
+            vec!["Name", "Position", "DOB", "Nationality", "Kit Number"]
+        );
+        assert_eq!(&records[0][0], "Wojciech Szczesny");
+    }
+
+    #[test]
+    fn test_read_tsv_without_header() {
+        let opt = CsvReaderOpt {
+            delimiter: '\t',
+            header: false,
+            comment: Some('#'),
+            ..Default::default()
+        };
+        let (headers, records) = read_headers("fixtures/no_header.tsv", &opt);
+        assert_eq!(headers, vec!["col1", "col2", "col3"]);
+        assert_eq!(records.len(), 2);
+        assert_eq!(&records[0][0], "Alice");
+
+        let opt = CsvReaderOpt {
+            columns: Some(vec!["name".into(), "city".into(), "amount".into()]),
+            ..opt
+        };
+        let (headers, records) = read_headers("fixtures/no_header.tsv", &opt);
+        assert_eq!(headers, vec!["name", "city", "amount"]);
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn test_read_semicolon_with_escape() {
+        let opt = CsvReaderOpt {
+            delimiter: ';',
+            quote: '\'',
+            escape: Some('\\'),
+            ..Default::default()
+        };
+        let (headers, records) = read_headers("fixtures/semicolon.csv", &opt);
+        assert_eq!(headers, vec!["name", "note"]);
+        assert_eq!(&records[0][1], "it's; fine");
+    }
+
+    #[test]
+    fn test_projection() {
+        let headers = StringRecord::from(vec!["Name", "Position", "DOB", "Kit Number"]);
+        let opt = CsvConvertOpt {
+            select: Some(vec!["Kit Number".into(), "Name".into(), "DOB".into()]),
+            exclude: vec!["DOB".into()],
+            rename: vec![("Kit Number".into(), "kit".into())],
+            ..Default::default()
+        };
+        let projection = Projection::new(&headers, &opt).unwrap();
+        assert_eq!(projection.headers, vec!["kit", "Name"]);
+        let record = StringRecord::from(vec!["Alice", "Forward", "2000-01-01", "10"]);
+        assert_eq!(projection.apply(&record), vec!["10", "Alice"]);
+
+        let opt = CsvConvertOpt {
+            exclude: vec!["Age".into()],
+            ..Default::default()
+        };
+        let err = Projection::new(&headers, &opt).err().unwrap();
+        assert_eq!(err.to_string(), "Column not found in csv: Age");
+    }
+
+    #[test]
+    fn test_record_to_value_with_types() {
+        let headers = StringRecord::from(vec!["name", "kit", "active"]);
+        let record = StringRecord::from(vec!["Alice", "10", ""]);
+        let mut types = [
+            CellType::Raw,
+            CellType::Inferred(ColumnType::Integer),
+            CellType::Declared(ColumnType::Boolean),
+        ];
+        let cells = convert_cells(&headers, &record, &mut types).unwrap();
+        let value = build_value(&headers, cells, None);
+        assert_eq!(
+            value,
+            serde_json::json!({"name": "Alice", "kit": 10, "active": null})
+        );
+
+        let record = StringRecord::from(vec!["Bob", "n/a", "yes"]);
+        let err = convert_cells(&headers, &record, &mut types).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Line 0, column active: can not parse "yes" as boolean"#
+        );
+
+        // 推断的列遇到无法转换的单元格后，之后的单元格都按字符串输出
+        let record = StringRecord::from(vec!["Carol", "n/a", "true"]);
+        let cells = convert_cells(&headers, &record, &mut types).unwrap();
+        assert_eq!(cells[1], "n/a");
+        assert_eq!(types[1], CellType::Raw);
+        let record = StringRecord::from(vec!["Dave", "7", "false"]);
+        let cells = convert_cells(&headers, &record, &mut types).unwrap();
+        assert_eq!(cells[1], "7");
+    }
+
+    #[test]
+    fn test_schema_uses_source_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let convert = |schema: &str| {
+            let schema_path = dir.path().join("schema.yaml");
+            std::fs::write(&schema_path, schema).unwrap();
+            let output = dir.path().join("players.json");
+            let opt = CsvConvertOpt {
+                schema: Some(schema_path.to_str().unwrap().to_string()),
+                exclude: vec!["dob".into()],
+                rename: vec![("kit".into(), "number".into())],
+                ..Default::default()
+            };
+            process_csv(
+                "fixtures/players_types.csv",
+                output.to_str().unwrap().to_string(),
+                OutputFormat::Json,
+                opt,
+            )?;
+            let content = std::fs::read_to_string(&output).unwrap();
+            anyhow::Ok(serde_json::from_str::<Value>(&content).unwrap())
+        };
+
+        // kit 改名为 number 之后仍然按原来的列名匹配，dob 没有选择，它的类型被忽略
+        let value = convert("columns: { kit: integer, dob: date, active: boolean }").unwrap();
+        assert_eq!(value[0]["number"], 10);
+        assert_eq!(value[0]["active"], true);
+        assert_eq!(value[0]["code"], "007");
+        assert!(value[0].get("dob").is_none());
+
+        let err = convert("columns: { number: integer }").unwrap_err();
+        assert_eq!(err.to_string(), "Schema column not found in csv: number");
+    }
+
+    #[test]
+    fn test_process_csv_compressed_output() {
+        let dir = tempfile::tempdir().unwrap();
+        for ext in ["json.gz", "json.zst", "json.bz2", "json.xz"] {
+            let output = dir.path().join(format!("players.{}", ext));
+            let output = output.to_str().unwrap();
+            process_csv(
+                "fixtures/players.csv",
+                output.to_string(),
+                OutputFormat::Json,
+                CsvConvertOpt::default(),
+            )
+            .unwrap();
+
+            let mut content = String::new();
+            get_data_reader(output)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            let value: Value = serde_json::from_str(&content).unwrap();
+            assert_eq!(value.as_array().unwrap().len(), 3, "{}", ext);
+            assert_eq!(value[0]["name"], "Alice", "{}", ext);
+        }
+    }
 }
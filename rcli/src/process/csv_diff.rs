@@ -0,0 +1,410 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{IsTerminal, Write},
+};
+
+use anyhow::Result;
+use csv::{StringRecord, WriterBuilder};
+use serde_json::{json, Map, Value};
+
+use super::{
+    csv_convert::{build_reader, resolve_headers},
+    CsvReaderOpt,
+};
+use crate::{cli::DiffFormat, get_writer};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowDiff {
+    Added(Row),
+    Removed(Row),
+    // row 为新文件里的这一行
+    Changed { row: Row, changes: Vec<Change> },
+}
+
+// 一行数据，key 是 key 列的值，cells 按照两个文件表头的并集排列，文件里没有的列为 None
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub key: Vec<String>,
+    pub cells: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub column: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+pub struct CsvDiff {
+    pub keys: Vec<String>,
+    pub headers: Vec<String>,
+    // headers 里的每一列在新文件里是否存在
+    pub in_new: Vec<bool>,
+    pub rows: Vec<RowDiff>,
+}
+
+pub fn process_csv_diff(
+    old: &str,
+    new: &str,
+    keys: &[String],
+    output: &str,
+    format: DiffFormat,
+    opt: CsvReaderOpt,
+) -> Result<()> {
+    let diff = diff_csv(old, new, keys, &opt)?;
+    let mut writer = get_writer(output)?;
+    match format {
+        DiffFormat::Text => {
+            // 只有输出到终端时才加颜色
+            let color = output == "-" && std::io::stdout().is_terminal();
+            writer.write_all(diff.to_text(color).as_bytes())?;
+        }
+        DiffFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &diff.to_json())?;
+            writeln!(writer)?;
+        }
+        DiffFormat::Csv => diff.write_patch(&mut writer)?,
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+// 先把旧文件按 key 读到内存里，然后逐行读取新文件进行比较
+pub fn diff_csv(old: &str, new: &str, keys: &[String], opt: &CsvReaderOpt) -> Result<CsvDiff> {
+    let mut old_reader = build_reader(old, opt)?;
+    let old_headers = resolve_headers(&mut old_reader, opt)?;
+    let mut new_reader = build_reader(new, opt)?;
+    let new_headers = resolve_headers(&mut new_reader, opt)?;
+
+    let mut headers: Vec<String> = old_headers.iter().map(String::from).collect();
+    for header in &new_headers {
+        if !headers.iter().any(|h| h == header) {
+            headers.push(header.to_string());
+        }
+    }
+    let old_key = key_indices(&old_headers, keys, old)?;
+    let new_key = key_indices(&new_headers, keys, new)?;
+    let old_columns = column_indices(&old_headers, &headers);
+    let new_columns = column_indices(&new_headers, &headers);
+
+    let mut old_rows: Vec<Row> = Vec::new();
+    let mut old_index: HashMap<Vec<String>, usize> = HashMap::new();
+    for record in old_reader.records() {
+        let row = to_row(&record?, &old_key, &old_columns);
+        if old_index.insert(row.key.clone(), old_rows.len()).is_some() {
+            anyhow::bail!("Duplicate key in {}: {}", old, key_label(keys, &row.key));
+        }
+        old_rows.push(row);
+    }
+
+    let mut rows = Vec::new();
+    let mut seen: HashSet<Vec<String>> = HashSet::new();
+    for record in new_reader.records() {
+        let row = to_row(&record?, &new_key, &new_columns);
+        if !seen.insert(row.key.clone()) {
+            anyhow::bail!("Duplicate key in {}: {}", new, key_label(keys, &row.key));
+        }
+        let Some(&idx) = old_index.get(&row.key) else {
+            rows.push(RowDiff::Added(row));
+            continue;
+        };
+        let changes: Vec<Change> = headers
+            .iter()
+            .zip(old_rows[idx].cells.iter().zip(&row.cells))
+            .filter(|(_, (old, new))| old != new)
+            .map(|(column, (old, new))| Change {
+                column: column.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            })
+            .collect();
+        if !changes.is_empty() {
+            rows.push(RowDiff::Changed { row, changes });
+        }
+    }
+    rows.extend(
+        old_rows
+            .into_iter()
+            .filter(|row| !seen.contains(&row.key))
+            .map(RowDiff::Removed),
+    );
+    Ok(CsvDiff {
+        keys: keys.to_vec(),
+        headers,
+        in_new: new_columns.iter().map(Option::is_some).collect(),
+        rows,
+    })
+}
+
+impl CsvDiff {
+    // + id=4: ... 新增，- id=2: ... 删除，~ id=3 修改，后面每行是一列的变化
+    pub fn to_text(&self, color: bool) -> String {
+        let paint = |code: &str, text: String| {
+            if color {
+                format!("{}{}{}", code, text, RESET)
+            } else {
+                text
+            }
+        };
+        let mut out = String::new();
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+        for row in &self.rows {
+            match row {
+                RowDiff::Added(row) => {
+                    added += 1;
+                    out.push_str(&paint(
+                        GREEN,
+                        format!("+ {}: {}", self.key(row), self.fields(row)),
+                    ));
+                }
+                RowDiff::Removed(row) => {
+                    removed += 1;
+                    out.push_str(&paint(
+                        RED,
+                        format!("- {}: {}", self.key(row), self.fields(row)),
+                    ));
+                }
+                RowDiff::Changed { row, changes } => {
+                    changed += 1;
+                    out.push_str(&paint(YELLOW, format!("~ {}", self.key(row))));
+                    for change in changes {
+                        out.push_str(&format!(
+                            "\n    {}: {} -> {}",
+                            change.column,
+                            display(&change.old),
+                            display(&change.new)
+                        ));
+                    }
+                }
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "{} added, {} removed, {} changed\n",
+            added, removed, changed
+        ));
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (mut added, mut removed, mut changed) = (Vec::new(), Vec::new(), Vec::new());
+        for row in &self.rows {
+            match row {
+                RowDiff::Added(row) => added.push(self.object(row)),
+                RowDiff::Removed(row) => removed.push(self.object(row)),
+                RowDiff::Changed { row, changes } => {
+                    let changes: Map<String, Value> = changes
+                        .iter()
+                        .map(|c| (c.column.clone(), json!({"old": c.old, "new": c.new})))
+                        .collect();
+                    changed.push(json!({"key": self.key(row), "changes": changes}));
+                }
+            }
+        }
+        json!({"added": added, "removed": removed, "changed": changed})
+    }
+
+    // patch csv：第一列 _op 为 add / remove / change，修改的行输出新文件里的值
+    // 表头和新文件的列一致，新文件里已经删除的列不输出，避免和空字符串混淆
+    pub fn write_patch(&self, writer: impl Write) -> Result<()> {
+        let mut writer = WriterBuilder::new().from_writer(writer);
+        let mut headers = vec!["_op"];
+        headers.extend(self.patch_columns(&self.headers).map(String::as_str));
+        writer.write_record(&headers)?;
+        for row in &self.rows {
+            let (op, row) = match row {
+                RowDiff::Added(row) => ("add", row),
+                RowDiff::Removed(row) => ("remove", row),
+                RowDiff::Changed { row, .. } => ("change", row),
+            };
+            let mut record = vec![op];
+            record.extend(
+                self.patch_columns(&row.cells)
+                    .map(|c| c.as_deref().unwrap_or_default()),
+            );
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn patch_columns<'a, T>(&'a self, items: &'a [T]) -> impl Iterator<Item = &'a T> {
+        items
+            .iter()
+            .zip(&self.in_new)
+            .filter_map(|(item, &in_new)| in_new.then_some(item))
+    }
+
+    fn key(&self, row: &Row) -> String {
+        key_label(&self.keys, &row.key)
+    }
+
+    fn fields(&self, row: &Row) -> String {
+        self.headers
+            .iter()
+            .zip(&row.cells)
+            .filter_map(|(header, cell)| cell.as_ref().map(|c| format!("{}={:?}", header, c)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn object(&self, row: &Row) -> Value {
+        let map: Map<String, Value> = self
+            .headers
+            .iter()
+            .zip(&row.cells)
+            .filter_map(|(header, cell)| cell.as_ref().map(|c| (header.clone(), json!(c))))
+            .collect();
+        Value::Object(map)
+    }
+}
+
+fn key_indices(headers: &StringRecord, keys: &[String], file: &str) -> Result<Vec<usize>> {
+    keys.iter()
+        .map(|key| {
+            headers
+                .iter()
+                .position(|h| h == key)
+                .ok_or_else(|| anyhow::anyhow!("Key column not found in {}: {}", file, key))
+        })
+        .collect()
+}
+
+// 合并后的每一列在这个文件里的下标，文件里没有的列为 None
+fn column_indices(record_headers: &StringRecord, headers: &[String]) -> Vec<Option<usize>> {
+    headers
+        .iter()
+        .map(|header| record_headers.iter().position(|h| h == header))
+        .collect()
+}
+
+fn to_row(record: &StringRecord, key: &[usize], columns: &[Option<usize>]) -> Row {
+    let cell = |i: usize| record.get(i).unwrap_or_default().to_string();
+    Row {
+        key: key.iter().map(|&i| cell(i)).collect(),
+        cells: columns.iter().map(|i| i.map(cell)).collect(),
+    }
+}
+
+// 多列 key 显示为 id=1,region=eu，只用于显示，比较时按每一列的值
+fn key_label(keys: &[String], values: &[String]) -> String {
+    keys.iter()
+        .zip(values)
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn display(cell: &Option<String>) -> String {
+    match cell {
+        Some(cell) => format!("{:?}", cell),
+        None => "(missing)".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str, keys: &[&str]) -> Result<CsvDiff> {
+        let keys: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+        diff_csv(old, new, &keys, &CsvReaderOpt::default())
+    }
+
+    #[test]
+    fn test_diff_csv() {
+        let ret = diff("fixtures/diff_old.csv", "fixtures/diff_new.csv", &["id"]).unwrap();
+        assert_eq!(
+            ret.to_text(false),
+            "~ id=3\n    email: \"c@x.com\" -> \"carol@x.com\"\n\
+             + id=4: id=\"4\", name=\"Dave\", email=\"d@x.com\"\n\
+             - id=2: id=\"2\", name=\"Bob\", email=\"b@x.com\"\n\
+             1 added, 1 removed, 1 changed\n"
+        );
+        assert_eq!(
+            ret.to_json()["changed"],
+            json!([{"key": "id=3", "changes": {"email": {"old": "c@x.com", "new": "carol@x.com"}}}])
+        );
+
+        let mut patch = Vec::new();
+        ret.write_patch(&mut patch).unwrap();
+        assert_eq!(
+            String::from_utf8(patch).unwrap(),
+            "_op,id,name,email\nchange,3,Carol,carol@x.com\nadd,4,Dave,d@x.com\nremove,2,Bob,b@x.com\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_csv_with_different_headers() {
+        let ret = diff(
+            "fixtures/diff_narrow.csv",
+            "fixtures/diff_wide.csv",
+            &["id"],
+        )
+        .unwrap();
+        assert_eq!(
+            ret.rows,
+            vec![RowDiff::Changed {
+                row: Row {
+                    key: vec!["1".to_string()],
+                    cells: vec![
+                        Some("1".to_string()),
+                        Some("Alice".to_string()),
+                        Some("30".to_string())
+                    ],
+                },
+                changes: vec![Change {
+                    column: "age".to_string(),
+                    old: None,
+                    new: Some("30".to_string()),
+                }],
+            }]
+        );
+
+        // 新文件里删除的 age 列不出现在 patch 里，而不是写成空字符串
+        let ret = diff(
+            "fixtures/diff_wide.csv",
+            "fixtures/diff_narrow.csv",
+            &["id"],
+        )
+        .unwrap();
+        assert_eq!(ret.headers, vec!["id", "name", "age"]);
+        let mut patch = Vec::new();
+        ret.write_patch(&mut patch).unwrap();
+        assert_eq!(
+            String::from_utf8(patch).unwrap(),
+            "_op,id,name\nchange,1,Alice\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_csv_composite_key_with_separators() {
+        // 两行的 key 拼接后都是 id=1,region=eu,region=x，但值不同，不能配对
+        let ret = diff(
+            "fixtures/diff_composite_old.csv",
+            "fixtures/diff_composite_new.csv",
+            &["id", "region"],
+        )
+        .unwrap();
+        assert_eq!(ret.rows.len(), 1);
+        let RowDiff::Changed { row, changes } = &ret.rows[0] else {
+            panic!("expect a changed row");
+        };
+        assert_eq!(row.key, vec!["1", "eu,region=x"]);
+        assert_eq!(changes[0].old.as_deref(), Some("Bob"));
+        assert_eq!(changes[0].new.as_deref(), Some("Robert"));
+
+        let Err(err) = diff("fixtures/partners.csv", "fixtures/diff_new.csv", &["id"]) else {
+            panic!("duplicate keys should error");
+        };
+        assert_eq!(
+            err.to_string(),
+            "Duplicate key in fixtures/partners.csv: id=1"
+        );
+    }
+}
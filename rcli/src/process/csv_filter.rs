@@ -0,0 +1,480 @@
+use std::cmp::Ordering;
+
+use anyhow::Result;
+use csv::StringRecord;
+use regex::Regex;
+
+// 行过滤表达式，如 `Nationality == 'Italy' && Kit Number > 10`
+// - 列名可以直接写（允许包含空格），也可以用反引号括起来：`Kit Number`
+// - 字符串用单引号或双引号，数字直接写
+// - 比较：== != < <= > >=，两边都是数字时按数字比较，否则按字符串比较
+// - 字符串：contains, startswith, endswith, =~ (正则匹配), !~ (正则不匹配)
+// - 逻辑：&& (and), || (or), ! (not) 以及括号
+pub struct RowFilter {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Cmp(CmpOp),
+    Str(String),
+    Num(String),
+    Ident(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Matches,
+    NotMatches,
+}
+
+#[derive(Debug)]
+enum Operand {
+    Column(usize),
+    Literal(String),
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, CmpOp, Operand),
+    Regex(Operand, Regex, bool),
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    headers: &'a StringRecord,
+}
+
+impl RowFilter {
+    pub fn compile(input: &str, headers: &StringRecord) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            headers,
+        };
+        let expr = parser.parse_or()?;
+        if let Some((col, token)) = parser.tokens.get(parser.pos) {
+            anyhow::bail!(
+                "Parse error at position {}: unexpected {}",
+                col,
+                describe(token)
+            );
+        }
+        Ok(Self { expr })
+    }
+
+    pub fn matches(&self, record: &StringRecord) -> bool {
+        self.expr.eval(record)
+    }
+}
+
+impl Expr {
+    fn eval(&self, record: &StringRecord) -> bool {
+        match self {
+            Expr::And(l, r) => l.eval(record) && r.eval(record),
+            Expr::Or(l, r) => l.eval(record) || r.eval(record),
+            Expr::Not(e) => !e.eval(record),
+            Expr::Compare(l, op, r) => {
+                let (l, r) = (l.value(record), r.value(record));
+                match op {
+                    CmpOp::Eq => compare(l, r) == Ordering::Equal,
+                    CmpOp::Ne => compare(l, r) != Ordering::Equal,
+                    CmpOp::Lt => compare(l, r) == Ordering::Less,
+                    CmpOp::Le => compare(l, r) != Ordering::Greater,
+                    CmpOp::Gt => compare(l, r) == Ordering::Greater,
+                    CmpOp::Ge => compare(l, r) != Ordering::Less,
+                    CmpOp::Contains => l.contains(r),
+                    CmpOp::StartsWith => l.starts_with(r),
+                    CmpOp::EndsWith => l.ends_with(r),
+                    CmpOp::Matches | CmpOp::NotMatches => unreachable!("compiled as regex"),
+                }
+            }
+            Expr::Regex(operand, regex, negate) => regex.is_match(operand.value(record)) != *negate,
+        }
+    }
+}
+
+impl CmpOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+            CmpOp::Contains => "contains",
+            CmpOp::StartsWith => "startswith",
+            CmpOp::EndsWith => "endswith",
+            CmpOp::Matches => "=~",
+            CmpOp::NotMatches => "!~",
+        }
+    }
+}
+
+impl Operand {
+    fn value<'a>(&'a self, record: &'a StringRecord) -> &'a str {
+        match self {
+            Operand::Column(idx) => record.get(*idx).unwrap_or_default(),
+            Operand::Literal(s) => s,
+        }
+    }
+}
+
+// 两边都能解析为有限的数字时按数字比较，否则（包括 NaN、inf）按字符串比较
+fn compare(l: &str, r: &str) -> Ordering {
+    match (parse_finite(l), parse_finite(r)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        _ => l.cmp(r),
+    }
+}
+
+fn parse_finite(s: &str) -> Option<f64> {
+    s.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(usize, Token)> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => anyhow::bail!(
+                "Parse error: expected {}, found end of expression",
+                expected
+            ),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.parse_or()?;
+            match self.next("`)`")? {
+                (_, Token::RParen) => return Ok(expr),
+                (col, token) => anyhow::bail!(
+                    "Parse error at position {}: expected `)`, found {}",
+                    col,
+                    describe(&token)
+                ),
+            }
+        }
+
+        let left = self.parse_operand()?;
+        let op = match self.next("a comparison operator")? {
+            (_, Token::Cmp(op)) => op,
+            (col, token) => anyhow::bail!(
+                "Parse error at position {}: expected a comparison operator, found {}",
+                col,
+                describe(&token)
+            ),
+        };
+        if matches!(op, CmpOp::Matches | CmpOp::NotMatches) {
+            let (col, token) = self.next("a regex string")?;
+            let Token::Str(pattern) = token else {
+                anyhow::bail!(
+                    "Parse error at position {}: expected a regex string, found {}",
+                    col,
+                    describe(&token)
+                );
+            };
+            let regex = Regex::new(&pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid regex at position {}: {}", col, e))?;
+            return Ok(Expr::Regex(left, regex, op == CmpOp::NotMatches));
+        }
+        let right = self.parse_operand()?;
+        Ok(Expr::Compare(left, op, right))
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand> {
+        match self.next("a column, string or number")? {
+            (col, Token::Ident(name)) => match self.headers.iter().position(|h| h == name) {
+                Some(idx) => Ok(Operand::Column(idx)),
+                None => anyhow::bail!("Unknown column {:?} at position {}", name, col),
+            },
+            (_, Token::Str(s)) | (_, Token::Num(s)) => Ok(Operand::Literal(s)),
+            (col, token) => anyhow::bail!(
+                "Parse error at position {}: expected a column, string or number, found {}",
+                col,
+                describe(&token)
+            ),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::LParen => "`(`".to_string(),
+        Token::RParen => "`)`".to_string(),
+        Token::And => "`&&`".to_string(),
+        Token::Or => "`||`".to_string(),
+        Token::Not => "`!`".to_string(),
+        Token::Cmp(op) => format!("operator `{}`", op.symbol()),
+        Token::Str(s) => format!("string '{}'", s),
+        Token::Num(n) => format!("number {}", n),
+        Token::Ident(name) => format!("column {}", name),
+    }
+}
+
+fn is_special(c: char) -> bool {
+    c.is_whitespace() || "()'\"`&|=!<>~".contains(c)
+}
+
+// 返回 (位置, token)，位置从 1 开始，用于错误提示
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    // 连续的普通单词合并为一个列名，以支持 Kit Number 这样带空格的列名
+    let mut ident: Option<(usize, String)> = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let col = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if !is_special(c) {
+            let start = i;
+            while i < chars.len() && !is_special(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let keyword = match word.to_lowercase().as_str() {
+                "and" => Some(Token::And),
+                "or" => Some(Token::Or),
+                "not" => Some(Token::Not),
+                "contains" => Some(Token::Cmp(CmpOp::Contains)),
+                "startswith" => Some(Token::Cmp(CmpOp::StartsWith)),
+                "endswith" => Some(Token::Cmp(CmpOp::EndsWith)),
+                "matches" => Some(Token::Cmp(CmpOp::Matches)),
+                _ => None,
+            };
+            match (keyword, &mut ident) {
+                (Some(token), _) => {
+                    tokens.extend(ident.take().map(|(col, name)| (col, Token::Ident(name))));
+                    tokens.push((col, token));
+                }
+                (None, Some((_, name))) => {
+                    name.push(' ');
+                    name.push_str(&word);
+                }
+                (None, None) if word.parse::<f64>().is_ok() => tokens.push((col, Token::Num(word))),
+                (None, None) => ident = Some((col, word)),
+            }
+            continue;
+        }
+
+        tokens.extend(ident.take().map(|(col, name)| (col, Token::Ident(name))));
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Cmp(CmpOp::Eq), 2),
+            ('=', Some('~')) => (Token::Cmp(CmpOp::Matches), 2),
+            ('=', _) => (Token::Cmp(CmpOp::Eq), 1),
+            ('!', Some('=')) => (Token::Cmp(CmpOp::Ne), 2),
+            ('!', Some('~')) => (Token::Cmp(CmpOp::NotMatches), 2),
+            ('!', _) => (Token::Not, 1),
+            ('<', Some('=')) => (Token::Cmp(CmpOp::Le), 2),
+            ('<', _) => (Token::Cmp(CmpOp::Lt), 1),
+            ('>', Some('=')) => (Token::Cmp(CmpOp::Ge), 2),
+            ('>', _) => (Token::Cmp(CmpOp::Gt), 1),
+            ('\'' | '"' | '`', _) => {
+                let (text, end) = read_quoted(&chars, i)?;
+                let token = if c == '`' {
+                    Token::Ident(text)
+                } else {
+                    Token::Str(text)
+                };
+                tokens.push((col, token));
+                i = end;
+                continue;
+            }
+            (c, _) => anyhow::bail!(
+                "Parse error at position {}: unexpected character '{}'",
+                col,
+                c
+            ),
+        };
+        tokens.push((col, token));
+        i += len;
+    }
+    tokens.extend(ident.take().map(|(col, name)| (col, Token::Ident(name))));
+    Ok(tokens)
+}
+
+// 读取引号括起来的内容，支持 \ 转义，返回内容和结束引号之后的位置
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize)> {
+    let quote = chars[start];
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            c if c == quote => return Ok((text, i + 1)),
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    anyhow::bail!(
+        "Parse error at position {}: unterminated {} quoted text",
+        start + 1,
+        quote
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["Name", "Nationality", "Kit Number"])
+    }
+
+    fn filter(expr: &str, row: Vec<&str>) -> bool {
+        RowFilter::compile(expr, &headers())
+            .unwrap()
+            .matches(&StringRecord::from(row))
+    }
+
+    #[test]
+    fn test_filter_comparisons() {
+        let row = vec!["Paulo Dybala", "Argentina", "10"];
+        assert!(filter(
+            "Nationality == 'Argentina' && Kit Number >= 10",
+            row.clone()
+        ));
+        assert!(!filter(
+            "Nationality == 'Italy' && Kit Number > 10",
+            row.clone()
+        ));
+        assert!(filter(
+            "`Kit Number` < 9 || Name contains 'Dybala'",
+            row.clone()
+        ));
+        // 数字按数值比较，而不是字符串
+        assert!(filter("Kit Number > 9", row.clone()));
+        assert!(filter("Kit Number == 10.0", row.clone()));
+        assert!(filter("Name != \"Buffon\"", row));
+    }
+
+    #[test]
+    fn test_filter_nan_is_not_a_number() {
+        // NaN 和 inf 不按数字比较，NaN 不会等于任何数字
+        let row = vec!["Paulo Dybala", "Argentina", "NaN"];
+        assert!(!filter("Kit Number == 5", row.clone()));
+        assert!(filter("Kit Number != 5", row.clone()));
+        assert!(!filter("Kit Number == 'nan'", row));
+        assert!(!filter("Kit Number == 1e400", vec!["A", "B", "inf"]));
+    }
+
+    #[test]
+    fn test_filter_logic_and_regex() {
+        let row = vec!["Gianluigi Buffon", "Italy", "77"];
+        assert!(filter(
+            "Name =~ '^Gian' and not (Kit Number < 50)",
+            row.clone()
+        ));
+        assert!(filter("Name !~ 'Dybala'", row.clone()));
+        assert!(filter(
+            "!(Nationality == 'Poland') && Name endswith 'Buffon'",
+            row.clone()
+        ));
+        assert!(filter("Name startswith 'Gian' or Kit Number == 1", row));
+    }
+
+    #[test]
+    fn test_filter_parse_errors() {
+        let err = |expr| {
+            RowFilter::compile(expr, &headers())
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(err("Age > 10"), "Unknown column \"Age\" at position 1");
+        assert_eq!(
+            err("Name == 'Italy"),
+            "Parse error at position 9: unterminated ' quoted text"
+        );
+        assert_eq!(
+            err("Name == 'a' &&"),
+            "Parse error: expected a column, string or number, found end of expression"
+        );
+        assert_eq!(
+            err("Name 'a'"),
+            "Parse error at position 6: expected a comparison operator, found string 'a'"
+        );
+        assert_eq!(
+            err("(Name == 'a'"),
+            "Parse error: expected `)`, found end of expression"
+        );
+        assert_eq!(
+            err("Name == == 'a'"),
+            "Parse error at position 9: expected a column, string or number, found operator `==`"
+        );
+        assert!(err("Name =~ '('").starts_with("Invalid regex at position 9"));
+        assert_eq!(
+            err("Name == 'a' & Kit Number > 1"),
+            "Parse error at position 13: unexpected character '&'"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read},
+};
+
+use anyhow::Result;
+use csv::WriterBuilder;
+use serde_json::Value;
+
+use crate::{
+    cli::{ArrayMode, OutputFormat},
+    finish_csv, get_data_reader, get_writer,
+};
+
+pub struct CsvFromJsonOpt {
+    pub delimiter: char,
+    pub separator: String,
+    pub arrays: ArrayMode,
+    pub toml_key: String,
+}
+
+type FlatRow = Vec<(String, Value)>;
+
+pub fn process_csv_from_json(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    opt: CsvFromJsonOpt,
+) -> Result<()> {
+    let records = read_records(get_data_reader(input)?, format, &opt.toml_key)?;
+
+    let mut rows = Vec::with_capacity(records.len());
+    for record in &records {
+        if !record.is_object() {
+            anyhow::bail!("Expect an array of objects, got: {}", record);
+        }
+        rows.extend(flatten(record, "", &opt));
+    }
+
+    // 所有行的 key 的并集作为表头，按照第一次出现的顺序排列，index 用来查找每个 key 所在的列
+    let mut headers: Vec<&str> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for row in &rows {
+        for (key, _) in row {
+            index.entry(key).or_insert_with(|| {
+                headers.push(key);
+                headers.len() - 1
+            });
+        }
+    }
+
+    let delimiter = u8::try_from(opt.delimiter)
+        .map_err(|_| anyhow::anyhow!("The delimiter must be a single ASCII character"))?;
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(get_writer(output)?);
+    writer.write_record(&headers)?;
+    for row in &rows {
+        // 同一行里 key 重复时保留第一个
+        let mut cells: Vec<Option<&Value>> = vec![None; headers.len()];
+        for (key, value) in row {
+            let cell = &mut cells[index[key.as_str()]];
+            if cell.is_none() {
+                *cell = Some(value);
+            }
+        }
+        writer.write_record(
+            cells
+                .iter()
+                .map(|cell| cell.map(cell_to_string).unwrap_or_default()),
+        )?;
+    }
+    finish_csv(writer)
+}
+
+// 读取 json 数组 / yaml 序列 / ndjson / toml 的 [[key]]，单个对象视为一行
+pub(crate) fn read_records(
+    mut reader: Box<dyn Read>,
+    format: OutputFormat,
+    toml_key: &str,
+) -> Result<Vec<Value>> {
+    let value: Value = match format {
+        OutputFormat::Json => serde_json::from_reader(reader)?,
+        OutputFormat::Yaml => serde_yaml::from_reader(reader)?,
+        OutputFormat::Ndjson => {
+            let mut records = Vec::new();
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    records.push(serde_json::from_str(&line)?);
+                }
+            }
+            Value::Array(records)
+        }
+        OutputFormat::Toml => {
+            let mut content = String::new();
+            reader.read_to_string(&mut content)?;
+            let mut doc: Value = toml::from_str(&content)?;
+            match doc.get_mut(toml_key) {
+                Some(rows) => rows.take(),
+                None => anyhow::bail!("Key not found in toml: {}", toml_key),
+            }
+        }
+        format => anyhow::bail!("Unsupported input format: {}", format),
+    };
+    match value {
+        Value::Array(records) => Ok(records),
+        Value::Object(_) => Ok(vec![value]),
+        v => anyhow::bail!("Expect an array of objects, got: {}", v),
+    }
+}
+
+// 把嵌套的对象展开成一行或多行（数组按 explode 展开时会产生多行）
+fn flatten(value: &Value, prefix: &str, opt: &CsvFromJsonOpt) -> Vec<FlatRow> {
+    match value {
+        Value::Object(map) => {
+            let mut rows = vec![FlatRow::new()];
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}{}{}", prefix, opt.separator, key)
+                };
+                let children = flatten(value, &key, opt);
+                rows = cartesian(rows, children);
+            }
+            rows
+        }
+        Value::Array(items) if opt.arrays == ArrayMode::Explode => {
+            if items.is_empty() {
+                return vec![vec![(prefix.to_string(), Value::Null)]];
+            }
+            items
+                .iter()
+                .flat_map(|item| flatten(item, prefix, opt))
+                .collect()
+        }
+        Value::Array(_) => vec![vec![(prefix.to_string(), Value::String(value.to_string()))]],
+        v => vec![vec![(prefix.to_string(), v.clone())]],
+    }
+}
+
+fn cartesian(left: Vec<FlatRow>, right: Vec<FlatRow>) -> Vec<FlatRow> {
+    let mut rows = Vec::with_capacity(left.len() * right.len());
+    for l in &left {
+        for r in &right {
+            let mut row = l.clone();
+            row.extend(r.iter().cloned());
+            rows.push(row);
+        }
+    }
+    rows
+}
+
+pub(crate) fn cell_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn opt(arrays: ArrayMode) -> CsvFromJsonOpt {
+        CsvFromJsonOpt {
+            delimiter: ',',
+            separator: ".".to_string(),
+            arrays,
+            toml_key: "rows".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_flatten_nested_object() {
+        let value = json!({
+            "name": "Alice",
+            "address": {"city": "Turin", "zip": "10121"},
+            "tags": ["a", "b"],
+        });
+        let rows = flatten(&value, "", &opt(ArrayMode::Json));
+        assert_eq!(
+            rows,
+            vec![vec![
+                ("name".to_string(), json!("Alice")),
+                ("address.city".to_string(), json!("Turin")),
+                ("address.zip".to_string(), json!("10121")),
+                ("tags".to_string(), json!(r#"["a","b"]"#)),
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_flatten_explode_arrays() {
+        let value = json!({
+            "name": "Alice",
+            "items": [{"sku": 1}, {"sku": 2}],
+            "tags": ["a", "b"],
+        });
+        let rows = flatten(&value, "", &opt(ArrayMode::Explode));
+        assert_eq!(rows.len(), 4);
+        assert_eq!(
+            rows[1],
+            vec![
+                ("name".to_string(), json!("Alice")),
+                ("items.sku".to_string(), json!(1)),
+                ("tags".to_string(), json!("b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_records() {
+        let ndjson = "{\"a\":1}\n\n{\"b\":2}\n";
+        let records =
+            read_records(Box::new(ndjson.as_bytes()), OutputFormat::Ndjson, "rows").unwrap();
+        assert_eq!(records, vec![json!({"a": 1}), json!({"b": 2})]);
+
+        let toml = "[[players]]\nname = \"Alice\"\n";
+        let records =
+            read_records(Box::new(toml.as_bytes()), OutputFormat::Toml, "players").unwrap();
+        assert_eq!(records, vec![json!({"name": "Alice"})]);
+    }
+
+    #[test]
+    fn test_process_csv_from_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("players.csv");
+        process_csv_from_json(
+            "fixtures/players.json",
+            output.to_str().unwrap(),
+            OutputFormat::Json,
+            opt(ArrayMode::Json),
+        )
+        .unwrap();
+        let content = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(
+            content,
+            "name,kit,nationality,active\nAlice,10,,\nBob,,Italy,true\n"
+        );
+    }
+}
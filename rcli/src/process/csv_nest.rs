@@ -0,0 +1,225 @@
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+// 表头里数组下标的上限，避免 tags[999999999999] 这样的表头分配巨大的数组
+const MAX_ARRAY_INDEX: usize = 10_000;
+
+// 表头按分隔符和 [n] 拆分后的路径，如 items[0].sku => [Key(items), Index(0), Key(sku)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+// 解析所有的表头，路径相同、一个是另一个的前缀（如 a 和 a.b）、或者同一个位置既是 key 又是下标
+// （如 a.b 和 a[0]）时报错，这样写入每一行时不会再有冲突
+pub fn parse_header_paths<'a>(
+    headers: impl IntoIterator<Item = &'a str>,
+    separator: &str,
+) -> Result<Vec<Vec<PathSegment>>> {
+    let headers: Vec<&str> = headers.into_iter().collect();
+    let paths = headers
+        .iter()
+        .map(|header| parse_header_path(header, separator))
+        .collect::<Result<Vec<_>>>()?;
+
+    // 排序之后，有冲突的路径一定有一对是相邻的
+    let mut order: Vec<usize> = (0..paths.len()).collect();
+    order.sort_by(|&a, &b| paths[a].cmp(&paths[b]));
+    for pair in order.windows(2) {
+        let (a, b) = (&paths[pair[0]], &paths[pair[1]]);
+        let conflict = match a.iter().zip(b).position(|(x, y)| x != y) {
+            None => true,
+            Some(i) => std::mem::discriminant(&a[i]) != std::mem::discriminant(&b[i]),
+        };
+        if conflict {
+            anyhow::bail!(
+                "Conflicting header paths: {} and {}",
+                headers[pair[0]],
+                headers[pair[1]]
+            );
+        }
+    }
+    Ok(paths)
+}
+
+pub fn parse_header_path(header: &str, separator: &str) -> Result<Vec<PathSegment>> {
+    if separator.is_empty() {
+        anyhow::bail!("Nest separator can not be empty");
+    }
+    let mut path = Vec::new();
+    for part in header.split(separator) {
+        let (key, mut rest) = match part.find('[') {
+            Some(pos) => part.split_at(pos),
+            None => (part, ""),
+        };
+        if !key.is_empty() {
+            path.push(PathSegment::Key(key.to_string()));
+        }
+        while !rest.is_empty() {
+            let Some(end) = rest.find(']') else {
+                anyhow::bail!("Invalid header path: {}, missing `]`", header);
+            };
+            let index = rest[1..end]
+                .parse::<usize>()
+                .map_err(|_| anyhow::anyhow!("Invalid array index in header: {}", header))?;
+            if index > MAX_ARRAY_INDEX {
+                anyhow::bail!(
+                    "Array index {} in header {} exceeds the limit of {}",
+                    index,
+                    header,
+                    MAX_ARRAY_INDEX
+                );
+            }
+            path.push(PathSegment::Index(index));
+            rest = &rest[end + 1..];
+            if !rest.is_empty() && !rest.starts_with('[') {
+                anyhow::bail!("Invalid header path: {}", header);
+            }
+        }
+    }
+    if path.is_empty() {
+        anyhow::bail!("Invalid header path: {:?}", header);
+    }
+    Ok(path)
+}
+
+// 把扁平的 { "address.city": .., "tags[0]": .. } 按照表头路径构造成嵌套的对象和数组
+// paths 需要是 parse_header_paths 检查过没有冲突的路径
+pub fn nest_record(cells: impl IntoIterator<Item = Value>, paths: &[Vec<PathSegment>]) -> Value {
+    let mut root = Value::Object(Map::new());
+    for (value, path) in cells.into_iter().zip(paths) {
+        insert_path(&mut root, path, value);
+    }
+    root
+}
+
+// 路径没有冲突，所以只会在还没有创建的节点（null）上创建对象和数组，不会覆盖数据
+fn insert_path(target: &mut Value, path: &[PathSegment], value: Value) {
+    let Some((segment, rest)) = path.split_first() else {
+        *target = value;
+        return;
+    };
+
+    match segment {
+        PathSegment::Key(key) => {
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            if let Value::Object(map) = target {
+                insert_path(map.entry(key.clone()).or_insert(Value::Null), rest, value);
+            }
+        }
+        PathSegment::Index(index) => {
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            if let Value::Array(items) = target {
+                if items.len() <= *index {
+                    items.resize(index + 1, Value::Null);
+                }
+                insert_path(&mut items[*index], rest, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn nest(flat: Value, separator: &str) -> Result<Value> {
+        let Value::Object(flat) = flat else {
+            panic!("expect an object");
+        };
+        let paths = parse_header_paths(flat.keys().map(|k| k.as_str()), separator)?;
+        Ok(nest_record(flat.values().cloned(), &paths))
+    }
+
+    #[test]
+    fn test_parse_header_path() {
+        assert_eq!(
+            parse_header_path("items[0].sku", ".").unwrap(),
+            vec![
+                PathSegment::Key("items".into()),
+                PathSegment::Index(0),
+                PathSegment::Key("sku".into())
+            ]
+        );
+        assert_eq!(
+            parse_header_path("matrix[1][2]", ".").unwrap(),
+            vec![
+                PathSegment::Key("matrix".into()),
+                PathSegment::Index(1),
+                PathSegment::Index(2)
+            ]
+        );
+        assert!(parse_header_path("tags[x]", ".").is_err());
+        assert!(parse_header_path("tags[0", ".").is_err());
+
+        let err = parse_header_path("tags[999999999999]", ".").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Array index 999999999999 in header tags[999999999999] exceeds the limit of 10000"
+        );
+        assert!(parse_header_path("tags[10000]", ".").is_ok());
+        let err = parse_header_path("address.city", "").unwrap_err();
+        assert_eq!(err.to_string(), "Nest separator can not be empty");
+    }
+
+    #[test]
+    fn test_nest_record() {
+        let flat = json!({
+            "name": "Alice",
+            "address.city": "Turin",
+            "address.zip": "10121",
+            "tags[0]": "a",
+            "tags[1]": "b",
+            "items[0].sku": 1,
+            "items[1].sku": 2,
+        });
+        assert_eq!(
+            nest(flat, ".").unwrap(),
+            json!({
+                "name": "Alice",
+                "address": {"city": "Turin", "zip": "10121"},
+                "tags": ["a", "b"],
+                "items": [{"sku": 1}, {"sku": 2}],
+            })
+        );
+
+        let flat = json!({"address__city": "Turin", "Kit Number": 10});
+        assert_eq!(
+            nest(flat, "__").unwrap(),
+            json!({"address": {"city": "Turin"}, "Kit Number": 10})
+        );
+    }
+
+    #[test]
+    fn test_nest_record_conflict() {
+        let conflict = |headers: &[&str]| {
+            parse_header_paths(headers.iter().copied(), ".")
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            conflict(&["address.city", "name", "address"]),
+            "Conflicting header paths: address and address.city"
+        );
+        assert_eq!(
+            conflict(&["x.a", "x.a"]),
+            "Conflicting header paths: x.a and x.a"
+        );
+        assert_eq!(
+            conflict(&["a", "a.b"]),
+            "Conflicting header paths: a and a.b"
+        );
+        assert_eq!(
+            conflict(&["tags.first", "id", "tags[0]"]),
+            "Conflicting header paths: tags.first and tags[0]"
+        );
+        assert!(parse_header_paths(["a.b", "a.c", "ab", "t[0]", "t[1].x"], ".").is_ok());
+    }
+}
@@ -0,0 +1,474 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+use csv::{StringRecord, Writer, WriterBuilder};
+use tempfile::NamedTempFile;
+
+use super::{
+    csv_convert::{ascii_byte, build_reader, resolve_headers},
+    CsvReaderOpt,
+};
+use crate::{finish_csv, get_writer, OutputWriter};
+
+// 按列的值拆分时最多同时打开的文件数，超过时关闭最久没有写入的文件
+const MAX_OPEN_WRITERS: usize = 64;
+
+// 按行数或者某一列的值拆分文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitMode {
+    Rows(usize),
+    By(String),
+}
+
+// 输出的 csv 和输入使用同样的分隔符
+pub(crate) fn csv_writer(output: &str, opt: &CsvReaderOpt) -> Result<Writer<OutputWriter>> {
+    Ok(WriterBuilder::new()
+        .delimiter(ascii_byte(opt.delimiter, "delimiter")?)
+        .from_writer(get_writer(output)?))
+}
+
+// 去掉重复的行，保留第一次出现的，keys 为空时比较整行
+pub fn process_csv_dedupe(
+    input: &str,
+    output: &str,
+    keys: &[String],
+    opt: CsvReaderOpt,
+) -> Result<()> {
+    let mut reader = build_reader(input, &opt)?;
+    let headers = resolve_headers(&mut reader, &opt)?;
+    let indices: Vec<usize> = if keys.is_empty() {
+        (0..headers.len()).collect()
+    } else {
+        column_indices(&headers, keys)?
+    };
+
+    let mut writer = csv_writer(output, &opt)?;
+    writer.write_record(&headers)?;
+    let mut seen: HashSet<Vec<String>> = HashSet::new();
+    for record in reader.records() {
+        let record = record?;
+        let key = indices
+            .iter()
+            .map(|&i| record.get(i).unwrap_or_default().to_string())
+            .collect();
+        if seen.insert(key) {
+            writer.write_record(&record)?;
+        }
+    }
+    finish_csv(writer)
+}
+
+// 拆分后的文件放在 dir 下面，命名为 <prefix>_1.csv, <prefix>_2.csv 或者 <prefix>_<value>.csv
+// 不同的值文件名冲突时加上 _2, _3 后缀，返回生成的文件
+pub fn process_csv_split(
+    input: &str,
+    dir: &str,
+    prefix: &str,
+    mode: SplitMode,
+    opt: CsvReaderOpt,
+) -> Result<Vec<String>> {
+    let mut reader = build_reader(input, &opt)?;
+    let headers = resolve_headers(&mut reader, &opt)?;
+    std::fs::create_dir_all(dir)?;
+    let path_of = |name: &str| {
+        Path::new(dir)
+            .join(format!("{}_{}.csv", prefix, name))
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let mut files = Vec::new();
+    match mode {
+        SplitMode::Rows(rows) => {
+            let mut writer: Option<Writer<OutputWriter>> = None;
+            for (i, record) in reader.records().enumerate() {
+                let record = record?;
+                if i % rows == 0 {
+                    if let Some(w) = writer.take() {
+                        finish_csv(w)?;
+                    }
+                    let path = path_of(&(i / rows + 1).to_string());
+                    let mut w = csv_writer(&path, &opt)?;
+                    w.write_record(&headers)?;
+                    writer = Some(w);
+                    files.push(path);
+                }
+                if let Some(w) = writer.as_mut() {
+                    w.write_record(&record)?;
+                }
+            }
+            if let Some(w) = writer {
+                finish_csv(w)?;
+            }
+        }
+        SplitMode::By(column) => {
+            let index = column_indices(&headers, &[column])?[0];
+            let mut writers = SplitWriters::new(&headers, &opt);
+            let mut names = HashSet::new();
+            for record in reader.records() {
+                let record = record?;
+                let value = record.get(index).unwrap_or_default();
+                if !writers.paths.contains_key(value) {
+                    let path = path_of(&unique_name(sanitize(value), &mut names));
+                    writers.paths.insert(value.to_string(), path.clone());
+                    files.push(path);
+                }
+                writers.write(value, &record)?;
+            }
+            writers.flush()?;
+        }
+    }
+    Ok(files)
+}
+
+// 按列的值拆分时的输出文件，只保留最近写入的 MAX_OPEN_WRITERS 个文件打开
+// 关闭过的文件再次写入时以追加方式打开
+struct SplitWriters<'a> {
+    headers: &'a StringRecord,
+    opt: &'a CsvReaderOpt,
+    // 列的值 => 文件路径
+    paths: HashMap<String, String>,
+    // 打开的文件和最后一次写入的序号
+    open: HashMap<String, (Writer<BufWriter<File>>, u64)>,
+    created: HashSet<String>,
+    tick: u64,
+}
+
+impl<'a> SplitWriters<'a> {
+    fn new(headers: &'a StringRecord, opt: &'a CsvReaderOpt) -> Self {
+        Self {
+            headers,
+            opt,
+            paths: HashMap::new(),
+            open: HashMap::new(),
+            created: HashSet::new(),
+            tick: 0,
+        }
+    }
+
+    fn write(&mut self, value: &str, record: &StringRecord) -> Result<()> {
+        self.tick += 1;
+        if !self.open.contains_key(value) {
+            if self.open.len() >= MAX_OPEN_WRITERS {
+                self.close_least_recent()?;
+            }
+            let writer = self.open_writer(value)?;
+            self.open.insert(value.to_string(), (writer, self.tick));
+        }
+        if let Some((writer, tick)) = self.open.get_mut(value) {
+            writer.write_record(record)?;
+            *tick = self.tick;
+        }
+        Ok(())
+    }
+
+    // 拆分出的文件都是不压缩的 .csv，直接写文件
+    fn open_writer(&mut self, value: &str) -> Result<Writer<BufWriter<File>>> {
+        let path = &self.paths[value];
+        let created = self.created.insert(value.to_string());
+        let file = if created {
+            File::create(path)?
+        } else {
+            OpenOptions::new().append(true).open(path)?
+        };
+        let mut writer = WriterBuilder::new()
+            .delimiter(ascii_byte(self.opt.delimiter, "delimiter")?)
+            .from_writer(BufWriter::new(file));
+        if created {
+            writer.write_record(self.headers)?;
+        }
+        Ok(writer)
+    }
+
+    fn close_least_recent(&mut self) -> Result<()> {
+        let value = self
+            .open
+            .iter()
+            .min_by_key(|(_, (_, tick))| *tick)
+            .map(|(value, _)| value.clone());
+        if let Some((mut writer, _)) = value.and_then(|value| self.open.remove(&value)) {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        for (writer, _) in self.open.values_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// 合并多个文件，表头为所有文件表头的并集（按第一次出现的顺序），文件里没有的列留空
+pub fn process_csv_cat(inputs: &[String], output: &str, opt: CsvReaderOpt) -> Result<()> {
+    cat_csv(inputs, std::io::stdin().lock(), output, &opt)
+}
+
+// 每个文件要读两遍，先合并表头再输出数据，stdin 只能读一次，所以先写到临时文件里
+fn cat_csv(inputs: &[String], stdin: impl Read, output: &str, opt: &CsvReaderOpt) -> Result<()> {
+    let spooled = if inputs.iter().any(|input| input == "-") {
+        Some(spool(stdin)?)
+    } else {
+        None
+    };
+    let stdin_path = spooled
+        .as_ref()
+        .map(|file| file.path().to_string_lossy().into_owned());
+    let inputs: Vec<&str> = inputs
+        .iter()
+        .map(|input| match (&stdin_path, input.as_str()) {
+            (Some(path), "-") => path.as_str(),
+            (_, input) => input,
+        })
+        .collect();
+
+    let mut headers: Vec<String> = Vec::new();
+    for input in &inputs {
+        let mut reader = build_reader(input, opt)?;
+        for header in &resolve_headers(&mut reader, opt)? {
+            if !headers.iter().any(|h| h == header) {
+                headers.push(header.to_string());
+            }
+        }
+    }
+
+    let mut writer = csv_writer(output, opt)?;
+    writer.write_record(&headers)?;
+    for input in &inputs {
+        let mut reader = build_reader(input, opt)?;
+        let file_headers = resolve_headers(&mut reader, opt)?;
+        let indices: Vec<Option<usize>> = headers
+            .iter()
+            .map(|h| file_headers.iter().position(|fh| fh == h))
+            .collect();
+        for record in reader.records() {
+            let record = record?;
+            let row: StringRecord = indices
+                .iter()
+                .map(|i| i.and_then(|i| record.get(i)).unwrap_or_default())
+                .collect();
+            writer.write_record(&row)?;
+        }
+    }
+    finish_csv(writer)
+}
+
+// 原样保存，压缩格式和编码在读取临时文件时再判断
+fn spool(mut reader: impl Read) -> Result<NamedTempFile> {
+    let mut file = NamedTempFile::new()?;
+    std::io::copy(&mut reader, &mut file)?;
+    file.flush()?;
+    Ok(file)
+}
+
+fn column_indices(headers: &StringRecord, columns: &[String]) -> Result<Vec<usize>> {
+    columns
+        .iter()
+        .map(|column| {
+            headers
+                .iter()
+                .position(|h| h == column)
+                .ok_or_else(|| anyhow::anyhow!("Column not found in csv: {}", column))
+        })
+        .collect()
+}
+
+// 列的值作为文件名的一部分，去掉路径分隔符等不安全的字符
+fn sanitize(value: &str) -> String {
+    let name: String = value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.trim_matches('.') {
+        "" => "empty".to_string(),
+        name => name.to_string(),
+    }
+}
+
+// 不同的值清理后可能得到相同的文件名，如 "New York" 和 "New_York"，后出现的加上 _2, _3 后缀
+// 按小写比较，在大小写不敏感的文件系统上也不会写到同一个文件
+fn unique_name(name: String, names: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut n = 1;
+    while !names.insert(unique.to_lowercase()) {
+        n += 1;
+        unique = format!("{}_{}", name, n);
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    fn path(dir: &tempfile::TempDir, name: &str) -> String {
+        dir.path().join(name).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_dedupe_and_cat() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = path(&dir, "out.csv");
+
+        process_csv_dedupe(
+            "fixtures/dupes.csv",
+            &out,
+            &["id".to_string()],
+            CsvReaderOpt::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "id,name\n1,Alice\n2,Bob\n"
+        );
+
+        let inputs = [
+            "fixtures/dupes.csv".to_string(),
+            "fixtures/ages.csv".to_string(),
+        ];
+        process_csv_cat(&inputs, &out, CsvReaderOpt::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "id,name,age\n1,Alice,\n2,Bob,\n1,Alice2,\n,Alice,30\n,Bob,20\n,Carol,\n,Dave,40\n,Eve,20\n"
+        );
+    }
+
+    #[test]
+    fn test_dedupe_compressed_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = path(&dir, "out.csv.bz2");
+        process_csv_dedupe(
+            "fixtures/dupes.csv",
+            &out,
+            &["id".to_string()],
+            CsvReaderOpt::default(),
+        )
+        .unwrap();
+
+        let mut content = String::new();
+        crate::get_data_reader(&out)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "id,name\n1,Alice\n2,Bob\n");
+    }
+
+    #[test]
+    fn test_cat_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = path(&dir, "out.csv");
+        let inputs = ["-".to_string(), "fixtures/roster.csv".to_string()];
+        let stdin = "a,name\n1,2\n3,4\n".as_bytes();
+        cat_csv(&inputs, stdin, &out, &CsvReaderOpt::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "a,name,team\n1,2,\n3,4,\n,A,x\n,B,y\n,C,x\n"
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().to_str().unwrap();
+
+        let files = process_csv_split(
+            "fixtures/roster.csv",
+            out,
+            "part",
+            SplitMode::Rows(2),
+            CsvReaderOpt::default(),
+        )
+        .unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(fs::read_to_string(&files[1]).unwrap(), "name,team\nC,x\n");
+
+        let files = process_csv_split(
+            "fixtures/roster.csv",
+            out,
+            "team",
+            SplitMode::By("team".to_string()),
+            CsvReaderOpt::default(),
+        )
+        .unwrap();
+        assert!(files[0].ends_with("team_x.csv"));
+        assert_eq!(
+            fs::read_to_string(&files[0]).unwrap(),
+            "name,team\nA,x\nC,x\n"
+        );
+
+        // 清理后文件名相同的值写到不同的文件
+        let files = process_csv_split(
+            "fixtures/cities.csv",
+            out,
+            "city",
+            SplitMode::By("city".to_string()),
+            CsvReaderOpt::default(),
+        )
+        .unwrap();
+        assert!(files[0].ends_with("city_New_York.csv"));
+        assert!(files[1].ends_with("city_New_York_2.csv"));
+        assert_eq!(
+            fs::read_to_string(&files[0]).unwrap(),
+            "name,city\nA,New York\nC,New York\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&files[1]).unwrap(),
+            "name,city\nB,New_York\n"
+        );
+    }
+
+    #[test]
+    fn test_split_by_many_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = path(&dir, "events.csv");
+        let mut content = String::from("id,user\n");
+        for i in 0..1000 {
+            content.push_str(&format!("{},u{}\n", i, i % 200));
+        }
+        fs::write(&input, content).unwrap();
+
+        // 200 个不同的值超过 MAX_OPEN_WRITERS，关闭的文件之后追加写入
+        let out = path(&dir, "out");
+        let files = process_csv_split(
+            &input,
+            &out,
+            "user",
+            SplitMode::By("user".to_string()),
+            CsvReaderOpt::default(),
+        )
+        .unwrap();
+        assert_eq!(files.len(), 200);
+        assert_eq!(
+            fs::read_to_string(&files[7]).unwrap(),
+            "id,user\n7,u7\n207,u7\n407,u7\n607,u7\n807,u7\n"
+        );
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("New York"), "New_York");
+        assert_eq!(sanitize("../etc"), "_etc");
+        assert_eq!(sanitize(""), "empty");
+
+        let mut names = HashSet::new();
+        assert_eq!(unique_name(sanitize("New York"), &mut names), "New_York");
+        assert_eq!(unique_name(sanitize("New_York"), &mut names), "New_York_2");
+        assert_eq!(unique_name(sanitize("new york"), &mut names), "new_york_3");
+        assert_eq!(unique_name(sanitize("?"), &mut names), "_");
+        assert_eq!(unique_name(sanitize("*"), &mut names), "__2");
+    }
+}
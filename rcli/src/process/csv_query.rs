@@ -0,0 +1,235 @@
+use std::path::Path;
+
+use anyhow::Result;
+use csv::StringRecord;
+use rusqlite::{
+    params_from_iter,
+    types::{Value as SqlValue, ValueRef},
+    Connection,
+};
+use serde_json::{Map, Number, Value};
+
+use super::{
+    csv_convert::{build_reader, resolve_headers},
+    csv_types::{infer_column_types, ColumnType},
+    record_writer::{new_record_writer, WriterOpt},
+    CsvReaderOpt,
+};
+use crate::{cli::OutputFormat, strip_compression_ext};
+
+pub struct CsvQueryOpt {
+    pub reader: CsvReaderOpt,
+    pub writer: WriterOpt,
+    pub infer_sample: usize,
+}
+
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+// 把每个 csv 加载成内存 sqlite 里的一张表（表名为文件名去掉扩展名），然后执行 sql
+pub fn process_csv_query(
+    sql: &str,
+    inputs: &[String],
+    output: &str,
+    format: OutputFormat,
+    opt: CsvQueryOpt,
+) -> Result<()> {
+    let result = query_csv(sql, inputs, &opt)?;
+    let mut writer = new_record_writer(format, output, &opt.writer)?;
+    for row in result.rows {
+        let record: Map<String, Value> = result.columns.iter().cloned().zip(row).collect();
+        writer.write_record(&Value::Object(record))?;
+    }
+    writer.finish()
+}
+
+pub fn query_csv(sql: &str, inputs: &[String], opt: &CsvQueryOpt) -> Result<QueryResult> {
+    let conn = Connection::open_in_memory()?;
+    let mut tables: Vec<String> = Vec::new();
+    for input in inputs {
+        let table = table_name(input);
+        if tables.contains(&table) {
+            anyhow::bail!(
+                "Duplicate table name: {}, input file names must be unique",
+                table
+            );
+        }
+        load_table(&conn, &table, input, opt)?;
+        tables.push(table);
+    }
+
+    let mut stmt = conn.prepare(sql)?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut rows = Vec::new();
+    let mut cursor = stmt.query([])?;
+    while let Some(row) = cursor.next()? {
+        let row = (0..columns.len())
+            .map(|i| row.get_ref(i).map(sql_to_json))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.push(row);
+    }
+    Ok(QueryResult { columns, rows })
+}
+
+// data/players.csv => players，data/players.csv.gz 也是 players，stdin 的表名为 stdin
+fn table_name(input: &str) -> String {
+    if input == "-" {
+        return "stdin".to_string();
+    }
+    Path::new(strip_compression_ext(input))
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(input)
+        .to_string()
+}
+
+fn load_table(conn: &Connection, table: &str, input: &str, opt: &CsvQueryOpt) -> Result<()> {
+    let mut reader = build_reader(input, &opt.reader)?;
+    let headers = resolve_headers(&mut reader, &opt.reader)?;
+    let mut records = reader.into_records();
+    // 根据前 infer_sample 行推断列类型，决定 sqlite 里的列类型，这样数字列可以按数值排序和聚合
+    let sample = records
+        .by_ref()
+        .take(opt.infer_sample)
+        .collect::<Result<Vec<StringRecord>, _>>()?;
+    let types = infer_column_types(headers.len(), &sample);
+
+    let columns: Vec<String> = headers
+        .iter()
+        .zip(&types)
+        .map(|(header, ty)| format!("{} {}", quote_ident(header), sql_type(*ty)))
+        .collect();
+    conn.execute(
+        &format!(
+            "CREATE TABLE {} ({})",
+            quote_ident(table),
+            columns.join(", ")
+        ),
+        [],
+    )?;
+
+    let placeholders = vec!["?"; headers.len()].join(", ");
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(&format!(
+            "INSERT INTO {} VALUES ({})",
+            quote_ident(table),
+            placeholders
+        ))?;
+        for record in sample.into_iter().map(Ok).chain(records) {
+            let record = record?;
+            let params = (0..headers.len())
+                .map(|i| cell_to_sql(record.get(i).unwrap_or_default(), types[i]));
+            stmt.execute(params_from_iter(params))?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+// 标识符用双引号包起来，这样带空格的列名也可以在 sql 里用 "Kit Number" 引用
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_type(ty: ColumnType) -> &'static str {
+    match ty {
+        ColumnType::Integer | ColumnType::Boolean => "INTEGER",
+        ColumnType::Float => "REAL",
+        // 日期按 YYYY-MM-DD 文本存储，可以直接比较和排序
+        ColumnType::Date | ColumnType::String => "TEXT",
+    }
+}
+
+// 空单元格为 NULL，样本之后出现的无法按类型解析的值按文本存储
+fn cell_to_sql(cell: &str, ty: ColumnType) -> SqlValue {
+    match ty.convert(cell) {
+        Some(Value::Null) => SqlValue::Null,
+        Some(Value::Bool(b)) => SqlValue::Integer(b as i64),
+        Some(Value::Number(n)) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        _ => SqlValue::Text(cell.to_string()),
+    }
+}
+
+fn sql_to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(text) | ValueRef::Blob(text) => {
+            Value::String(String::from_utf8_lossy(text).into_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn opt() -> CsvQueryOpt {
+        CsvQueryOpt {
+            reader: CsvReaderOpt::default(),
+            writer: WriterOpt::default(),
+            infer_sample: 1000,
+        }
+    }
+
+    #[test]
+    fn test_query_group_by() {
+        let inputs = vec!["assets/juventus.csv".to_string()];
+        let result = query_csv(
+            "SELECT Nationality, count(*) AS players FROM juventus \
+             WHERE Nationality = 'Italy' GROUP BY Nationality",
+            &inputs,
+            &opt(),
+        )
+        .unwrap();
+        assert_eq!(result.columns, vec!["Nationality", "players"]);
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0][0], json!("Italy"));
+        assert!(result.rows[0][1].as_i64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_query_join_and_order() {
+        let inputs = vec![
+            "fixtures/players.csv".to_string(),
+            "fixtures/teams.csv".to_string(),
+        ];
+        let result = query_csv(
+            "SELECT p.name, t.team, p.goals FROM players p JOIN teams t ON p.team_id = t.id \
+             ORDER BY p.goals DESC LIMIT 2",
+            &inputs,
+            &opt(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![json!("Alice"), json!("Juventus"), json!(10)],
+                vec![json!("Bob"), json!("Torino"), json!(3)],
+            ]
+        );
+
+        let result = query_csv(
+            "SELECT goals FROM players WHERE name = 'Carol'",
+            &inputs,
+            &opt(),
+        )
+        .unwrap();
+        assert_eq!(result.rows, vec![vec![Value::Null]]);
+    }
+
+    #[test]
+    fn test_table_name() {
+        assert_eq!(table_name("assets/juventus.csv"), "juventus");
+        assert_eq!(table_name("-"), "stdin");
+    }
+}
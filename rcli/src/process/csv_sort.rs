@@ -0,0 +1,280 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use csv::{Reader, ReaderBuilder, StringRecord, Writer, WriterBuilder};
+
+use super::{
+    csv_convert::{build_reader, resolve_headers},
+    csv_ops::csv_writer,
+    CsvReaderOpt,
+};
+use crate::finish_csv;
+
+// 归并时最多同时打开的临时文件数，块更多时先分组归并，避免打开的文件过多
+const MAX_MERGE_FAN_IN: usize = 64;
+
+// 排序的列，如 --by age:desc
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub desc: bool,
+}
+
+pub struct CsvSortOpt {
+    pub reader: CsvReaderOpt,
+    // 每次在内存里排序的行数，超过时先把排好序的块写到临时文件，最后再归并
+    pub chunk_rows: usize,
+}
+
+// 归并时堆里的一项，按照 (行, 块的序号) 比较，保证排序是稳定的
+struct HeapItem<'a> {
+    record: StringRecord,
+    chunk: usize,
+    keys: &'a [(usize, bool)],
+}
+
+pub fn process_csv_sort(input: &str, output: &str, by: &[SortKey], opt: CsvSortOpt) -> Result<()> {
+    let mut reader = build_reader(input, &opt.reader)?;
+    let headers = resolve_headers(&mut reader, &opt.reader)?;
+    let keys = by
+        .iter()
+        .map(|key| {
+            headers
+                .iter()
+                .position(|h| h == key.column)
+                .map(|i| (i, key.desc))
+                .ok_or_else(|| anyhow::anyhow!("Column not found in csv: {}", key.column))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut writer = csv_writer(output, &opt.reader)?;
+    writer.write_record(&headers)?;
+
+    let chunk_rows = opt.chunk_rows.max(1);
+    let mut records = reader.into_records();
+    let mut chunk = read_chunk(&mut records, chunk_rows)?;
+    sort_records(&mut chunk, &keys);
+    // 数据能一次放进内存时直接输出，不需要临时文件
+    if chunk.len() < chunk_rows {
+        for record in &chunk {
+            writer.write_record(record)?;
+        }
+        return finish_csv(writer);
+    }
+
+    // 临时目录在 drop 时删除，出错时也会清理
+    let dir = tempfile::Builder::new().prefix("rcli-sort-").tempdir()?;
+    external_sort(
+        chunk,
+        &mut records,
+        &keys,
+        chunk_rows,
+        dir.path(),
+        &mut writer,
+    )?;
+    finish_csv(writer)
+}
+
+fn external_sort<R: std::io::Read, W: Write>(
+    first: Vec<StringRecord>,
+    records: &mut csv::StringRecordsIntoIter<R>,
+    keys: &[(usize, bool)],
+    chunk_rows: usize,
+    dir: &Path,
+    writer: &mut Writer<W>,
+) -> Result<()> {
+    let mut paths = Vec::new();
+    let mut chunk = first;
+    while !chunk.is_empty() {
+        let path = dir.join(format!("chunk-{}.csv", paths.len()));
+        let mut chunk_writer = WriterBuilder::new().has_headers(false).from_path(&path)?;
+        for record in &chunk {
+            chunk_writer.write_record(record)?;
+        }
+        chunk_writer.flush()?;
+        paths.push(path);
+
+        chunk = read_chunk(records, chunk_rows)?;
+        sort_records(&mut chunk, keys);
+    }
+
+    // 每一轮把相邻的 MAX_MERGE_FAN_IN 个块归并成一个，块的先后顺序不变，排序仍然是稳定的
+    let mut pass = 0;
+    while paths.len() > MAX_MERGE_FAN_IN {
+        pass += 1;
+        let mut merged = Vec::new();
+        for group in paths.chunks(MAX_MERGE_FAN_IN) {
+            let path = dir.join(format!("merge-{}-{}.csv", pass, merged.len()));
+            let mut chunk_writer = WriterBuilder::new().has_headers(false).from_path(&path)?;
+            merge_chunks(group, keys, &mut chunk_writer)?;
+            chunk_writer.flush()?;
+            for path in group {
+                fs::remove_file(path)?;
+            }
+            merged.push(path);
+        }
+        paths = merged;
+    }
+    merge_chunks(&paths, keys, writer)
+}
+
+fn merge_chunks<W: Write>(
+    paths: &[PathBuf],
+    keys: &[(usize, bool)],
+    writer: &mut Writer<W>,
+) -> Result<()> {
+    let mut readers: Vec<Reader<File>> = paths
+        .iter()
+        .map(|path| ReaderBuilder::new().has_headers(false).from_path(path))
+        .collect::<Result<_, _>>()?;
+    let mut heap = BinaryHeap::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = next_record(reader)? {
+            heap.push(HeapItem {
+                record,
+                chunk: i,
+                keys,
+            });
+        }
+    }
+    while let Some(item) = heap.pop() {
+        writer.write_record(&item.record)?;
+        if let Some(record) = next_record(&mut readers[item.chunk])? {
+            heap.push(HeapItem {
+                record,
+                chunk: item.chunk,
+                keys,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn read_chunk<R: std::io::Read>(
+    records: &mut csv::StringRecordsIntoIter<R>,
+    rows: usize,
+) -> Result<Vec<StringRecord>> {
+    Ok(records.take(rows).collect::<Result<Vec<_>, _>>()?)
+}
+
+fn next_record(reader: &mut Reader<File>) -> Result<Option<StringRecord>> {
+    let mut record = StringRecord::new();
+    Ok(reader.read_record(&mut record)?.then_some(record))
+}
+
+fn sort_records(records: &mut [StringRecord], keys: &[(usize, bool)]) {
+    records.sort_by(|a, b| compare_records(a, b, keys));
+}
+
+fn compare_records(a: &StringRecord, b: &StringRecord, keys: &[(usize, bool)]) -> Ordering {
+    for &(i, desc) in keys {
+        let ord = compare_cells(a.get(i).unwrap_or_default(), b.get(i).unwrap_or_default());
+        let ord = if desc { ord.reverse() } else { ord };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+// 两个都是数字时按数值比较，数字排在字符串前面，其他按字符串比较
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.total_cmp(&y),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        _ => a.cmp(b),
+    }
+}
+
+impl Ord for HeapItem<'_> {
+    // BinaryHeap 是最大堆，所以反过来比较
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_records(&self.record, &other.record, self.keys)
+            .then(self.chunk.cmp(&other.chunk))
+            .reverse()
+    }
+}
+
+impl PartialOrd for HeapItem<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapItem<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapItem<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort(input: &str, by: &[SortKey], chunk_rows: usize) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("sorted.csv");
+        let opt = CsvSortOpt {
+            reader: CsvReaderOpt::default(),
+            chunk_rows,
+        };
+        process_csv_sort(input, output.to_str().unwrap(), by, opt).unwrap();
+        fs::read_to_string(&output).unwrap()
+    }
+
+    fn by(keys: &[(&str, bool)]) -> Vec<SortKey> {
+        keys.iter()
+            .map(|(column, desc)| SortKey {
+                column: column.to_string(),
+                desc: *desc,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_in_memory_and_external() {
+        let by = by(&[("team", false), ("age", true)]);
+        let expected = "name,team,age\nDave,a,30\nBob,a,9\nCarol,b,100\nAlice,b,30\nEve,b,9\n";
+        assert_eq!(sort("fixtures/sort_players.csv", &by, 1000), expected);
+        // 每块 2 行，需要写临时文件再归并
+        assert_eq!(sort("fixtures/sort_players.csv", &by, 2), expected);
+    }
+
+    #[test]
+    fn test_sort_multi_pass_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("numbers.csv");
+        let mut content = String::from("n,seq\n");
+        for i in 0..500 {
+            content.push_str(&format!("{},{}\n", i * 37 % 50, i));
+        }
+        fs::write(&input, content).unwrap();
+        let input = input.to_str().unwrap();
+
+        // 每块 2 行，250 个块超过 MAX_MERGE_FAN_IN，需要多轮归并
+        let by = by(&[("n", false)]);
+        let external = sort(input, &by, 2);
+        assert_eq!(external, sort(input, &by, 1000));
+        let rows: Vec<&str> = external.lines().collect();
+        assert_eq!(rows.len(), 501);
+        assert_eq!(rows[1], "0,0");
+        assert_eq!(rows[2], "0,50");
+        assert_eq!(rows[500], "49,477");
+    }
+
+    #[test]
+    fn test_compare_cells() {
+        assert_eq!(compare_cells("9", "10"), Ordering::Less);
+        assert_eq!(compare_cells("b", "a"), Ordering::Greater);
+        assert_eq!(compare_cells("10", "a"), Ordering::Less);
+    }
+}
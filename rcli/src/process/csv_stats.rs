@@ -0,0 +1,552 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use super::{
+    csv_convert::{build_reader, resolve_headers},
+    csv_from_json::cell_to_string,
+    csv_types::ColumnType,
+    record_writer::{new_record_writer, WriterOpt},
+    CsvReaderOpt,
+};
+use crate::cli::OutputFormat;
+
+pub struct CsvStatsOpt {
+    pub reader: CsvReaderOpt,
+    pub writer: WriterOpt,
+    pub top: usize,
+}
+
+// 不同值超过这个数量后不再精确计数，distinct、top 和中位数改为估算，输出里 approximate 为 true
+const MAX_EXACT_DISTINCT: usize = 10_000;
+
+// HyperLogLog 有 2^12 个寄存器，标准误差约 1.04 / sqrt(4096) ≈ 1.6%
+const HLL_PRECISION: u32 = 12;
+
+// 单列的统计，逐行累加，不保存整列数据，内存有上限
+// 不同值不多时精确计数，超过 MAX_EXACT_DISTINCT 后改用 HyperLogLog、Misra-Gries 和 P² 估算
+#[derive(Debug)]
+struct ColumnStats {
+    ty: Option<ColumnType>,
+    count: usize,
+    nulls: usize,
+    counts: HashMap<String, usize>,
+    approximate: bool,
+    distinct: HyperLogLog,
+    frequent: MisraGries,
+    median: P2Median,
+    min: Option<String>,
+    max: Option<String>,
+    max_length: usize,
+    numeric: Numeric,
+}
+
+// Welford 算法计算均值和方差，只需要一次遍历
+#[derive(Debug, Default)]
+struct Numeric {
+    n: usize,
+    mean: f64,
+    m2: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+pub fn process_csv_stats(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    opt: CsvStatsOpt,
+) -> Result<()> {
+    let summaries = csv_stats(input, &opt)?;
+    let mut writer = new_record_writer(format, output, &opt.writer)?;
+    for summary in summaries {
+        // 表格里浮点数只保留两位小数，top 显示为 `value (count)`
+        let summary = match summary {
+            Value::Object(map) if format.is_tabular() => Value::Object(
+                map.into_iter()
+                    .map(|(k, v)| (k, Value::String(display(&v))))
+                    .collect(),
+            ),
+            v => v,
+        };
+        writer.write_record(&summary)?;
+    }
+    writer.finish()
+}
+
+// 每一列输出一条统计记录
+pub fn csv_stats(input: &str, opt: &CsvStatsOpt) -> Result<Vec<Value>> {
+    let mut reader = build_reader(input, &opt.reader)?;
+    let headers = resolve_headers(&mut reader, &opt.reader)?;
+    let mut columns: Vec<ColumnStats> = (0..headers.len())
+        .map(|_| ColumnStats::new(opt.top))
+        .collect();
+    for record in reader.records() {
+        let record = record?;
+        for (i, column) in columns.iter_mut().enumerate() {
+            column.update(record.get(i).unwrap_or_default());
+        }
+    }
+    Ok(headers
+        .iter()
+        .zip(columns)
+        .map(|(name, column)| column.summary(name, opt.top))
+        .collect())
+}
+
+impl ColumnStats {
+    fn new(top: usize) -> Self {
+        Self {
+            ty: None,
+            count: 0,
+            nulls: 0,
+            counts: HashMap::new(),
+            approximate: false,
+            distinct: HyperLogLog::new(),
+            // 计数器多一些，top-N 的估算更准确
+            frequent: MisraGries::new(top.max(1) * 10),
+            median: P2Median::default(),
+            min: None,
+            max: None,
+            max_length: 0,
+            numeric: Numeric::default(),
+        }
+    }
+
+    fn update(&mut self, cell: &str) {
+        let Some(cell_type) = ColumnType::infer(cell) else {
+            self.nulls += 1;
+            return;
+        };
+        self.ty = Some(match self.ty {
+            Some(ty) => ty.merge(cell_type),
+            None => cell_type,
+        });
+        self.count += 1;
+        self.max_length = self.max_length.max(cell.chars().count());
+        if self.min.as_deref().is_none_or(|min| cell < min) {
+            self.min = Some(cell.to_string());
+        }
+        if self.max.as_deref().is_none_or(|max| cell > max) {
+            self.max = Some(cell.to_string());
+        }
+        if matches!(cell_type, ColumnType::Integer | ColumnType::Float) {
+            if let Ok(v) = cell.trim().parse::<f64>() {
+                self.numeric.update(v);
+                self.median.update(v);
+            }
+        }
+        self.distinct.insert(cell);
+        self.frequent.insert(cell);
+        if self.approximate {
+            return;
+        }
+        match self.counts.get_mut(cell) {
+            Some(n) => *n += 1,
+            None => {
+                self.counts.insert(cell.to_string(), 1);
+                if self.counts.len() > MAX_EXACT_DISTINCT {
+                    self.approximate = true;
+                    self.counts = HashMap::new();
+                }
+            }
+        }
+    }
+
+    fn summary(self, name: &str, top: usize) -> Value {
+        let ty = self.ty.unwrap_or(ColumnType::String);
+        let is_numeric = matches!(ty, ColumnType::Integer | ColumnType::Float);
+
+        // 估算时 top 的次数是下限
+        let counts = if self.approximate {
+            &self.frequent.counters
+        } else {
+            &self.counts
+        };
+        let mut frequent: Vec<(&String, &usize)> = counts.iter().collect();
+        frequent.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let top: Vec<Value> = frequent
+            .into_iter()
+            .take(top)
+            .map(|(value, count)| json!({"value": value, "count": count}))
+            .collect();
+
+        let (min, max, mean, median, stddev) = if is_numeric {
+            let numeric = &self.numeric;
+            (
+                number(numeric.min, ty),
+                number(numeric.max, ty),
+                float(numeric.mean_value()),
+                float(if self.approximate {
+                    self.median.estimate()
+                } else {
+                    self.median()
+                }),
+                float(numeric.stddev()),
+            )
+        } else {
+            (
+                json!(self.min),
+                json!(self.max),
+                Value::Null,
+                Value::Null,
+                Value::Null,
+            )
+        };
+
+        json!({
+            "column": name,
+            "type": ty.to_string(),
+            "count": self.count,
+            "nulls": self.nulls,
+            "distinct": if self.approximate {
+                self.distinct.estimate()
+            } else {
+                self.counts.len()
+            },
+            "min": min,
+            "max": max,
+            "mean": mean,
+            "median": median,
+            "stddev": stddev,
+            "max_length": self.max_length,
+            "top": top,
+            "approximate": self.approximate,
+        })
+    }
+
+    // 根据不同值的出现次数计算中位数，偶数个时取中间两个数的平均值
+    fn median(&self) -> Option<f64> {
+        let mut values: Vec<(f64, usize)> = self
+            .counts
+            .iter()
+            .filter_map(|(value, count)| value.trim().parse::<f64>().ok().map(|v| (v, *count)))
+            .collect();
+        values.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let n: usize = values.iter().map(|(_, count)| count).sum();
+        if n == 0 {
+            return None;
+        }
+        let nth = |k: usize| {
+            let mut seen = 0;
+            for (value, count) in &values {
+                seen += count;
+                if seen > k {
+                    return *value;
+                }
+            }
+            unreachable!("k is less than the number of values")
+        };
+        if n % 2 == 1 {
+            Some(nth(n / 2))
+        } else {
+            Some((nth(n / 2 - 1) + nth(n / 2)) / 2.0)
+        }
+    }
+}
+
+impl Numeric {
+    fn update(&mut self, v: f64) {
+        self.n += 1;
+        let delta = v - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (v - self.mean);
+        self.min = Some(self.min.map_or(v, |min| min.min(v)));
+        self.max = Some(self.max.map_or(v, |max| max.max(v)));
+    }
+
+    fn mean_value(&self) -> Option<f64> {
+        (self.n > 0).then_some(self.mean)
+    }
+
+    // 样本标准差（除以 n - 1）
+    fn stddev(&self) -> Option<f64> {
+        (self.n > 1).then(|| (self.m2 / (self.n - 1) as f64).sqrt())
+    }
+}
+
+// 每个值按 hash 的前 12 位选择寄存器，寄存器记录剩余位里第一个 1 出现的最大位置
+#[derive(Debug)]
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        self.registers[index] = self.registers[index].max(rank as u8);
+    }
+
+    fn estimate(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        // 基数较小时用 linear counting 修正
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+}
+
+// Misra-Gries 最多保留 capacity 个计数器，计数器满了以后遇到新值时所有计数器减一
+// 出现次数超过 n / (capacity + 1) 的值一定会保留，计数的误差不超过 n / (capacity + 1)
+#[derive(Debug)]
+struct MisraGries {
+    capacity: usize,
+    counters: HashMap<String, usize>,
+}
+
+impl MisraGries {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counters: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        if let Some(n) = self.counters.get_mut(value) {
+            *n += 1;
+        } else if self.counters.len() < self.capacity {
+            self.counters.insert(value.to_string(), 1);
+        } else {
+            self.counters.retain(|_, n| {
+                *n -= 1;
+                *n > 0
+            });
+        }
+    }
+}
+
+// P² 算法用 5 个标记估算中位数，不保存数据
+#[derive(Debug, Default)]
+struct P2Median {
+    count: usize,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+}
+
+impl P2Median {
+    const INCREMENTS: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+    fn update(&mut self, v: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = v;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+                self.positions = [1.0, 2.0, 3.0, 4.0, 5.0];
+                self.desired = [1.0, 2.0, 3.0, 4.0, 5.0];
+            }
+            return;
+        }
+        self.count += 1;
+
+        let q = &mut self.heights;
+        let k = if v < q[0] {
+            q[0] = v;
+            0
+        } else if v >= q[4] {
+            q[4] = v;
+            3
+        } else {
+            (1..5).find(|&i| v < q[i]).map_or(3, |i| i - 1)
+        };
+        for n in &mut self.positions[k + 1..] {
+            *n += 1.0;
+        }
+        for (desired, inc) in self.desired.iter_mut().zip(Self::INCREMENTS) {
+            *desired += inc;
+        }
+
+        // 中间的三个标记偏离期望位置超过 1 时移动一格，优先用抛物线插值调整高度
+        for i in 1..4 {
+            let n = &mut self.positions;
+            let delta = self.desired[i] - n[i];
+            if (delta >= 1.0 && n[i + 1] - n[i] > 1.0) || (delta <= -1.0 && n[i - 1] - n[i] < -1.0)
+            {
+                let d = delta.signum();
+                let parabolic = q[i]
+                    + d / (n[i + 1] - n[i - 1])
+                        * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                            + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]));
+                q[i] = if q[i - 1] < parabolic && parabolic < q[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0.0 { i + 1 } else { i - 1 };
+                    q[i] + d * (q[j] - q[i]) / (n[j] - n[i])
+                };
+                n[i] += d;
+            }
+        }
+    }
+
+    fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            1..=4 => {
+                let mut values = self.heights[..self.count].to_vec();
+                values.sort_by(f64::total_cmp);
+                let mid = values.len() / 2;
+                Some(if values.len() % 2 == 1 {
+                    values[mid]
+                } else {
+                    (values[mid - 1] + values[mid]) / 2.0
+                })
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+}
+
+fn number(v: Option<f64>, ty: ColumnType) -> Value {
+    match (v, ty) {
+        (Some(v), ColumnType::Integer) => json!(v as i64),
+        (v, _) => float(v),
+    }
+}
+
+fn float(v: Option<f64>) -> Value {
+    v.map_or(Value::Null, |v| json!(v))
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Number(n) if n.is_f64() => format!("{:.2}", n.as_f64().unwrap_or_default()),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| format!("{} ({})", cell_to_string(&item["value"]), item["count"]))
+            .collect::<Vec<_>>()
+            .join(", "),
+        v => cell_to_string(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(input: &str) -> Vec<Value> {
+        let opt = CsvStatsOpt {
+            reader: CsvReaderOpt::default(),
+            writer: WriterOpt::default(),
+            top: 2,
+        };
+        csv_stats(input, &opt).unwrap()
+    }
+
+    #[test]
+    fn test_numeric_column_stats() {
+        let ret = stats("fixtures/ages.csv");
+        let age = &ret[1];
+        assert_eq!(age["type"], "integer");
+        assert_eq!(age["count"], 4);
+        assert_eq!(age["nulls"], 1);
+        assert_eq!(age["distinct"], 3);
+        assert_eq!(age["min"], 20);
+        assert_eq!(age["max"], 40);
+        assert_eq!(age["mean"], 27.5);
+        assert_eq!(age["median"], 25.0);
+        assert!((age["stddev"].as_f64().unwrap() - 9.574).abs() < 0.001);
+        assert_eq!(age["top"][0], json!({"value": "20", "count": 2}));
+        assert_eq!(age["top"].as_array().unwrap().len(), 2);
+        assert_eq!(age["approximate"], false);
+    }
+
+    #[test]
+    fn test_string_column_stats() {
+        let ret = stats("fixtures/ages.csv");
+        let name = &ret[0];
+        assert_eq!(name["type"], "string");
+        assert_eq!(name["min"], "Alice");
+        assert_eq!(name["max"], "Eve");
+        assert_eq!(name["mean"], Value::Null);
+        assert_eq!(name["max_length"], 5);
+    }
+
+    #[test]
+    fn test_unique_column_stats_are_approximate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ids.csv");
+        let rows = 50_000;
+        let mut content = String::from("id,score,team\n");
+        for i in 0..rows {
+            content.push_str(&format!("user-{},{},{}\n", i, i, ["a", "b", "c"][i % 3]));
+        }
+        std::fs::write(&path, content).unwrap();
+
+        let ret = stats(path.to_str().unwrap());
+        let (id, score, team) = (&ret[0], &ret[1], &ret[2]);
+        assert_eq!(id["approximate"], true);
+        assert_eq!(id["count"], rows);
+        let distinct = id["distinct"].as_f64().unwrap();
+        assert!((distinct / rows as f64 - 1.0).abs() < 0.05, "{}", distinct);
+        // 全部唯一的列没有出现次数超过误差范围的值
+        assert!(id["top"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|item| item["count"].as_u64().unwrap() <= 1));
+
+        assert_eq!(score["approximate"], true);
+        let median = score["median"].as_f64().unwrap();
+        assert!(
+            (median / (rows as f64 / 2.0) - 1.0).abs() < 0.02,
+            "{}",
+            median
+        );
+        assert_eq!(score["min"], 0);
+        assert_eq!(score["max"], rows - 1);
+
+        // 不同值少的列仍然是精确的
+        assert_eq!(team["approximate"], false);
+        assert_eq!(team["distinct"], 3);
+    }
+
+    #[test]
+    fn test_sketches() {
+        let mut hll = HyperLogLog::new();
+        for i in 0..1000 {
+            hll.insert(&(i % 100).to_string());
+        }
+        assert!((95..=105).contains(&hll.estimate()));
+
+        let mut frequent = MisraGries::new(2);
+        for value in ["a", "b", "a", "c", "a", "d", "a"] {
+            frequent.insert(value);
+        }
+        assert!(frequent.counters["a"] >= 2);
+
+        let mut median = P2Median::default();
+        assert_eq!(median.estimate(), None);
+        for v in [3.0, 1.0, 2.0, 4.0] {
+            median.update(v);
+        }
+        assert_eq!(median.estimate(), Some(2.5));
+
+        // 1..=1001 打乱顺序
+        let mut median = P2Median::default();
+        for i in 0..1001 {
+            median.update((i * 7919 % 1001 + 1) as f64);
+        }
+        assert!((median.estimate().unwrap() - 501.0).abs() < 10.0);
+    }
+}
@@ -0,0 +1,308 @@
+use core::fmt;
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d"];
+const DATETIME_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    String,
+}
+
+// schema.yaml，列可以直接写类型，也可以写详细的约束:
+// columns:
+//   Kit Number: integer
+//   DOB: string
+//   Nationality:
+//     type: string
+//     enum: [Italy, Brazil]
+//     nullable: false
+#[derive(Debug, Deserialize)]
+pub struct CsvSchema {
+    pub columns: BTreeMap<String, ColumnSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnSpec {
+    Type(ColumnType),
+    Rule(ColumnRule),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnRule {
+    #[serde(rename = "type")]
+    pub ty: Option<ColumnType>,
+    // 列必须出现在表头里
+    #[serde(default = "default_true")]
+    pub required: bool,
+    // 是否允许空单元格
+    #[serde(default = "default_true")]
+    pub nullable: bool,
+    // 整个值需要匹配的正则表达式
+    pub pattern: Option<String>,
+    #[serde(rename = "enum")]
+    pub values: Option<Vec<String>>,
+    // 数值的范围（闭区间）
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub unique: bool,
+}
+
+impl CsvSchema {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_yaml::from_str(&content)?)
+    }
+}
+
+impl ColumnSpec {
+    pub fn column_type(&self) -> Option<ColumnType> {
+        match self {
+            ColumnSpec::Type(ty) => Some(*ty),
+            ColumnSpec::Rule(rule) => rule.ty,
+        }
+    }
+
+    // 只写了类型的列也是必须存在的
+    pub fn rule(&self) -> ColumnRule {
+        match self {
+            ColumnSpec::Type(ty) => ColumnRule {
+                ty: Some(*ty),
+                required: true,
+                nullable: true,
+                pattern: None,
+                values: None,
+                min: None,
+                max: None,
+                unique: false,
+            },
+            ColumnSpec::Rule(rule) => rule.clone(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ColumnType {
+    // 推断单个单元格的类型，空单元格返回 None（即 null）
+    pub fn infer(cell: &str) -> Option<Self> {
+        let cell = cell.trim();
+        if cell.is_empty() {
+            return None;
+        }
+        let ty = if parse_integer(cell).is_some() {
+            ColumnType::Integer
+        } else if parse_float(cell).is_some() {
+            ColumnType::Float
+        } else if parse_boolean(cell).is_some() {
+            ColumnType::Boolean
+        } else if is_date(cell) {
+            ColumnType::Date
+        } else {
+            ColumnType::String
+        };
+        Some(ty)
+    }
+
+    // 合并两个类型：整数和浮点数合并为浮点数，其他不一致的情况退化为字符串
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => {
+                ColumnType::Float
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    // 按照类型转换单元格，空单元格转换为 null，无法转换时返回 None
+    pub fn convert(self, cell: &str) -> Option<Value> {
+        let trimmed = cell.trim();
+        if trimmed.is_empty() {
+            return Some(Value::Null);
+        }
+        match self {
+            ColumnType::Integer => parse_integer(trimmed).map(Value::from),
+            ColumnType::Float => parse_float(trimmed)
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+            ColumnType::Boolean => parse_boolean(trimmed).map(Value::Bool),
+            ColumnType::Date => is_date(trimmed).then(|| Value::String(trimmed.to_string())),
+            ColumnType::String => Some(Value::String(cell.to_string())),
+        }
+    }
+}
+
+impl From<ColumnType> for &'static str {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Date => "date",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+// 对每一列取样本中所有非空单元格类型的合并结果，全为空的列视为字符串
+pub fn infer_column_types<'a>(
+    width: usize,
+    records: impl IntoIterator<Item = &'a StringRecord>,
+) -> Vec<ColumnType> {
+    let mut types: Vec<Option<ColumnType>> = vec![None; width];
+    for record in records {
+        for (ty, cell) in types.iter_mut().zip(record.iter()) {
+            if let Some(cell_type) = ColumnType::infer(cell) {
+                *ty = Some(match *ty {
+                    Some(t) => t.merge(cell_type),
+                    None => cell_type,
+                });
+            }
+        }
+    }
+    types
+        .into_iter()
+        .map(|t| t.unwrap_or(ColumnType::String))
+        .collect()
+}
+
+// 带前导 0 的数字（如编号 007）保留为字符串
+fn has_leading_zero(cell: &str) -> bool {
+    let digits = cell.strip_prefix(['-', '+']).unwrap_or(cell);
+    let int_part = digits.split('.').next().unwrap_or_default();
+    int_part.len() > 1 && int_part.starts_with('0')
+}
+
+fn parse_integer(cell: &str) -> Option<i64> {
+    if has_leading_zero(cell) {
+        return None;
+    }
+    cell.parse().ok()
+}
+
+fn parse_float(cell: &str) -> Option<f64> {
+    // 排除 "inf", "NaN" 这类 rust 能解析但不是数字的写法
+    if has_leading_zero(cell) || !cell.bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    cell.parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+fn parse_boolean(cell: &str) -> Option<bool> {
+    match cell.to_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn is_date(cell: &str) -> bool {
+    parse_date(cell).is_some() || parse_datetime(cell).is_some()
+}
+
+// 只有日期部分的单元格
+pub(crate) fn parse_date(cell: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(cell, fmt).ok())
+}
+
+// 带时间的单元格，RFC 3339 带时区的时间转换为 UTC
+pub(crate) fn parse_datetime(cell: &str) -> Option<NaiveDateTime> {
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(cell, fmt).ok())
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(cell)
+                .ok()
+                .map(|dt| dt.naive_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_cell_type() {
+        assert_eq!(ColumnType::infer("10"), Some(ColumnType::Integer));
+        assert_eq!(ColumnType::infer("-3.5"), Some(ColumnType::Float));
+        assert_eq!(ColumnType::infer("TRUE"), Some(ColumnType::Boolean));
+        assert_eq!(ColumnType::infer("2024-05-01"), Some(ColumnType::Date));
+        assert_eq!(ColumnType::infer("007"), Some(ColumnType::String));
+        assert_eq!(ColumnType::infer("0.5"), Some(ColumnType::Float));
+        assert_eq!(ColumnType::infer("NaN"), Some(ColumnType::String));
+        assert_eq!(ColumnType::infer(" "), None);
+    }
+
+    #[test]
+    fn test_load_schema() {
+        let schema: CsvSchema = serde_yaml::from_str(
+            "columns:\n  kit: integer\n  email:\n    pattern: '.+@.+'\n    nullable: false\n",
+        )
+        .unwrap();
+        assert_eq!(
+            schema.columns["kit"].column_type(),
+            Some(ColumnType::Integer)
+        );
+        let rule = schema.columns["email"].rule();
+        assert_eq!(rule.ty, None);
+        assert!(rule.required && !rule.nullable);
+
+        let ret = serde_yaml::from_str::<CsvSchema>("columns:\n  kit:\n    typo: integer\n");
+        assert!(ret.is_err());
+    }
+
+    #[test]
+    fn test_infer_column_types() {
+        let records = vec![
+            StringRecord::from(vec!["1", "1.5", "", "a"]),
+            StringRecord::from(vec!["2", "2", "", "true"]),
+        ];
+        let types = infer_column_types(4, &records);
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Integer,
+                ColumnType::Float,
+                ColumnType::String,
+                ColumnType::String
+            ]
+        );
+    }
+
+    #[test]
+    fn test_convert_cell() {
+        assert_eq!(ColumnType::Integer.convert("10"), Some(Value::from(10)));
+        assert_eq!(ColumnType::Float.convert("2"), Some(Value::from(2.0)));
+        assert_eq!(
+            ColumnType::Boolean.convert("false"),
+            Some(Value::Bool(false))
+        );
+        assert_eq!(ColumnType::Integer.convert(""), Some(Value::Null));
+        assert_eq!(ColumnType::Integer.convert("abc"), None);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{collections::HashMap, io::Write};
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    csv_convert::{build_reader, resolve_headers},
+    csv_types::{ColumnRule, CsvSchema},
+    CsvReaderOpt,
+};
+use crate::{cli::ReportFormat, get_writer};
+
+// 一条校验失败的记录，line 为 csv 文件里的行号，表头缺列时为 1
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub line: u64,
+    pub column: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub reason: String,
+}
+
+// 编译好的列规则
+struct ColumnCheck {
+    index: usize,
+    name: String,
+    rule: ColumnRule,
+    pattern: Option<Regex>,
+    // 值 => 第一次出现的行号，用于唯一性检查
+    seen: HashMap<String, u64>,
+}
+
+// 校验 csv 并输出报告，有不符合 schema 的数据时返回错误（进程以非 0 退出）
+pub fn process_csv_validate(
+    input: &str,
+    schema: &str,
+    output: &str,
+    format: ReportFormat,
+    opt: CsvReaderOpt,
+) -> Result<()> {
+    let violations = validate_csv(input, schema, &opt)?;
+    let mut writer = get_writer(output)?;
+    match format {
+        ReportFormat::Text => {
+            for v in &violations {
+                match &v.value {
+                    Some(value) => writeln!(
+                        writer,
+                        "line {}, column {:?}, value {:?}: {}",
+                        v.line, v.column, value, v.reason
+                    )?,
+                    None => writeln!(
+                        writer,
+                        "line {}, column {:?}: {}",
+                        v.line, v.column, v.reason
+                    )?,
+                }
+            }
+            if violations.is_empty() {
+                writeln!(writer, "{} is valid", input)?;
+            }
+        }
+        ReportFormat::Json => {
+            let report = serde_json::json!({
+                "valid": violations.is_empty(),
+                "violations": violations,
+            });
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writeln!(writer)?;
+        }
+    }
+    writer.finish()?;
+
+    if !violations.is_empty() {
+        let mut lines: Vec<u64> = violations.iter().map(|v| v.line).collect();
+        lines.dedup();
+        anyhow::bail!(
+            "Validation failed: {} violations in {} lines",
+            violations.len(),
+            lines.len()
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_csv(input: &str, schema: &str, opt: &CsvReaderOpt) -> Result<Vec<Violation>> {
+    let schema = CsvSchema::load(schema)?;
+    let mut reader = build_reader(input, opt)?;
+    let headers = resolve_headers(&mut reader, opt)?;
+
+    let mut violations = Vec::new();
+    let mut checks = Vec::new();
+    for (name, spec) in &schema.columns {
+        let rule = spec.rule();
+        let Some(index) = headers.iter().position(|h| h == name) else {
+            if rule.required {
+                violations.push(Violation {
+                    line: 1,
+                    column: name.clone(),
+                    value: None,
+                    reason: "required column is missing".to_string(),
+                });
+            }
+            continue;
+        };
+        // 正则需要匹配整个值
+        let pattern = match &rule.pattern {
+            Some(pattern) => Some(
+                Regex::new(&format!("^(?:{})$", pattern))
+                    .map_err(|e| anyhow::anyhow!("Invalid pattern for column {}: {}", name, e))?,
+            ),
+            None => None,
+        };
+        checks.push(ColumnCheck {
+            index,
+            name: name.clone(),
+            rule,
+            pattern,
+            seen: HashMap::new(),
+        });
+    }
+    // 报告里同一行的错误按列在文件里的顺序排列
+    checks.sort_by_key(|check| check.index);
+
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        for check in checks.iter_mut() {
+            let cell = record.get(check.index).unwrap_or_default();
+            for reason in check.check(cell, line) {
+                violations.push(Violation {
+                    line,
+                    column: check.name.clone(),
+                    value: Some(cell.to_string()),
+                    reason,
+                });
+            }
+        }
+    }
+    Ok(violations)
+}
+
+impl ColumnCheck {
+    // 返回所有不满足的约束，唯一性单独记录，其他约束失败的值也会参与后面的重复检查
+    fn check(&mut self, cell: &str, line: u64) -> Vec<String> {
+        let rule = &self.rule;
+        if cell.trim().is_empty() {
+            return match rule.nullable {
+                true => Vec::new(),
+                false => vec!["value is required".to_string()],
+            };
+        }
+
+        let mut reasons = Vec::new();
+        let value = match rule.ty {
+            Some(ty) => {
+                let value = ty.convert(cell);
+                if value.is_none() {
+                    reasons.push(format!("can not parse as {}", ty));
+                }
+                value
+            }
+            None => Some(Value::String(cell.to_string())),
+        };
+        if let (Some(pattern), Some(source)) = (&self.pattern, &rule.pattern) {
+            if !pattern.is_match(cell) {
+                reasons.push(format!("does not match pattern {}", source));
+            }
+        }
+        if let Some(values) = &rule.values {
+            if !values.iter().any(|v| v == cell) {
+                reasons.push(format!("must be one of [{}]", values.join(", ")));
+            }
+        }
+        // 类型不对时已经报告过，不再重复报告范围检查
+        if let (Some(value), true) = (&value, rule.min.is_some() || rule.max.is_some()) {
+            let number = match value {
+                Value::Number(n) => n.as_f64(),
+                _ => cell.trim().parse::<f64>().ok(),
+            };
+            match number {
+                None => reasons.push("range check requires a number".to_string()),
+                Some(number) => {
+                    if let Some(min) = rule.min.filter(|min| number < *min) {
+                        reasons.push(format!("less than minimum {}", min));
+                    }
+                    if let Some(max) = rule.max.filter(|max| number > *max) {
+                        reasons.push(format!("greater than maximum {}", max));
+                    }
+                }
+            }
+        }
+        if rule.unique {
+            match self.seen.get(cell) {
+                Some(first) => {
+                    reasons.push(format!("duplicate value, first seen at line {}", first))
+                }
+                None => {
+                    self.seen.insert(cell.to_string(), line);
+                }
+            }
+        }
+        reasons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_csv() {
+        let violations = validate_csv(
+            "fixtures/partners.csv",
+            "fixtures/partners_rules.yaml",
+            &CsvReaderOpt::default(),
+        )
+        .unwrap();
+        let reasons: Vec<(u64, &str, &str)> = violations
+            .iter()
+            .map(|v| (v.line, v.column.as_str(), v.reason.as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (1, "country", "required column is missing"),
+                (3, "email", "does not match pattern [^@]+@[^@]+"),
+                (3, "age", "less than minimum 18"),
+                (4, "id", "duplicate value, first seen at line 2"),
+                (4, "email", "value is required"),
+                (4, "age", "can not parse as integer"),
+                (4, "tier", "must be one of [gold, silver]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_csv_reports_every_rule() {
+        let violations = validate_csv(
+            "fixtures/codes.csv",
+            "fixtures/codes_rules.yaml",
+            &CsvReaderOpt::default(),
+        )
+        .unwrap();
+        let reasons: Vec<(u64, &str, &str)> = violations
+            .iter()
+            .map(|v| (v.line, v.column.as_str(), v.reason.as_str()))
+            .collect();
+        // note 允许为空，owner 不允许；第 2 行的 abc 不合法，但仍然是第 3 行重复检查的第一次出现
+        assert_eq!(
+            reasons,
+            vec![
+                (1, "region", "required column is missing"),
+                (2, "code", "does not match pattern [A-Z]{3}"),
+                (2, "code", "must be one of [ABC, XYZ]"),
+                (3, "code", "does not match pattern [A-Z]{3}"),
+                (3, "code", "must be one of [ABC, XYZ]"),
+                (3, "code", "duplicate value, first seen at line 2"),
+                (3, "qty", "greater than maximum 10"),
+                (3, "owner", "value is required"),
+                (4, "qty", "can not parse as integer"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_process_csv_validate_json_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("report.json");
+        let err = process_csv_validate(
+            "fixtures/codes.csv",
+            "fixtures/codes_rules.yaml",
+            output.to_str().unwrap(),
+            ReportFormat::Json,
+            CsvReaderOpt::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Validation failed: 9 violations in 4 lines"
+        );
+
+        let report: Value =
+            serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(report["valid"], false);
+        let violations = report["violations"].as_array().unwrap();
+        assert_eq!(violations.len(), 9);
+        assert_eq!(
+            violations[0],
+            serde_json::json!({"line": 1, "column": "region", "reason": "required column is missing"})
+        );
+        assert_eq!(violations[1]["value"], "abc");
+    }
+}
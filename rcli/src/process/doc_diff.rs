@@ -0,0 +1,699 @@
+use std::{
+    fmt,
+    io::{IsTerminal, Write},
+};
+
+use anyhow::Result;
+use serde_json::{json, Number, Value};
+
+use super::convert::{load_document, load_document_with_format, to_document};
+use crate::{cli::DocDiffFormat, cli::DocFormat, get_writer};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+// 两个数组长度的乘积超过这个值时不再计算 LCS，按下标逐个比较
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+pub struct DocDiffOpt {
+    // 两个文件的格式，None 表示根据扩展名或者内容判断
+    pub from: Option<DocFormat>,
+    // 数组按多重集合比较，忽略元素的顺序
+    pub ignore_order: bool,
+}
+
+pub struct PatchOpt {
+    pub from: Option<DocFormat>,
+    // 输出的格式，None 表示根据输出文件的扩展名判断，否则和输入相同
+    pub to: Option<DocFormat>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+// 路径里的数组下标是按顺序应用前面的变化之后的位置，所以可以直接转换为 JSON Patch
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocChange {
+    Added {
+        path: Vec<PathSegment>,
+        value: Value,
+    },
+    Removed {
+        path: Vec<PathSegment>,
+        value: Value,
+    },
+    Changed {
+        path: Vec<PathSegment>,
+        old: Value,
+        new: Value,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct DocDiff {
+    pub changes: Vec<DocChange>,
+}
+
+enum Edit {
+    Keep,
+    Delete(usize),
+    Insert(usize),
+}
+
+pub fn process_diff(
+    old: &str,
+    new: &str,
+    output: &str,
+    format: DocDiffFormat,
+    opt: DocDiffOpt,
+) -> Result<()> {
+    let old = load_document(old, opt.from)?;
+    let new = load_document(new, opt.from)?;
+    let diff = diff_documents(&old, &new, opt.ignore_order);
+    let mut writer = get_writer(output)?;
+    match format {
+        DocDiffFormat::Tree => {
+            // 只有输出到终端时才加颜色
+            let color = output == "-" && std::io::stdout().is_terminal();
+            writer.write_all(diff.to_text(color).as_bytes())?;
+        }
+        DocDiffFormat::Patch => {
+            serde_json::to_writer_pretty(&mut writer, &diff.to_patch())?;
+            writeln!(writer)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+pub fn process_patch(input: &str, patch: &str, output: &str, opt: PatchOpt) -> Result<()> {
+    let (doc, format) = load_document_with_format(input, opt.from)?;
+    let patch = load_document(patch, None)?;
+    let doc = apply_patch(doc, &patch)?;
+    let to = opt
+        .to
+        .or_else(|| DocFormat::from_path(output))
+        .unwrap_or(format);
+    let mut writer = get_writer(output)?;
+    writer.write_all(to_document(&doc, to, true)?.as_bytes())?;
+    writer.finish()?;
+    Ok(())
+}
+
+// 忽略 key 的顺序和数字的写法（1 和 1.0 相同），ignore_order 时数组也忽略顺序
+pub fn diff_documents(old: &Value, new: &Value, ignore_order: bool) -> DocDiff {
+    let mut diff = DocDiff::default();
+    diff.compare(&mut Vec::new(), old, new, ignore_order);
+    diff
+}
+
+impl DocDiff {
+    fn compare(
+        &mut self,
+        path: &mut Vec<PathSegment>,
+        old: &Value,
+        new: &Value,
+        ignore_order: bool,
+    ) {
+        match (old, new) {
+            (Value::Object(a), Value::Object(b)) => {
+                for (key, av) in a {
+                    path.push(PathSegment::Key(key.clone()));
+                    match b.get(key) {
+                        Some(bv) => self.compare(path, av, bv, ignore_order),
+                        None => self.changes.push(DocChange::Removed {
+                            path: path.clone(),
+                            value: av.clone(),
+                        }),
+                    }
+                    path.pop();
+                }
+                for (key, bv) in b.iter().filter(|(key, _)| !a.contains_key(*key)) {
+                    let mut path = path.clone();
+                    path.push(PathSegment::Key(key.clone()));
+                    self.changes.push(DocChange::Added {
+                        path,
+                        value: bv.clone(),
+                    });
+                }
+            }
+            (Value::Array(a), Value::Array(b)) if ignore_order => {
+                self.compare_unordered(path, a, b)
+            }
+            (Value::Array(a), Value::Array(b)) => self.compare_ordered(path, a, b),
+            (a, b) if equal(a, b, ignore_order) => {}
+            (a, b) => self.changes.push(DocChange::Changed {
+                path: path.clone(),
+                old: a.clone(),
+                new: b.clone(),
+            }),
+        }
+    }
+
+    // 按 LCS 对齐两个数组，相邻的删除和新增配对为修改，继续比较里面的内容
+    fn compare_ordered(&mut self, path: &mut Vec<PathSegment>, a: &[Value], b: &[Value]) {
+        let mut pos = 0;
+        let mut deletes = Vec::new();
+        let mut inserts = Vec::new();
+        let mut edits = edit_script(a, b);
+        edits.push(Edit::Keep);
+        for edit in edits {
+            match edit {
+                Edit::Delete(i) => deletes.push(i),
+                Edit::Insert(j) => inserts.push(j),
+                Edit::Keep => {
+                    for (&i, &j) in deletes.iter().zip(&inserts) {
+                        path.push(PathSegment::Index(pos));
+                        self.compare(path, &a[i], &b[j], false);
+                        path.pop();
+                        pos += 1;
+                    }
+                    let paired = deletes.len().min(inserts.len());
+                    for &i in &deletes[paired..] {
+                        self.changes.push(DocChange::Removed {
+                            path: child(path, PathSegment::Index(pos)),
+                            value: a[i].clone(),
+                        });
+                    }
+                    for &j in &inserts[paired..] {
+                        self.changes.push(DocChange::Added {
+                            path: child(path, PathSegment::Index(pos)),
+                            value: b[j].clone(),
+                        });
+                        pos += 1;
+                    }
+                    deletes.clear();
+                    inserts.clear();
+                    pos += 1;
+                }
+            }
+        }
+    }
+
+    // 元素按多重集合匹配，先从后往前删除多余的元素，再把新增的元素追加到末尾
+    fn compare_unordered(&mut self, path: &[PathSegment], a: &[Value], b: &[Value]) {
+        let mut matched = vec![false; a.len()];
+        let mut added = Vec::new();
+        for bv in b {
+            match (0..a.len()).find(|&i| !matched[i] && equal(&a[i], bv, true)) {
+                Some(i) => matched[i] = true,
+                None => added.push(bv),
+            }
+        }
+        let removed: Vec<usize> = (0..a.len()).filter(|&i| !matched[i]).collect();
+        for &i in removed.iter().rev() {
+            self.changes.push(DocChange::Removed {
+                path: child(path, PathSegment::Index(i)),
+                value: a[i].clone(),
+            });
+        }
+        let len = a.len() - removed.len();
+        for (k, value) in added.into_iter().enumerate() {
+            self.changes.push(DocChange::Added {
+                path: child(path, PathSegment::Index(len + k)),
+                value: value.clone(),
+            });
+        }
+    }
+
+    // 按文档结构缩进显示，+ 新增，- 删除，~ 修改
+    pub fn to_text(&self, color: bool) -> String {
+        let paint = |code: &str, text: String| {
+            if color {
+                format!("{}{}{}", code, text, RESET)
+            } else {
+                text
+            }
+        };
+        let mut out = String::new();
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+        let mut current: &[PathSegment] = &[];
+        for change in &self.changes {
+            let path = change.path();
+            let Some((last, parent)) = path.split_last() else {
+                // 顶层的值类型不同时整个替换
+                if let DocChange::Changed { old, new, .. } = change {
+                    changed += 1;
+                    out.push_str(&paint(YELLOW, format!("~ (root): {} -> {}", old, new)));
+                    out.push('\n');
+                }
+                continue;
+            };
+            let common = current
+                .iter()
+                .zip(parent)
+                .take_while(|(a, b)| a == b)
+                .count();
+            for (depth, segment) in parent.iter().enumerate().skip(common) {
+                out.push_str(&format!("{}{}:\n", "  ".repeat(depth), segment));
+            }
+            current = parent;
+
+            let indent = "  ".repeat(parent.len());
+            let line = match change {
+                DocChange::Added { value, .. } => {
+                    added += 1;
+                    paint(GREEN, format!("+ {}: {}", last, value))
+                }
+                DocChange::Removed { value, .. } => {
+                    removed += 1;
+                    paint(RED, format!("- {}: {}", last, value))
+                }
+                DocChange::Changed { old, new, .. } => {
+                    changed += 1;
+                    paint(YELLOW, format!("~ {}: {} -> {}", last, old, new))
+                }
+            };
+            out.push_str(&indent);
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "{} added, {} removed, {} changed\n",
+            added, removed, changed
+        ));
+        out
+    }
+
+    // RFC 6902 JSON Patch，按顺序应用即可把旧文档变成新文档
+    pub fn to_patch(&self) -> Value {
+        let ops = self
+            .changes
+            .iter()
+            .map(|change| match change {
+                DocChange::Added { path, value } => {
+                    json!({"op": "add", "path": to_pointer(path), "value": value})
+                }
+                DocChange::Removed { path, .. } => {
+                    json!({"op": "remove", "path": to_pointer(path)})
+                }
+                DocChange::Changed { path, new, .. } => {
+                    json!({"op": "replace", "path": to_pointer(path), "value": new})
+                }
+            })
+            .collect();
+        Value::Array(ops)
+    }
+}
+
+impl DocChange {
+    pub fn path(&self) -> &[PathSegment] {
+        match self {
+            DocChange::Added { path, .. }
+            | DocChange::Removed { path, .. }
+            | DocChange::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, "{}", key),
+            PathSegment::Index(i) => write!(f, "[{}]", i),
+        }
+    }
+}
+
+fn child(path: &[PathSegment], segment: PathSegment) -> Vec<PathSegment> {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
+
+// 先去掉相同的头尾，中间部分用 LCS 计算，太大时按下标逐个比较
+fn edit_script(a: &[Value], b: &[Value]) -> Vec<Edit> {
+    let prefix = a
+        .iter()
+        .zip(b)
+        .take_while(|(x, y)| equal(x, y, false))
+        .count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| equal(x, y, false))
+        .count();
+    let (n, m) = (a.len() - prefix - suffix, b.len() - prefix - suffix);
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|_| Edit::Keep).collect();
+    if n * m > MAX_LCS_CELLS {
+        edits.extend((0..n).map(|i| Edit::Delete(prefix + i)));
+        edits.extend((0..m).map(|j| Edit::Insert(prefix + j)));
+    } else {
+        let a = &a[prefix..prefix + n];
+        let b = &b[prefix..prefix + m];
+        // lcs[i][j] 是 a[i..] 和 b[j..] 的最长公共子序列长度
+        let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if equal(&a[i], &b[j], false) {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && equal(&a[i], &b[j], false) {
+                edits.push(Edit::Keep);
+                i += 1;
+                j += 1;
+            } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+                edits.push(Edit::Insert(prefix + j));
+                j += 1;
+            } else {
+                edits.push(Edit::Delete(prefix + i));
+                i += 1;
+            }
+        }
+    }
+    edits.extend((0..suffix).map(|_| Edit::Keep));
+    edits
+}
+
+// 两边都是整数时精确比较，2^53 以上的整数转成浮点数会丢失精度
+// 有一边是浮点数时按浮点数比较，这样 1 和 1.0 相等
+fn numbers_equal(x: &Number, y: &Number) -> bool {
+    if x.is_f64() || y.is_f64() {
+        x.as_f64() == y.as_f64()
+    } else {
+        x == y
+    }
+}
+
+fn equal(a: &Value, b: &Value, ignore_order: bool) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => numbers_equal(x, y),
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(k, xv)| y.get(k).is_some_and(|yv| equal(xv, yv, ignore_order)))
+        }
+        (Value::Array(x), Value::Array(y)) if x.len() != y.len() => false,
+        (Value::Array(x), Value::Array(y)) if ignore_order => {
+            let mut matched = vec![false; y.len()];
+            x.iter().all(
+                |xv| match (0..y.len()).find(|&i| !matched[i] && equal(xv, &y[i], true)) {
+                    Some(i) => {
+                        matched[i] = true;
+                        true
+                    }
+                    None => false,
+                },
+            )
+        }
+        (Value::Array(x), Value::Array(y)) => x.iter().zip(y).all(|(xv, yv)| equal(xv, yv, false)),
+        (a, b) => a == b,
+    }
+}
+
+// RFC 6901：~ 转义为 ~0，/ 转义为 ~1
+fn to_pointer(path: &[PathSegment]) -> String {
+    path.iter()
+        .map(|segment| match segment {
+            PathSegment::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+            PathSegment::Index(i) => format!("/{}", i),
+        })
+        .collect()
+}
+
+fn parse_pointer(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        anyhow::bail!("Invalid JSON pointer {:?}: must start with /", pointer);
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+// 按 RFC 6902 依次应用 add / remove / replace / move / copy / test
+pub fn apply_patch(mut doc: Value, patch: &Value) -> Result<Value> {
+    let Value::Array(ops) = patch else {
+        anyhow::bail!("JSON Patch must be an array of operations");
+    };
+    for (i, op) in ops.iter().enumerate() {
+        apply_operation(&mut doc, op)
+            .map_err(|e| anyhow::anyhow!("Patch operation {} failed: {}", i, e))?;
+    }
+    Ok(doc)
+}
+
+fn apply_operation(doc: &mut Value, op: &Value) -> Result<()> {
+    let field = |name: &str| -> Result<&Value> {
+        op.get(name)
+            .ok_or_else(|| anyhow::anyhow!("missing \"{}\" in {}", name, op))
+    };
+    let pointer = |name: &str| -> Result<(String, Vec<String>)> {
+        let Value::String(p) = field(name)? else {
+            anyhow::bail!("\"{}\" must be a string", name);
+        };
+        Ok((p.clone(), parse_pointer(p)?))
+    };
+    let (path, tokens) = pointer("path")?;
+    match field("op")?.as_str() {
+        Some("add") => add(doc, &tokens, field("value")?.clone()),
+        Some("remove") => remove(doc, &tokens).map(|_| ()),
+        Some("replace") => {
+            let target = get_mut(doc, &tokens)
+                .ok_or_else(|| anyhow::anyhow!("path {:?} does not exist", path))?;
+            *target = field("value")?.clone();
+            Ok(())
+        }
+        Some("move") => {
+            let (from, from_tokens) = pointer("from")?;
+            if tokens.len() > from_tokens.len() && tokens.starts_with(&from_tokens) {
+                anyhow::bail!("cannot move {:?} into its own child {:?}", from, path);
+            }
+            let value = remove(doc, &from_tokens)?;
+            add(doc, &tokens, value)
+        }
+        Some("copy") => {
+            let (from, from_tokens) = pointer("from")?;
+            let value = get_mut(doc, &from_tokens)
+                .ok_or_else(|| anyhow::anyhow!("path {:?} does not exist", from))?
+                .clone();
+            add(doc, &tokens, value)
+        }
+        Some("test") => {
+            let expected = field("value")?;
+            match get_mut(doc, &tokens) {
+                Some(actual) if equal(actual, expected, false) => Ok(()),
+                Some(actual) => anyhow::bail!(
+                    "test failed at {:?}: expected {}, found {}",
+                    path,
+                    expected,
+                    actual
+                ),
+                None => anyhow::bail!("path {:?} does not exist", path),
+            }
+        }
+        _ => anyhow::bail!("unsupported op in {}", op),
+    }
+}
+
+fn get_mut<'a>(doc: &'a mut Value, tokens: &[String]) -> Option<&'a mut Value> {
+    tokens.iter().try_fold(doc, |value, token| match value {
+        Value::Object(map) => map.get_mut(token),
+        Value::Array(items) => {
+            let i = array_index(token, items.len(), false).ok()?;
+            items.get_mut(i)
+        }
+        _ => None,
+    })
+}
+
+// "-" 表示数组末尾，只在 add 时可用
+fn array_index(token: &str, len: usize, allow_end: bool) -> Result<usize> {
+    if token == "-" && allow_end {
+        return Ok(len);
+    }
+    // 不允许前导 0 和符号
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    let index: usize = if valid { token.parse()? } else { usize::MAX };
+    let max = if allow_end {
+        len
+    } else {
+        len.saturating_sub(1)
+    };
+    if index > max || (!allow_end && len == 0) {
+        anyhow::bail!("array index {:?} is out of bounds", token);
+    }
+    Ok(index)
+}
+
+fn parent_of<'a>(doc: &'a mut Value, tokens: &'a [String]) -> Result<(&'a mut Value, &'a String)> {
+    let (last, parent) = tokens
+        .split_last()
+        .expect("root path is handled by the caller");
+    let parent = get_mut(doc, parent)
+        .ok_or_else(|| anyhow::anyhow!("parent of {:?} does not exist", to_display(tokens)))?;
+    Ok((parent, last))
+}
+
+fn add(doc: &mut Value, tokens: &[String], value: Value) -> Result<()> {
+    if tokens.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (parent, last) = parent_of(doc, tokens)?;
+    match parent {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(items) => {
+            let i = array_index(last, items.len(), true)?;
+            items.insert(i, value);
+        }
+        _ => anyhow::bail!("cannot add to a scalar at {:?}", to_display(tokens)),
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut Value, tokens: &[String]) -> Result<Value> {
+    if tokens.is_empty() {
+        return Ok(std::mem::take(doc));
+    }
+    let (parent, last) = parent_of(doc, tokens)?;
+    let removed = match parent {
+        Value::Object(map) => map.shift_remove(last),
+        Value::Array(items) => {
+            let i = array_index(last, items.len(), false)?;
+            Some(items.remove(i))
+        }
+        _ => None,
+    };
+    removed.ok_or_else(|| anyhow::anyhow!("path {:?} does not exist", to_display(tokens)))
+}
+
+fn to_display(tokens: &[String]) -> String {
+    tokens
+        .iter()
+        .map(|t| format!("/{}", t.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(old: &Value, new: &Value, ignore_order: bool) -> DocDiff {
+        let diff = diff_documents(old, new, ignore_order);
+        let patched = apply_patch(old.clone(), &diff.to_patch()).unwrap();
+        assert!(equal(&patched, new, ignore_order), "{} != {}", patched, new);
+        diff
+    }
+
+    #[test]
+    fn test_diff_documents() {
+        let old = json!({
+            "name": "api",
+            "replicas": 2,
+            "env": {"LOG": "info", "DEBUG": true},
+            "ports": [80, 443],
+            "hosts": ["a", "b", "c"]
+        });
+        // key 的顺序和 2 / 2.0 的差别不算变化
+        let new = json!({
+            "replicas": 2.0,
+            "name": "api",
+            "env": {"LOG": "debug", "a/b": 1},
+            "ports": [80, 443, 8080],
+            "hosts": ["a", "x", "c", "d"]
+        });
+        let diff = round_trip(&old, &new, false);
+        assert_eq!(
+            diff.to_text(false),
+            "env:\n  ~ LOG: \"info\" -> \"debug\"\n  - DEBUG: true\n  + a/b: 1\nports:\n  + [2]: 8080\nhosts:\n  ~ [1]: \"b\" -> \"x\"\n  + [3]: \"d\"\n3 added, 1 removed, 2 changed\n"
+        );
+        assert_eq!(
+            diff.to_patch()[2],
+            json!({"op": "add", "path": "/env/a~1b", "value": 1})
+        );
+
+        let diff = round_trip(&json!([1, 2, 3, 4]), &json!([0, 1, 3, 4, 5]), false);
+        assert_eq!(
+            diff.to_patch(),
+            json!([
+                {"op": "add", "path": "/0", "value": 0},
+                {"op": "remove", "path": "/2"},
+                {"op": "add", "path": "/4", "value": 5}
+            ])
+        );
+        assert!(diff_documents(&old, &old, false).changes.is_empty());
+        round_trip(&json!({"a": 1}), &json!([1]), false);
+    }
+
+    #[test]
+    fn test_diff_ignore_order() {
+        let old = json!({"tags": ["a", "b", "c"], "items": [{"id": 1, "v": [1, 2]}, {"id": 2}]});
+        let new = json!({"tags": ["c", "a", "d"], "items": [{"id": 2}, {"id": 1, "v": [2, 1]}]});
+        let diff = round_trip(&old, &new, true);
+        assert_eq!(
+            diff.to_patch(),
+            json!([
+                {"op": "remove", "path": "/tags/1"},
+                {"op": "add", "path": "/tags/2", "value": "d"}
+            ])
+        );
+        assert_eq!(diff_documents(&old, &new, false).changes.len(), 5);
+    }
+
+    #[test]
+    fn test_diff_large_integers() {
+        let old: Value = serde_json::from_str(r#"{"id": 9007199254740993, "n": 1}"#).unwrap();
+        let new: Value = serde_json::from_str(r#"{"id": 9007199254740992, "n": 1.0}"#).unwrap();
+        let diff = round_trip(&old, &new, false);
+        assert_eq!(
+            diff.to_patch(),
+            json!([{"op": "replace", "path": "/id", "value": 9007199254740992u64}])
+        );
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let doc = json!({"a": {"b": [1, 2]}, "c": "x"});
+        let patch = json!([
+            {"op": "test", "path": "/c", "value": "x"},
+            {"op": "add", "path": "/a/b/-", "value": 3},
+            {"op": "move", "from": "/c", "path": "/a/c"},
+            {"op": "copy", "from": "/a/b/0", "path": "/first"},
+            {"op": "replace", "path": "/a/b/1", "value": 20},
+            {"op": "remove", "path": "/a/b/0"}
+        ]);
+        assert_eq!(
+            apply_patch(doc.clone(), &patch).unwrap(),
+            json!({"a": {"b": [20, 3], "c": "x"}, "first": 1})
+        );
+
+        let err = |patch: Value| apply_patch(doc.clone(), &patch).unwrap_err().to_string();
+        assert_eq!(
+            err(json!([{"op": "remove", "path": "/missing"}])),
+            "Patch operation 0 failed: path \"/missing\" does not exist"
+        );
+        assert_eq!(
+            err(json!([{"op": "test", "path": "/c", "value": "y"}])),
+            "Patch operation 0 failed: test failed at \"/c\": expected \"y\", found \"x\""
+        );
+        assert_eq!(
+            err(json!([{"op": "add", "path": "/a/b/5", "value": 1}])),
+            "Patch operation 0 failed: array index \"5\" is out of bounds"
+        );
+        assert_eq!(
+            err(json!([{"op": "move", "from": "/a", "path": "/a/b/c"}])),
+            "Patch operation 0 failed: cannot move \"/a\" into its own child \"/a/b/c\""
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+use crate::cli::InputEncoding;
+
+// 自动检测编码时读取的样本大小
+const SAMPLE_SIZE: usize = 64 * 1024;
+
+// 把输入转码为 UTF-8，并去掉开头的 BOM
+// 有 BOM 时以 BOM 为准，UTF-8 的输入原样输出，交给后面的解析去报告非法字节
+pub fn decode_reader(mut reader: Box<dyn Read>, encoding: InputEncoding) -> Result<Box<dyn Read>> {
+    let encoding = match encoding {
+        InputEncoding::Fixed(encoding) => Some(encoding),
+        InputEncoding::Auto => {
+            let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+            reader
+                .by_ref()
+                .take(SAMPLE_SIZE as u64)
+                .read_to_end(&mut sample)?;
+            let encoding = detect_encoding(&sample, sample.len() < SAMPLE_SIZE);
+            // 把读出来的样本放回到输入的前面
+            reader = Box::new(Cursor::new(sample).chain(reader));
+            encoding
+        }
+    };
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .bom_override(true)
+        .utf8_passthru(true)
+        .strip_bom(true)
+        .build(reader);
+    Ok(Box::new(decoder))
+}
+
+// 根据样本猜测编码，返回 None 表示根据 BOM 判断
+fn detect_encoding(sample: &[u8], last: bool) -> Option<&'static Encoding> {
+    if Encoding::for_bom(sample).is_some() {
+        return None;
+    }
+    // 样本可能在一个字符的中间截断，这种情况也算是合法的 UTF-8
+    match std::str::from_utf8(sample) {
+        Ok(_) => return Some(UTF_8),
+        Err(e) if e.error_len().is_none() && !last => return Some(UTF_8),
+        Err(_) => {}
+    }
+    if let Some(encoding) = detect_utf16(sample) {
+        return Some(encoding);
+    }
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Allow);
+    detector.feed(sample, last);
+    Some(detector.guess(None, Utf8Detection::Allow))
+}
+
+// 没有 BOM 的 UTF-16：ASCII 字符的高位字节是 0，奇数或偶数位置有大量的 0
+fn detect_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    if sample.len() < 4 {
+        return None;
+    }
+    let half = sample.len() / 2;
+    let even = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&b| b == 0)
+        .count();
+    if odd * 10 > half * 3 && even * 10 < half {
+        Some(UTF_16LE)
+    } else if even * 10 > half * 3 && odd * 10 < half {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use encoding_rs::{GBK, SHIFT_JIS};
+
+    fn decode(bytes: Vec<u8>, encoding: InputEncoding) -> String {
+        let mut reader = decode_reader(Box::new(Cursor::new(bytes)), encoding).unwrap();
+        let mut ret = String::new();
+        reader.read_to_string(&mut ret).unwrap();
+        ret
+    }
+
+    fn utf16le(text: &str, bom: bool) -> Vec<u8> {
+        let mut bytes = if bom { vec![0xFF, 0xFE] } else { vec![] };
+        for unit in text.encode_utf16() {
+            bytes.extend(unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_decode_with_bom() {
+        let text = "name,city\nAlice,Turin\n";
+        let mut utf8 = b"\xEF\xBB\xBF".to_vec();
+        utf8.extend(text.as_bytes());
+        assert_eq!(decode(utf8, InputEncoding::Auto), text);
+        assert_eq!(decode(utf16le(text, true), InputEncoding::Auto), text);
+        // 指定的编码和 BOM 不一致时以 BOM 为准
+        assert_eq!(decode(utf16le(text, true), InputEncoding::Fixed(GBK)), text);
+    }
+
+    #[test]
+    fn test_decode_auto_detect() {
+        let text = "姓名,城市,备注\n张三,北京,这是一个从旧的 Windows 系统导出的文件\n李四,上海,中文内容需要正确地转换为 UTF-8 编码\n";
+        let (gbk, _, _) = GBK.encode(text);
+        assert_eq!(decode(gbk.to_vec(), InputEncoding::Auto), text);
+        assert_eq!(decode(utf16le(text, false), InputEncoding::Auto), text);
+        assert_eq!(decode(text.as_bytes().to_vec(), InputEncoding::Auto), text);
+    }
+
+    #[test]
+    fn test_decode_fixed_encoding() {
+        let text = "名前,都市\n山田,東京\n";
+        let (sjis, _, _) = SHIFT_JIS.encode(text);
+        assert_eq!(decode(sjis.to_vec(), InputEncoding::Fixed(SHIFT_JIS)), text);
+    }
+}
@@ -0,0 +1,853 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime};
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+use super::record_writer::{new_record_writer, WriterOpt};
+use crate::cli::OutputFormat;
+
+const FIRST_NAMES: &[&str] = &[
+    "James",
+    "Mary",
+    "John",
+    "Patricia",
+    "Robert",
+    "Jennifer",
+    "Michael",
+    "Linda",
+    "William",
+    "Elizabeth",
+    "David",
+    "Barbara",
+    "Richard",
+    "Susan",
+    "Joseph",
+    "Jessica",
+    "Thomas",
+    "Sarah",
+    "Charles",
+    "Karen",
+    "Marco",
+    "Giulia",
+    "Luca",
+    "Sofia",
+    "Wei",
+    "Mei",
+    "Hiroshi",
+    "Yuki",
+    "Carlos",
+    "Lucia",
+    "Ahmed",
+    "Fatima",
+    "Ivan",
+    "Olga",
+    "Pierre",
+    "Camille",
+    "Hans",
+    "Anna",
+    "Raj",
+    "Priya",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Smith",
+    "Johnson",
+    "Williams",
+    "Brown",
+    "Jones",
+    "Garcia",
+    "Miller",
+    "Davis",
+    "Rodriguez",
+    "Martinez",
+    "Wilson",
+    "Anderson",
+    "Taylor",
+    "Thomas",
+    "Moore",
+    "Jackson",
+    "Martin",
+    "Lee",
+    "Thompson",
+    "White",
+    "Rossi",
+    "Russo",
+    "Ferrari",
+    "Esposito",
+    "Wang",
+    "Li",
+    "Zhang",
+    "Chen",
+    "Tanaka",
+    "Suzuki",
+    "Silva",
+    "Santos",
+    "Muller",
+    "Schmidt",
+    "Dubois",
+    "Laurent",
+    "Ivanov",
+    "Petrov",
+    "Patel",
+    "Singh",
+];
+
+const STREETS: &[&str] = &[
+    "Main",
+    "Oak",
+    "Pine",
+    "Maple",
+    "Cedar",
+    "Elm",
+    "Washington",
+    "Lake",
+    "Hill",
+    "Park",
+    "Sunset",
+    "River",
+    "Church",
+    "Mill",
+    "Garden",
+    "Station",
+    "Victoria",
+    "King",
+    "Queen",
+    "High",
+];
+
+const STREET_SUFFIXES: &[&str] = &["St", "Ave", "Rd", "Blvd", "Ln", "Dr", "Way", "Ct"];
+
+// (城市, 国家)，同一行的城市和国家保持一致
+const LOCATIONS: &[(&str, &str)] = &[
+    ("New York", "United States"),
+    ("San Francisco", "United States"),
+    ("Chicago", "United States"),
+    ("Toronto", "Canada"),
+    ("London", "United Kingdom"),
+    ("Manchester", "United Kingdom"),
+    ("Paris", "France"),
+    ("Lyon", "France"),
+    ("Berlin", "Germany"),
+    ("Munich", "Germany"),
+    ("Turin", "Italy"),
+    ("Milan", "Italy"),
+    ("Madrid", "Spain"),
+    ("Lisbon", "Portugal"),
+    ("Amsterdam", "Netherlands"),
+    ("Stockholm", "Sweden"),
+    ("Tokyo", "Japan"),
+    ("Osaka", "Japan"),
+    ("Beijing", "China"),
+    ("Shanghai", "China"),
+    ("Singapore", "Singapore"),
+    ("Sydney", "Australia"),
+    ("Sao Paulo", "Brazil"),
+    ("Mumbai", "India"),
+];
+
+const DOMAINS: &[&str] = &[
+    "example.com",
+    "example.org",
+    "example.net",
+    "mail.test",
+    "inbox.test",
+];
+
+const COMPANY_SUFFIXES: &[&str] = &[
+    "Group",
+    "Holdings",
+    "Labs",
+    "Partners",
+    "Systems",
+    "Industries",
+    "Solutions",
+    "& Co",
+];
+
+const WORDS: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "commodo",
+];
+
+// 没有指定行数时，输出的表默认 10 行，被引用的表默认 100 行
+const DEFAULT_ROWS: usize = 10;
+const DEFAULT_REF_ROWS: usize = 100;
+
+// people.yaml，单表可以直接写 columns，多表写在 tables 下面，用 ref 引用其他表的列:
+// tables:
+//   companies:
+//     rows: 20
+//     columns:
+//       id: sequence
+//       name: company
+//   people:
+//     columns:
+//       id: uuid
+//       name: name
+//       email: email
+//       age: { type: integer, min: 18, max: 80 }
+//       status: { type: enum, values: [active, inactive], weights: [9, 1] }
+//       company_id: { type: ref, table: companies, column: id }
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FakeSchemaFile {
+    rows: Option<usize>,
+    columns: Option<serde_yaml::Mapping>,
+    tables: Option<serde_yaml::Mapping>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TableFile {
+    rows: Option<usize>,
+    columns: serde_yaml::Mapping,
+}
+
+#[derive(Debug)]
+pub struct FakeSchema {
+    pub tables: Vec<FakeTable>,
+}
+
+#[derive(Debug)]
+pub struct FakeTable {
+    pub name: String,
+    pub rows: Option<usize>,
+    // 保持 schema 里列的顺序
+    pub columns: Vec<(String, FieldSpec)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    Sequence,
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    Datetime,
+    Enum,
+    Uuid,
+    FirstName,
+    LastName,
+    Name,
+    Email,
+    Phone,
+    Address,
+    City,
+    Country,
+    Company,
+    Text,
+    Ref,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum FieldSpec {
+    Kind(FieldKind),
+    Rule(Box<FieldRule>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldRule {
+    #[serde(rename = "type")]
+    pub kind: FieldKind,
+    // integer / float 的范围（闭区间），sequence 的起始值用 start
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub start: Option<i64>,
+    // float 保留的小数位数
+    pub decimals: Option<u32>,
+    // date / datetime 的范围
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // enum 的候选值和权重
+    pub values: Option<Vec<Value>>,
+    pub weights: Option<Vec<f64>>,
+    // ref 引用的表和列
+    pub table: Option<String>,
+    pub column: Option<String>,
+    // text 的单词数
+    pub words: Option<usize>,
+    // 生成 null 的概率
+    #[serde(default)]
+    pub null_rate: f64,
+}
+
+pub struct FakeOpt {
+    pub writer: WriterOpt,
+    // 输出的表，默认为最后一个
+    pub table: Option<String>,
+    pub rows: Option<usize>,
+    pub seed: Option<u64>,
+}
+
+// 编译好的列生成器
+enum Generator {
+    Sequence(i64),
+    Integer(i64, i64),
+    Float(f64, f64, u32),
+    Boolean,
+    Date(NaiveDate, i64),
+    Datetime(NaiveDateTime, i64),
+    Enum(Vec<Value>, Option<WeightedIndex<f64>>),
+    Uuid,
+    FirstName,
+    LastName,
+    Name,
+    Email,
+    Phone,
+    Address,
+    City,
+    Country,
+    Company,
+    Text(usize),
+    // 被引用的表的这一列的所有值
+    Ref(Vec<Value>),
+}
+
+struct Field {
+    name: String,
+    generator: Generator,
+    null_rate: f64,
+}
+
+// 同一行里的姓名、地点只生成一次，这样 name / email、city / country 能对得上
+#[derive(Default)]
+struct RowContext {
+    first: Option<&'static str>,
+    last: Option<&'static str>,
+    location: Option<(&'static str, &'static str)>,
+}
+
+struct Faker<'a> {
+    schema: &'a FakeSchema,
+    seed: u64,
+    // 已经生成的被引用的表
+    cache: HashMap<String, Vec<Map<String, Value>>>,
+}
+
+impl FakeSchema {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let file: FakeSchemaFile = serde_yaml::from_str(&content)?;
+        let mut tables = Vec::new();
+        match (file.columns, file.tables) {
+            (Some(columns), None) => {
+                let name = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or("main")
+                    .to_string();
+                tables.push(FakeTable::new(name, file.rows, columns)?);
+            }
+            (None, Some(specs)) => {
+                for (name, spec) in specs {
+                    let name = table_key(name)?;
+                    let spec: TableFile = serde_yaml::from_value(spec)
+                        .map_err(|e| anyhow::anyhow!("Invalid table {}: {}", name, e))?;
+                    tables.push(FakeTable::new(name, spec.rows, spec.columns)?);
+                }
+            }
+            _ => anyhow::bail!("Schema must have either columns or tables"),
+        }
+        if tables.is_empty() {
+            anyhow::bail!("No table found in schema");
+        }
+        Ok(Self { tables })
+    }
+
+    fn table(&self, name: &str) -> Result<&FakeTable> {
+        self.tables
+            .iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Table not found in schema: {}", name))
+    }
+}
+
+impl FakeTable {
+    fn new(name: String, rows: Option<usize>, columns: serde_yaml::Mapping) -> Result<Self> {
+        let columns = columns
+            .into_iter()
+            .map(|(column, spec)| {
+                let column = table_key(column)?;
+                let spec: FieldSpec = serde_yaml::from_value(spec)
+                    .map_err(|e| anyhow::anyhow!("Invalid column {}.{}: {}", name, column, e))?;
+                Ok((column, spec))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            name,
+            rows,
+            columns,
+        })
+    }
+}
+
+impl FieldSpec {
+    fn rule(&self) -> FieldRule {
+        match self {
+            FieldSpec::Kind(kind) => FieldRule {
+                kind: *kind,
+                min: None,
+                max: None,
+                start: None,
+                decimals: None,
+                from: None,
+                to: None,
+                values: None,
+                weights: None,
+                table: None,
+                column: None,
+                words: None,
+                null_rate: 0.0,
+            },
+            FieldSpec::Rule(rule) => (**rule).clone(),
+        }
+    }
+}
+
+fn table_key(key: serde_yaml::Value) -> Result<String> {
+    match key {
+        serde_yaml::Value::String(s) => Ok(s),
+        v => anyhow::bail!("Expect a string name, got: {:?}", v),
+    }
+}
+
+pub fn process_fake(schema: &str, output: &str, format: OutputFormat, opt: FakeOpt) -> Result<()> {
+    let schema = FakeSchema::load(schema)?;
+    let seed = opt.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut writer = new_record_writer(format, output, &opt.writer)?;
+    fake_rows(&schema, opt.table.as_deref(), opt.rows, seed, |row| {
+        writer.write_record(&Value::Object(row))
+    })?;
+    writer.finish()
+}
+
+// 生成表的数据，每生成一行调用一次 f，不会把输出的表放在内存里
+pub fn fake_rows(
+    schema: &FakeSchema,
+    table: Option<&str>,
+    rows: Option<usize>,
+    seed: u64,
+    mut f: impl FnMut(Map<String, Value>) -> Result<()>,
+) -> Result<()> {
+    let table = match table {
+        Some(name) => schema.table(name)?,
+        None => schema
+            .tables
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("No table found in schema"))?,
+    };
+    let rows = rows.or(table.rows).unwrap_or(DEFAULT_ROWS);
+    let mut faker = Faker {
+        schema,
+        seed,
+        cache: HashMap::new(),
+    };
+    let fields = faker.compile(table, &mut HashSet::new())?;
+    let mut rng = table_rng(seed, &table.name);
+    for i in 0..rows {
+        f(generate_row(&fields, i, &mut rng))?;
+    }
+    Ok(())
+}
+
+impl Faker<'_> {
+    fn compile(&mut self, table: &FakeTable, visiting: &mut HashSet<String>) -> Result<Vec<Field>> {
+        visiting.insert(table.name.clone());
+        let fields = table
+            .columns
+            .iter()
+            .map(|(name, spec)| {
+                let rule = spec.rule();
+                if !(0.0..=1.0).contains(&rule.null_rate) {
+                    anyhow::bail!("Column {}: null_rate must be between 0 and 1", name);
+                }
+                let generator = self
+                    .generator(&rule, visiting)
+                    .map_err(|e| anyhow::anyhow!("Column {}.{}: {}", table.name, name, e))?;
+                Ok(Field {
+                    name: name.clone(),
+                    generator,
+                    null_rate: rule.null_rate,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        visiting.remove(&table.name);
+        Ok(fields)
+    }
+
+    fn generator(&mut self, rule: &FieldRule, visiting: &mut HashSet<String>) -> Result<Generator> {
+        let generator = match rule.kind {
+            FieldKind::Sequence => Generator::Sequence(rule.start.unwrap_or(1)),
+            FieldKind::Integer => {
+                let (min, max) = range(rule, 0.0, 1000.0)?;
+                // 取整之后范围可能为空，比如 min: 1.2, max: 1.8
+                let (low, high) = (min.ceil(), max.floor());
+                if low > high {
+                    anyhow::bail!("No integer between {} and {}", min, max);
+                }
+                Generator::Integer(low as i64, high as i64)
+            }
+            FieldKind::Float => {
+                let (min, max) = range(rule, 0.0, 1.0)?;
+                if !(max - min).is_finite() {
+                    anyhow::bail!("Range between {} and {} is too large", min, max);
+                }
+                Generator::Float(min, max, rule.decimals.unwrap_or(2))
+            }
+            FieldKind::Boolean => Generator::Boolean,
+            FieldKind::Date => {
+                let from = parse_date(rule.from.as_deref(), "2000-01-01")?;
+                let to = parse_date(rule.to.as_deref(), "2025-12-31")?;
+                if from > to {
+                    anyhow::bail!("from must not be after to");
+                }
+                Generator::Date(from, (to - from).num_days())
+            }
+            FieldKind::Datetime => {
+                let from = parse_datetime(rule.from.as_deref(), "2000-01-01")?;
+                let to = parse_datetime(rule.to.as_deref(), "2025-12-31")?;
+                if from > to {
+                    anyhow::bail!("from must not be after to");
+                }
+                Generator::Datetime(from, (to - from).num_seconds())
+            }
+            FieldKind::Enum => {
+                let values = match &rule.values {
+                    Some(values) if !values.is_empty() => values.clone(),
+                    _ => anyhow::bail!("enum requires non-empty values"),
+                };
+                let weights = match &rule.weights {
+                    Some(weights) if weights.len() != values.len() => {
+                        anyhow::bail!("weights must have the same length as values")
+                    }
+                    Some(weights) => Some(WeightedIndex::new(weights)?),
+                    None => None,
+                };
+                Generator::Enum(values, weights)
+            }
+            FieldKind::Uuid => Generator::Uuid,
+            FieldKind::FirstName => Generator::FirstName,
+            FieldKind::LastName => Generator::LastName,
+            FieldKind::Name => Generator::Name,
+            FieldKind::Email => Generator::Email,
+            FieldKind::Phone => Generator::Phone,
+            FieldKind::Address => Generator::Address,
+            FieldKind::City => Generator::City,
+            FieldKind::Country => Generator::Country,
+            FieldKind::Company => Generator::Company,
+            FieldKind::Text => Generator::Text(rule.words.unwrap_or(8).max(1)),
+            FieldKind::Ref => {
+                let (Some(table), Some(column)) = (&rule.table, &rule.column) else {
+                    anyhow::bail!("ref requires table and column");
+                };
+                let values = self.referenced_values(table, column, visiting)?;
+                if values.is_empty() {
+                    anyhow::bail!("Referenced table {} has no rows", table);
+                }
+                Generator::Ref(values)
+            }
+        };
+        Ok(generator)
+    }
+
+    // 被引用的表用自己的随机数种子生成，所以单独用 --table 输出时得到的是同样的数据
+    fn referenced_values(
+        &mut self,
+        table: &str,
+        column: &str,
+        visiting: &mut HashSet<String>,
+    ) -> Result<Vec<Value>> {
+        if !self.cache.contains_key(table) {
+            if visiting.contains(table) {
+                anyhow::bail!("Circular reference to table {}", table);
+            }
+            let spec = self.schema.table(table)?;
+            let fields = self.compile(spec, visiting)?;
+            let mut rng = table_rng(self.seed, table);
+            let rows = (0..spec.rows.unwrap_or(DEFAULT_REF_ROWS))
+                .map(|i| generate_row(&fields, i, &mut rng))
+                .collect();
+            self.cache.insert(table.to_string(), rows);
+        }
+        let rows = &self.cache[table];
+        if let Some(row) = rows.first() {
+            if !row.contains_key(column) {
+                anyhow::bail!("Column not found in table {}: {}", table, column);
+            }
+        }
+        Ok(rows
+            .iter()
+            .filter_map(|row| row.get(column))
+            .filter(|v| !v.is_null())
+            .cloned()
+            .collect())
+    }
+}
+
+fn generate_row(fields: &[Field], index: usize, rng: &mut StdRng) -> Map<String, Value> {
+    let mut ctx = RowContext::default();
+    fields
+        .iter()
+        .map(|field| {
+            // 先生成值再判断 null，保证 null_rate 不影响其他列的随机序列
+            let value = field.generator.generate(index, rng, &mut ctx);
+            let value = if field.null_rate > 0.0 && rng.gen_bool(field.null_rate) {
+                Value::Null
+            } else {
+                value
+            };
+            (field.name.clone(), value)
+        })
+        .collect()
+}
+
+impl Generator {
+    fn generate(&self, index: usize, rng: &mut StdRng, ctx: &mut RowContext) -> Value {
+        match self {
+            Generator::Sequence(start) => Value::from(start + index as i64),
+            Generator::Integer(min, max) => Value::from(rng.gen_range(*min..=*max)),
+            Generator::Float(min, max, decimals) => {
+                let factor = 10f64.powi(*decimals as i32);
+                let value = (rng.gen_range(*min..=*max) * factor).round() / factor;
+                Number::from_f64(value).map_or(Value::Null, Value::Number)
+            }
+            Generator::Boolean => Value::Bool(rng.gen()),
+            Generator::Date(from, days) => {
+                let date = *from + chrono::Duration::days(rng.gen_range(0..=*days));
+                Value::String(date.format("%Y-%m-%d").to_string())
+            }
+            Generator::Datetime(from, seconds) => {
+                let datetime = *from + chrono::Duration::seconds(rng.gen_range(0..=*seconds));
+                Value::String(datetime.format("%Y-%m-%dT%H:%M:%S").to_string())
+            }
+            Generator::Enum(values, weights) => {
+                let i = match weights {
+                    Some(weights) => weights.sample(rng),
+                    None => rng.gen_range(0..values.len()),
+                };
+                values[i].clone()
+            }
+            Generator::Uuid => Value::String(uuid_v4(rng)),
+            Generator::FirstName => Value::String(ctx.first(rng).to_string()),
+            Generator::LastName => Value::String(ctx.last(rng).to_string()),
+            Generator::Name => Value::String(format!("{} {}", ctx.first(rng), ctx.last(rng))),
+            Generator::Email => {
+                let local = format!("{}.{}", ctx.first(rng), ctx.last(rng)).to_lowercase();
+                let n: u16 = rng.gen_range(1..1000);
+                Value::String(format!("{}{}@{}", local, n, pick(DOMAINS, rng)))
+            }
+            Generator::Phone => Value::String(format!(
+                "+1-{}-555-{:04}",
+                rng.gen_range(200..1000),
+                rng.gen_range(0..10000)
+            )),
+            Generator::Address => Value::String(format!(
+                "{} {} {}",
+                rng.gen_range(1..10000),
+                pick(STREETS, rng),
+                pick(STREET_SUFFIXES, rng)
+            )),
+            Generator::City => Value::String(ctx.location(rng).0.to_string()),
+            Generator::Country => Value::String(ctx.location(rng).1.to_string()),
+            Generator::Company => Value::String(format!(
+                "{} {}",
+                pick(LAST_NAMES, rng),
+                pick(COMPANY_SUFFIXES, rng)
+            )),
+            Generator::Text(words) => {
+                let mut text = (0..*words)
+                    .map(|_| pick(WORDS, rng))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if let Some(first) = text.get_mut(0..1) {
+                    first.make_ascii_uppercase();
+                }
+                text.push('.');
+                Value::String(text)
+            }
+            Generator::Ref(values) => values[rng.gen_range(0..values.len())].clone(),
+        }
+    }
+}
+
+impl RowContext {
+    fn first(&mut self, rng: &mut StdRng) -> &'static str {
+        self.first.get_or_insert_with(|| pick(FIRST_NAMES, rng))
+    }
+
+    fn last(&mut self, rng: &mut StdRng) -> &'static str {
+        self.last.get_or_insert_with(|| pick(LAST_NAMES, rng))
+    }
+
+    fn location(&mut self, rng: &mut StdRng) -> (&'static str, &'static str) {
+        *self.location.get_or_insert_with(|| pick(LOCATIONS, rng))
+    }
+}
+
+fn pick<T: Copy>(items: &[T], rng: &mut StdRng) -> T {
+    items[rng.gen_range(0..items.len())]
+}
+
+// 每个表使用 seed 和表名派生的随机数种子，FNV-1a 保证跨版本稳定
+fn table_rng(seed: u64, table: &str) -> StdRng {
+    let hash = table.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    StdRng::seed_from_u64(seed ^ hash)
+}
+
+fn uuid_v4(rng: &mut StdRng) -> String {
+    let mut bytes: [u8; 16] = rng.gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn range(rule: &FieldRule, min: f64, max: f64) -> Result<(f64, f64)> {
+    let min = rule.min.unwrap_or(min);
+    let max = rule.max.unwrap_or(max.max(min));
+    if !min.is_finite() || !max.is_finite() {
+        anyhow::bail!("min and max must be finite numbers");
+    }
+    if min > max {
+        anyhow::bail!("min must not be greater than max");
+    }
+    Ok((min, max))
+}
+
+fn parse_date(value: Option<&str>, default: &str) -> Result<NaiveDate> {
+    let value = value.unwrap_or(default);
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|e| anyhow::anyhow!("Invalid date {}: {}", value, e))
+}
+
+fn parse_datetime(value: Option<&str>, default: &str) -> Result<NaiveDateTime> {
+    let value = value.unwrap_or(default);
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| parse_date(Some(value), default).map(|d| d.and_time(chrono::NaiveTime::MIN)))
+        .map_err(|e| anyhow::anyhow!("Invalid datetime {}: {}", value, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str) -> FakeSchema {
+        FakeSchema::load(format!("fixtures/{}.yaml", name)).unwrap()
+    }
+
+    fn collect(schema: &FakeSchema, table: Option<&str>, rows: usize, seed: u64) -> Vec<Value> {
+        let mut ret = Vec::new();
+        fake_rows(schema, table, Some(rows), seed, |row| {
+            ret.push(Value::Object(row));
+            Ok(())
+        })
+        .unwrap();
+        ret
+    }
+
+    #[test]
+    fn test_fake_rows() {
+        let schema = load("fake_players");
+        let rows = collect(&schema, None, 50, 42);
+        assert_eq!(rows.len(), 50);
+        for row in &rows {
+            let age = row["age"].as_i64().unwrap();
+            assert!((18..=80).contains(&age));
+            let salary = row["salary"].as_f64().unwrap();
+            assert!((1000.0..=2000.0).contains(&salary));
+            assert!(row["joined"].as_str().unwrap().starts_with("2020-"));
+            assert_eq!(row["status"], "active");
+            let company = row["company_id"].as_i64().unwrap();
+            assert!((100..105).contains(&company));
+            assert!(row["nickname"].is_null());
+            assert_eq!(row["id"].as_str().unwrap().len(), 36);
+            // email 和同一行的姓名一致
+            let email = row["email"].as_str().unwrap();
+            let first = row["first_name"].as_str().unwrap().to_lowercase();
+            let last = row["last_name"].as_str().unwrap().to_lowercase();
+            assert!(email.starts_with(&format!("{}.{}", first, last)));
+        }
+        let keys: Vec<&String> = rows[0].as_object().unwrap().keys().collect();
+        assert_eq!(keys[0], "id");
+        assert_eq!(keys[10], "nickname");
+    }
+
+    #[test]
+    fn test_fake_rows_with_seed() {
+        let schema = load("fake_players");
+        assert_eq!(collect(&schema, None, 20, 7), collect(&schema, None, 20, 7));
+        assert_ne!(collect(&schema, None, 20, 7), collect(&schema, None, 20, 8));
+        let companies = collect(&schema, Some("companies"), 5, 7);
+        assert_eq!(companies[0]["id"], 100);
+        assert_eq!(companies[4]["id"], 104);
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        let schema = load("fake_circular");
+        let ret = fake_rows(&schema, None, Some(1), 1, |_| Ok(()));
+        assert!(ret.unwrap_err().to_string().contains("Circular reference"));
+
+        let schema = load("fake_invalid");
+        let errors = [
+            ("reversed", "min must not be greater than max"),
+            ("no_integer", "No integer between 1.2 and 1.8"),
+            ("nan", "min and max must be finite numbers"),
+            ("infinite", "min and max must be finite numbers"),
+            ("too_large", "is too large"),
+        ];
+        for (table, error) in errors {
+            let ret = fake_rows(&schema, Some(table), Some(1), 1, |_| Ok(()));
+            let err = ret.unwrap_err().to_string();
+            assert!(err.contains(error), "{}: {}", table, err);
+        }
+    }
+}
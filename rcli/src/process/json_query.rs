@@ -0,0 +1,2414 @@
+use std::{cmp::Ordering, io::Write};
+
+use anyhow::Result;
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+    Engine as _,
+};
+use regex::{Captures, Regex, RegexBuilder};
+use serde_json::{Map, Number, Value};
+
+use super::convert::{load_document, to_document};
+use crate::{cli::DocFormat, get_writer};
+
+// 支持的内置函数和参数个数，解析时检查，避免运行到一半才报错
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("empty", 0),
+    ("error", 0),
+    ("error", 1),
+    ("not", 0),
+    ("length", 0),
+    ("keys", 0),
+    ("keys_unsorted", 0),
+    ("values", 0),
+    ("has", 1),
+    ("in", 1),
+    ("contains", 1),
+    ("inside", 1),
+    ("map", 1),
+    ("map_values", 1),
+    ("select", 1),
+    ("recurse", 0),
+    ("recurse", 1),
+    ("type", 0),
+    ("add", 0),
+    ("any", 0),
+    ("any", 1),
+    ("all", 0),
+    ("all", 1),
+    ("flatten", 0),
+    ("flatten", 1),
+    ("range", 1),
+    ("range", 2),
+    ("floor", 0),
+    ("ceil", 0),
+    ("round", 0),
+    ("sqrt", 0),
+    ("abs", 0),
+    ("tostring", 0),
+    ("tonumber", 0),
+    ("tojson", 0),
+    ("fromjson", 0),
+    ("ascii_downcase", 0),
+    ("ascii_upcase", 0),
+    ("trim", 0),
+    ("ltrim", 0),
+    ("rtrim", 0),
+    ("ltrimstr", 1),
+    ("rtrimstr", 1),
+    ("startswith", 1),
+    ("endswith", 1),
+    ("split", 1),
+    ("join", 1),
+    ("test", 1),
+    ("test", 2),
+    ("capture", 1),
+    ("capture", 2),
+    ("sub", 2),
+    ("sub", 3),
+    ("gsub", 2),
+    ("gsub", 3),
+    ("sort", 0),
+    ("sort_by", 1),
+    ("group_by", 1),
+    ("unique", 0),
+    ("unique_by", 1),
+    ("min", 0),
+    ("max", 0),
+    ("min_by", 1),
+    ("max_by", 1),
+    ("reverse", 0),
+    ("to_entries", 0),
+    ("from_entries", 0),
+    ("with_entries", 1),
+    ("first", 0),
+    ("first", 1),
+    ("last", 0),
+    ("last", 1),
+    ("limit", 2),
+    ("walk", 1),
+    ("objects", 0),
+    ("arrays", 0),
+    ("strings", 0),
+    ("numbers", 0),
+    ("booleans", 0),
+    ("nulls", 0),
+    ("iterables", 0),
+    ("scalars", 0),
+];
+
+const FORMATS: &[&str] = &[
+    "csv", "tsv", "json", "text", "base64", "base64d", "html", "uri",
+];
+
+// 超过这个范围的整数结果保持为浮点数
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+// 收集到内存里的结果个数的上限，避免 [range(1e11)] 这样的过滤器耗尽内存
+const MAX_VALUES: usize = 10_000_000;
+
+// 字符串拼接和重复的结果的最大字节数
+const MAX_STRING_BYTES: usize = 256 * 1024 * 1024;
+
+pub struct JsonQueryOpt {
+    // 输入的格式，None 表示根据扩展名或者内容判断
+    pub from: Option<DocFormat>,
+    pub to: DocFormat,
+    // 字符串结果直接输出，不加引号
+    pub raw_output: bool,
+    pub compact: bool,
+    // --arg / --argjson 传入的变量
+    pub vars: Vec<(String, Value)>,
+}
+
+// jq 语法的一个子集：路径、管道、select / map、对象和数组构造、常用的内置函数
+// 不支持 try / catch、def、del、paths 和赋值运算符，可以用 `?` 和 `//` 代替 try
+#[derive(Debug)]
+pub struct JsonQuery {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    Recurse,
+    Field(String),
+    Ident(String),
+    Var(String),
+    Format(String),
+    Num(f64),
+    Str(Vec<StrPart>),
+    Pipe,
+    Comma,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Colon,
+    Semicolon,
+    Question,
+    Alt,
+    Op(BinOp),
+}
+
+// 字符串里的 \(...) 插值保存原始文本和位置，由解析器再解析
+#[derive(Debug, Clone, PartialEq)]
+enum StrPart {
+    Lit(String),
+    Interp(usize, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Identity,
+    Recurse,
+    Literal(Value),
+    Str(Vec<StrExpr>),
+    Format(String),
+    Index(Box<Expr>, Box<Expr>),
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    Iterate(Box<Expr>),
+    Try(Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+    Alt(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    Neg(Box<Expr>),
+    Array(Option<Box<Expr>>),
+    Object(Vec<(Expr, Expr)>),
+    Var(String),
+    // source as $name | body
+    Bind(Box<Expr>, String, Box<Expr>),
+    // reduce source as $name (init; update)
+    Reduce(Box<Expr>, String, Box<Expr>, Box<Expr>),
+    If(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug)]
+enum StrExpr {
+    Lit(String),
+    Expr(Expr),
+}
+
+// 变量按定义顺序保存，查找时后定义的优先
+type Env = Vec<(String, Value)>;
+
+// 接收过滤器的每个输出，返回 false 时停止求值
+type Emit<'a> = dyn FnMut(Value) -> Result<bool> + 'a;
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+pub fn process_query(input: &str, output: &str, filter: &str, opt: JsonQueryOpt) -> Result<()> {
+    let query = JsonQuery::compile(filter)?;
+    // ndjson 和 rcli csv 的输出一样整体视为数组，用 .[] 逐行处理
+    let value = load_document(input, opt.from)?;
+
+    // 每个结果单独输出，ndjson 即每个结果一行 json
+    let (to, pretty) = match opt.to {
+        DocFormat::Ndjson => (DocFormat::Json, false),
+        to => (to, !opt.compact),
+    };
+    let mut writer = get_writer(output)?;
+    let mut count = 0;
+    query.for_each(&value, &opt.vars, |result| {
+        match result {
+            Value::String(s) if opt.raw_output => writeln!(writer, "{}", s)?,
+            result => {
+                if to == DocFormat::Yaml && count > 0 {
+                    writer.write_all(b"---\n")?;
+                }
+                writer.write_all(to_document(&result, to, pretty)?.as_bytes())?;
+            }
+        }
+        count += 1;
+        Ok(())
+    })?;
+    writer.finish()?;
+    Ok(())
+}
+
+impl JsonQuery {
+    pub fn compile(filter: &str) -> Result<Self> {
+        let tokens = tokenize(filter, 0)?;
+        if tokens.is_empty() {
+            return Ok(Self {
+                expr: Expr::Identity,
+            });
+        }
+        let expr = Parser::new(tokens).parse_all()?;
+        Ok(Self { expr })
+    }
+
+    pub fn run(&self, input: &Value, vars: &[(String, Value)]) -> Result<Vec<Value>> {
+        eval(&self.expr, input, &vars.to_vec())
+    }
+
+    // 逐个处理结果，不把所有结果都放在内存里
+    pub fn for_each(
+        &self,
+        input: &Value,
+        vars: &[(String, Value)],
+        mut f: impl FnMut(Value) -> Result<()>,
+    ) -> Result<()> {
+        each(&self.expr, input, &vars.to_vec(), &mut |v| {
+            f(v)?;
+            Ok(true)
+        })?;
+        Ok(())
+    }
+}
+
+impl Parser {
+    fn new(tokens: Vec<(usize, Token)>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn parse_all(mut self) -> Result<Expr> {
+        let expr = self.parse_pipe()?;
+        if let Some((col, token)) = self.tokens.get(self.pos) {
+            anyhow::bail!(
+                "Parse error at position {}: unexpected {}",
+                col,
+                describe(token)
+            );
+        }
+        Ok(expr)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(name)) if name == keyword)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(usize, Token)> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => anyhow::bail!(
+                "Parse error: expected {}, found end of expression",
+                expected
+            ),
+        }
+    }
+
+    fn expect(&mut self, token: &Token, expected: &str) -> Result<()> {
+        match self.next(expected)? {
+            (_, t) if &t == token => Ok(()),
+            (col, t) => anyhow::bail!(
+                "Parse error at position {}: expected {}, found {}",
+                col,
+                expected,
+                describe(&t)
+            ),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        self.expect(
+            &Token::Ident(keyword.to_string()),
+            &format!("`{}`", keyword),
+        )
+    }
+
+    fn parse_var(&mut self) -> Result<String> {
+        match self.next("a variable")? {
+            (_, Token::Var(name)) => Ok(name),
+            (col, token) => anyhow::bail!(
+                "Parse error at position {}: expected a variable, found {}",
+                col,
+                describe(&token)
+            ),
+        }
+    }
+
+    fn parse_pipe(&mut self) -> Result<Expr> {
+        let left = self.parse_comma()?;
+        if self.eat(&Token::Pipe) {
+            return Ok(Expr::Pipe(Box::new(left), Box::new(self.parse_pipe()?)));
+        }
+        Ok(left)
+    }
+
+    fn parse_comma(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_alt()?;
+        while self.eat(&Token::Comma) {
+            expr = Expr::Comma(Box::new(expr), Box::new(self.parse_alt()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_alt(&mut self) -> Result<Expr> {
+        let expr = self.parse_or()?;
+        if self.eat(&Token::Alt) {
+            return Ok(Expr::Alt(Box::new(expr), Box::new(self.parse_alt()?)));
+        }
+        Ok(expr)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_compare()?;
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_compare()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_compare(&mut self) -> Result<Expr> {
+        let expr = self.parse_additive()?;
+        match self.peek() {
+            Some(Token::Op(
+                op @ (BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge),
+            )) => {
+                let op = *op;
+                self.pos += 1;
+                let right = self.parse_additive()?;
+                Ok(Expr::Binary(Box::new(expr), op, Box::new(right)))
+            }
+            _ => Ok(expr),
+        }
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_multiplicative()?;
+        while let Some(Token::Op(op @ (BinOp::Add | BinOp::Sub))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            let right = self.parse_multiplicative()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while let Some(Token::Op(op @ (BinOp::Mul | BinOp::Div | BinOp::Mod))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Op(BinOp::Sub)) {
+            return Ok(Expr::Neg(Box::new(self.parse_unary()?)));
+        }
+        self.parse_postfix(true)
+    }
+
+    // term 后面可以跟 .foo、[...]、? 这些后缀，以及 `as $x | body`
+    fn parse_postfix(&mut self, allow_bind: bool) -> Result<Expr> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Field(name)) => {
+                    let key = Expr::Literal(Value::String(name.clone()));
+                    self.pos += 1;
+                    expr = Expr::Index(Box::new(expr), Box::new(key));
+                }
+                Some(Token::Dot) => match self.tokens.get(self.pos + 1) {
+                    Some((_, Token::Str(parts))) => {
+                        let key = self.string_expr(parts.clone())?;
+                        self.pos += 2;
+                        expr = Expr::Index(Box::new(expr), Box::new(key));
+                    }
+                    Some((_, Token::LBracket)) => {
+                        self.pos += 1;
+                    }
+                    _ => break,
+                },
+                Some(Token::LBracket) => {
+                    self.pos += 1;
+                    expr = self.parse_bracket(expr)?;
+                }
+                Some(Token::Question) => {
+                    self.pos += 1;
+                    expr = Expr::Try(Box::new(expr));
+                }
+                _ => break,
+            }
+        }
+        if allow_bind && self.peek_keyword("as") {
+            self.pos += 1;
+            let name = self.parse_var()?;
+            self.expect(&Token::Pipe, "`|`")?;
+            let body = self.parse_pipe()?;
+            return Ok(Expr::Bind(Box::new(expr), name, Box::new(body)));
+        }
+        Ok(expr)
+    }
+
+    // `[` 之后的部分：[]、[index]、[from:to]
+    fn parse_bracket(&mut self, target: Expr) -> Result<Expr> {
+        let target = Box::new(target);
+        if self.eat(&Token::RBracket) {
+            return Ok(Expr::Iterate(target));
+        }
+        let from = if self.peek() == Some(&Token::Colon) {
+            None
+        } else {
+            Some(Box::new(self.parse_pipe()?))
+        };
+        if self.eat(&Token::Colon) {
+            let to = if self.peek() == Some(&Token::RBracket) {
+                None
+            } else {
+                Some(Box::new(self.parse_pipe()?))
+            };
+            self.expect(&Token::RBracket, "`]`")?;
+            return Ok(Expr::Slice(target, from, to));
+        }
+        self.expect(&Token::RBracket, "`]`")?;
+        match from {
+            Some(index) => Ok(Expr::Index(target, index)),
+            None => anyhow::bail!("Parse error: expected an index"),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let (col, token) = self.next("an expression")?;
+        match token {
+            Token::Dot => match self.peek() {
+                Some(Token::Str(parts)) => {
+                    let key = self.string_expr(parts.clone())?;
+                    self.pos += 1;
+                    Ok(Expr::Index(Box::new(Expr::Identity), Box::new(key)))
+                }
+                _ => Ok(Expr::Identity),
+            },
+            Token::Recurse => Ok(Expr::Recurse),
+            Token::Field(name) => Ok(Expr::Index(
+                Box::new(Expr::Identity),
+                Box::new(Expr::Literal(Value::String(name))),
+            )),
+            Token::Num(n) => Ok(Expr::Literal(to_number(n))),
+            Token::Str(parts) => self.string_expr(parts),
+            Token::Format(name) if FORMATS.contains(&name.as_str()) => Ok(Expr::Format(name)),
+            Token::Format(name) => {
+                anyhow::bail!("Unknown format @{} at position {}", name, col)
+            }
+            Token::Var(name) => Ok(Expr::Var(name)),
+            Token::LParen => {
+                let expr = self.parse_pipe()?;
+                self.expect(&Token::RParen, "`)`")?;
+                Ok(expr)
+            }
+            Token::LBracket => {
+                if self.eat(&Token::RBracket) {
+                    return Ok(Expr::Array(None));
+                }
+                let expr = self.parse_pipe()?;
+                self.expect(&Token::RBracket, "`]`")?;
+                Ok(Expr::Array(Some(Box::new(expr))))
+            }
+            Token::LBrace => self.parse_object(),
+            Token::Ident(name) => match name.as_str() {
+                "null" => Ok(Expr::Literal(Value::Null)),
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "if" => self.parse_if(),
+                "reduce" => self.parse_reduce(),
+                "try" | "catch" | "def" | "del" | "paths" => anyhow::bail!(
+                    "Parse error at position {}: `{}` is not supported",
+                    col,
+                    name
+                ),
+                "then" | "elif" | "else" | "end" | "as" | "and" | "or" => anyhow::bail!(
+                    "Parse error at position {}: unexpected keyword `{}`",
+                    col,
+                    name
+                ),
+                _ => self.parse_call(col, name),
+            },
+            token => anyhow::bail!(
+                "Parse error at position {}: unexpected {}",
+                col,
+                describe(&token)
+            ),
+        }
+    }
+
+    fn parse_call(&mut self, col: usize, name: String) -> Result<Expr> {
+        let mut args = Vec::new();
+        if self.eat(&Token::LParen) {
+            loop {
+                args.push(self.parse_pipe()?);
+                if !self.eat(&Token::Semicolon) {
+                    break;
+                }
+            }
+            self.expect(&Token::RParen, "`)`")?;
+        }
+        if !FUNCTIONS.contains(&(name.as_str(), args.len())) {
+            anyhow::bail!(
+                "Unknown function {}/{} at position {}",
+                name,
+                args.len(),
+                col
+            );
+        }
+        Ok(Expr::Call(name, args))
+    }
+
+    fn parse_if(&mut self) -> Result<Expr> {
+        let mut branches = Vec::new();
+        loop {
+            let cond = self.parse_pipe()?;
+            self.expect_keyword("then")?;
+            branches.push((cond, self.parse_pipe()?));
+            if self.peek_keyword("elif") {
+                self.pos += 1;
+                continue;
+            }
+            break;
+        }
+        // 没有 else 时相当于 else . end
+        let otherwise = if self.peek_keyword("else") {
+            self.pos += 1;
+            Some(Box::new(self.parse_pipe()?))
+        } else {
+            None
+        };
+        self.expect_keyword("end")?;
+        Ok(Expr::If(branches, otherwise))
+    }
+
+    fn parse_reduce(&mut self) -> Result<Expr> {
+        let source = self.parse_postfix(false)?;
+        self.expect_keyword("as")?;
+        let name = self.parse_var()?;
+        self.expect(&Token::LParen, "`(`")?;
+        let init = self.parse_pipe()?;
+        self.expect(&Token::Semicolon, "`;`")?;
+        let update = self.parse_pipe()?;
+        self.expect(&Token::RParen, "`)`")?;
+        Ok(Expr::Reduce(
+            Box::new(source),
+            name,
+            Box::new(init),
+            Box::new(update),
+        ))
+    }
+
+    // {a, b: expr, "c": expr, $v, (expr): expr}
+    fn parse_object(&mut self) -> Result<Expr> {
+        let mut entries = Vec::new();
+        if self.eat(&Token::RBrace) {
+            return Ok(Expr::Object(entries));
+        }
+        loop {
+            let (col, token) = self.next("an object key")?;
+            let (key, shorthand) = match token {
+                Token::Ident(name) => {
+                    let key = Value::String(name);
+                    let value = Expr::Index(
+                        Box::new(Expr::Identity),
+                        Box::new(Expr::Literal(key.clone())),
+                    );
+                    (Expr::Literal(key), Some(value))
+                }
+                Token::Var(name) => (
+                    Expr::Literal(Value::String(name.clone())),
+                    Some(Expr::Var(name)),
+                ),
+                Token::Str(parts) => {
+                    let value = Expr::Index(
+                        Box::new(Expr::Identity),
+                        Box::new(self.string_expr(parts.clone())?),
+                    );
+                    (self.string_expr(parts)?, Some(value))
+                }
+                Token::LParen => {
+                    let key = self.parse_pipe()?;
+                    self.expect(&Token::RParen, "`)`")?;
+                    (key, None)
+                }
+                token => anyhow::bail!(
+                    "Parse error at position {}: expected an object key, found {}",
+                    col,
+                    describe(&token)
+                ),
+            };
+            let value = match (self.eat(&Token::Colon), shorthand) {
+                (true, _) => self.parse_object_value()?,
+                (false, Some(value)) => value,
+                (false, None) => anyhow::bail!(
+                    "Parse error at position {}: expected `:` after a computed object key",
+                    col
+                ),
+            };
+            entries.push((key, value));
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&Token::RBrace, "`}`")?;
+        Ok(Expr::Object(entries))
+    }
+
+    // 对象的值里 `,` 用来分隔字段，需要多个输出时用括号
+    fn parse_object_value(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_alt()?;
+        while self.eat(&Token::Pipe) {
+            expr = Expr::Pipe(Box::new(expr), Box::new(self.parse_alt()?));
+        }
+        Ok(expr)
+    }
+
+    fn string_expr(&self, parts: Vec<StrPart>) -> Result<Expr> {
+        if let [StrPart::Lit(s)] = parts.as_slice() {
+            return Ok(Expr::Literal(Value::String(s.clone())));
+        }
+        let parts = parts
+            .into_iter()
+            .map(|part| match part {
+                StrPart::Lit(s) => Ok(StrExpr::Lit(s)),
+                StrPart::Interp(offset, src) => {
+                    let tokens = tokenize(&src, offset)?;
+                    Ok(StrExpr::Expr(Parser::new(tokens).parse_all()?))
+                }
+            })
+            .collect::<Result<_>>()?;
+        Ok(Expr::Str(parts))
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Dot => "`.`".to_string(),
+        Token::Recurse => "`..`".to_string(),
+        Token::Field(name) => format!("field .{}", name),
+        Token::Ident(name) => format!("`{}`", name),
+        Token::Var(name) => format!("variable ${}", name),
+        Token::Format(name) => format!("format @{}", name),
+        Token::Num(n) => format!("number {}", n),
+        Token::Str(_) => "string".to_string(),
+        Token::Pipe => "`|`".to_string(),
+        Token::Comma => "`,`".to_string(),
+        Token::LParen => "`(`".to_string(),
+        Token::RParen => "`)`".to_string(),
+        Token::LBracket => "`[`".to_string(),
+        Token::RBracket => "`]`".to_string(),
+        Token::LBrace => "`{`".to_string(),
+        Token::RBrace => "`}`".to_string(),
+        Token::Colon => "`:`".to_string(),
+        Token::Semicolon => "`;`".to_string(),
+        Token::Question => "`?`".to_string(),
+        Token::Alt => "`//`".to_string(),
+        Token::Op(op) => format!("operator `{}`", op.symbol()),
+    }
+}
+
+impl BinOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn read_ident(chars: &[char], i: &mut usize) -> String {
+    let start = *i;
+    while *i < chars.len() && (chars[*i].is_ascii_alphanumeric() || chars[*i] == '_') {
+        *i += 1;
+    }
+    chars[start..*i].iter().collect()
+}
+
+// 返回 (位置, token)，位置从 1 开始，offset 是字符串插值在整个表达式里的偏移
+fn tokenize(input: &str, offset: usize) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let col = offset + i + 1;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        // 注释到行尾
+        if c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        let next = chars.get(i + 1).copied();
+        let token = match (c, next) {
+            ('.', Some('.')) => {
+                i += 2;
+                Token::Recurse
+            }
+            ('.', Some(n)) if is_ident_start(n) => {
+                i += 1;
+                Token::Field(read_ident(&chars, &mut i))
+            }
+            ('$', Some(n)) if is_ident_start(n) => {
+                i += 1;
+                Token::Var(read_ident(&chars, &mut i))
+            }
+            ('@', Some(n)) if is_ident_start(n) => {
+                i += 1;
+                Token::Format(read_ident(&chars, &mut i))
+            }
+            (c, _) if is_ident_start(c) => Token::Ident(read_ident(&chars, &mut i)),
+            (c, _) if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    i += 1;
+                    if i < chars.len() && matches!(chars[i], '+' | '-') {
+                        i += 1;
+                    }
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let n = text.parse::<f64>().map_err(|_| {
+                    anyhow::anyhow!("Parse error at position {}: invalid number {}", col, text)
+                })?;
+                Token::Num(n)
+            }
+            ('"', _) => Token::Str(read_string(&chars, &mut i, offset)?),
+            _ => {
+                let (token, len) = match (c, next) {
+                    ('|' | '+' | '-' | '*' | '/' | '%', Some('=')) => anyhow::bail!(
+                        "Parse error at position {}: assignment operators are not supported",
+                        col
+                    ),
+                    ('=', next) if next != Some('=') => anyhow::bail!(
+                        "Parse error at position {}: assignment operators are not supported",
+                        col
+                    ),
+                    ('=', Some('=')) => (Token::Op(BinOp::Eq), 2),
+                    ('!', Some('=')) => (Token::Op(BinOp::Ne), 2),
+                    ('<', Some('=')) => (Token::Op(BinOp::Le), 2),
+                    ('<', _) => (Token::Op(BinOp::Lt), 1),
+                    ('>', Some('=')) => (Token::Op(BinOp::Ge), 2),
+                    ('>', _) => (Token::Op(BinOp::Gt), 1),
+                    ('/', Some('/')) => (Token::Alt, 2),
+                    ('/', _) => (Token::Op(BinOp::Div), 1),
+                    ('+', _) => (Token::Op(BinOp::Add), 1),
+                    ('-', _) => (Token::Op(BinOp::Sub), 1),
+                    ('*', _) => (Token::Op(BinOp::Mul), 1),
+                    ('%', _) => (Token::Op(BinOp::Mod), 1),
+                    ('|', _) => (Token::Pipe, 1),
+                    (',', _) => (Token::Comma, 1),
+                    ('(', _) => (Token::LParen, 1),
+                    (')', _) => (Token::RParen, 1),
+                    ('[', _) => (Token::LBracket, 1),
+                    (']', _) => (Token::RBracket, 1),
+                    ('{', _) => (Token::LBrace, 1),
+                    ('}', _) => (Token::RBrace, 1),
+                    (':', _) => (Token::Colon, 1),
+                    (';', _) => (Token::Semicolon, 1),
+                    ('?', _) => (Token::Question, 1),
+                    ('.', _) => (Token::Dot, 1),
+                    (c, _) => anyhow::bail!(
+                        "Parse error at position {}: unexpected character '{}'",
+                        col,
+                        c
+                    ),
+                };
+                i += len;
+                token
+            }
+        };
+        tokens.push((col, token));
+    }
+    Ok(tokens)
+}
+
+// 读取双引号字符串，i 指向开头的引号，结束时指向结束引号之后
+fn read_string(chars: &[char], i: &mut usize, offset: usize) -> Result<Vec<StrPart>> {
+    let start = *i;
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    *i += 1;
+    while *i < chars.len() {
+        match chars[*i] {
+            '"' => {
+                *i += 1;
+                if !text.is_empty() || parts.is_empty() {
+                    parts.push(StrPart::Lit(text));
+                }
+                return Ok(parts);
+            }
+            '\\' if *i + 1 < chars.len() => {
+                let escaped = chars[*i + 1];
+                *i += 2;
+                match escaped {
+                    'n' => text.push('\n'),
+                    't' => text.push('\t'),
+                    'r' => text.push('\r'),
+                    'b' => text.push('\u{8}'),
+                    'f' => text.push('\u{c}'),
+                    'u' => {
+                        let hex: String = chars.iter().skip(*i).take(4).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| {
+                                anyhow::anyhow!(
+                                    "Parse error at position {}: invalid escape \\u{}",
+                                    offset + *i - 1,
+                                    hex
+                                )
+                            })?;
+                        text.push(c);
+                        *i += 4;
+                    }
+                    '(' => {
+                        let src_start = *i;
+                        let end = find_interp_end(chars, src_start).ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "Parse error at position {}: unterminated string interpolation",
+                                offset + src_start - 1
+                            )
+                        })?;
+                        if !text.is_empty() {
+                            parts.push(StrPart::Lit(std::mem::take(&mut text)));
+                        }
+                        let src = chars[src_start..end].iter().collect();
+                        parts.push(StrPart::Interp(offset + src_start, src));
+                        *i = end + 1;
+                    }
+                    c => text.push(c),
+                }
+            }
+            c => {
+                text.push(c);
+                *i += 1;
+            }
+        }
+    }
+    anyhow::bail!(
+        "Parse error at position {}: unterminated string",
+        offset + start + 1
+    )
+}
+
+// 找到和 \( 匹配的右括号，跳过中间嵌套的字符串
+fn find_interp_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 1;
+    let mut i = start;
+    let mut in_string = false;
+    while i < chars.len() {
+        match (in_string, chars[i]) {
+            (true, '\\') => i += 1,
+            (true, '"') => in_string = false,
+            (false, '"') => in_string = true,
+            (false, '(') => depth += 1,
+            (false, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// 需要所有输出的地方（数组构造、排序等）使用，结果超过 MAX_VALUES 个时报错
+fn eval(expr: &Expr, input: &Value, env: &Env) -> Result<Vec<Value>> {
+    let mut out = Vec::new();
+    each(expr, input, env, &mut |v| {
+        if out.len() >= MAX_VALUES {
+            anyhow::bail!("Filter produced more than {} values", MAX_VALUES);
+        }
+        out.push(v);
+        Ok(true)
+    })?;
+    Ok(out)
+}
+
+fn emit_all(values: Vec<Value>, emit: &mut Emit) -> Result<bool> {
+    for v in values {
+        if !emit(v)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// 逐个输出结果，返回 false 表示下游不再需要结果，这样 first / limit 不会算出所有结果
+fn each(expr: &Expr, input: &Value, env: &Env, emit: &mut Emit) -> Result<bool> {
+    let values = match expr {
+        Expr::Identity => vec![input.clone()],
+        Expr::Recurse => {
+            let mut out = Vec::new();
+            descendants(input, &mut out);
+            out
+        }
+        Expr::Literal(v) => vec![v.clone()],
+        Expr::Str(parts) => {
+            let mut out = vec![String::new()];
+            for part in parts {
+                match part {
+                    StrExpr::Lit(s) => out.iter_mut().for_each(|o| o.push_str(s)),
+                    StrExpr::Expr(e) => {
+                        let values = eval(e, input, env)?;
+                        out = out
+                            .iter()
+                            .flat_map(|o| {
+                                values.iter().map(move |v| format!("{}{}", o, to_text(v)))
+                            })
+                            .collect();
+                    }
+                }
+            }
+            out.into_iter().map(Value::String).collect()
+        }
+        Expr::Format(name) => vec![format_value(name, input)?],
+        Expr::Index(target, key) => {
+            let keys = eval(key, input, env)?;
+            return each(target, input, env, &mut |t| {
+                for k in &keys {
+                    if !emit(index(&t, k)?)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            });
+        }
+        Expr::Slice(target, from, to) => {
+            let froms = eval_or_null(from.as_deref(), input, env)?;
+            let tos = eval_or_null(to.as_deref(), input, env)?;
+            return each(target, input, env, &mut |t| {
+                for from in &froms {
+                    for to in &tos {
+                        if !emit(slice(&t, from, to)?)? {
+                            return Ok(false);
+                        }
+                    }
+                }
+                Ok(true)
+            });
+        }
+        Expr::Iterate(target) => {
+            return each(target, input, env, &mut |t| emit_all(iterate(&t)?, emit));
+        }
+        Expr::Try(e) => eval(e, input, env).unwrap_or_default(),
+        Expr::Pipe(left, right) => {
+            return each(left, input, env, &mut |v| each(right, &v, env, emit));
+        }
+        Expr::Comma(left, right) => {
+            if !each(left, input, env, emit)? {
+                return Ok(false);
+            }
+            return each(right, input, env, emit);
+        }
+        // 左边没有 null / false 以外的输出（包括出错）时使用右边
+        Expr::Alt(left, right) => {
+            let values: Vec<Value> = eval(left, input, env)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(truthy)
+                .collect();
+            if values.is_empty() {
+                return each(right, input, env, emit);
+            }
+            values
+        }
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            let is_and = matches!(expr, Expr::And(..));
+            return each(left, input, env, &mut |l| {
+                if truthy(&l) != is_and {
+                    return emit(Value::Bool(!is_and));
+                }
+                each(right, input, env, &mut |r| emit(Value::Bool(truthy(&r))))
+            });
+        }
+        Expr::Binary(left, op, right) => {
+            let lefts = eval(left, input, env)?;
+            return each(right, input, env, &mut |r| {
+                for l in &lefts {
+                    if !emit(binary(*op, l, &r)?)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            });
+        }
+        Expr::Neg(e) => {
+            return each(e, input, env, &mut |v| match as_f64(&v) {
+                Some(n) => emit(to_number(-n)),
+                None => anyhow::bail!("{} cannot be negated", describe_value(&v)),
+            });
+        }
+        Expr::Array(None) => vec![Value::Array(Vec::new())],
+        Expr::Array(Some(e)) => vec![Value::Array(eval(e, input, env)?)],
+        Expr::Object(entries) => {
+            let mut out = vec![Map::new()];
+            for (key, value) in entries {
+                let keys = eval(key, input, env)?;
+                let values = eval(value, input, env)?;
+                let mut next = Vec::new();
+                for map in &out {
+                    for k in &keys {
+                        let Value::String(k) = k else {
+                            anyhow::bail!("Object keys must be strings, got {}", describe_value(k));
+                        };
+                        for v in &values {
+                            let mut map = map.clone();
+                            map.insert(k.clone(), v.clone());
+                            next.push(map);
+                        }
+                    }
+                }
+                out = next;
+            }
+            out.into_iter().map(Value::Object).collect()
+        }
+        Expr::Var(name) => match env.iter().rev().find(|(n, _)| n == name) {
+            Some((_, v)) => vec![v.clone()],
+            None => anyhow::bail!("${} is not defined", name),
+        },
+        Expr::Bind(source, name, body) => {
+            return each(source, input, env, &mut |v| {
+                let mut env = env.clone();
+                env.push((name.clone(), v));
+                each(body, input, &env, emit)
+            });
+        }
+        // source 只求值一次，每个输出依次更新所有的初始值
+        Expr::Reduce(source, name, init, update) => {
+            let mut accs = eval(init, input, env)?;
+            each(source, input, env, &mut |item| {
+                let mut env = env.clone();
+                env.push((name.clone(), item));
+                for acc in accs.iter_mut() {
+                    *acc = eval(update, acc, &env)?.pop().unwrap_or(Value::Null);
+                }
+                Ok(true)
+            })?;
+            accs
+        }
+        Expr::If(branches, otherwise) => {
+            return each_if(branches, otherwise.as_deref(), input, env, emit);
+        }
+        Expr::Call(name, args) => return call(name, args, input, env, emit),
+    };
+    emit_all(values, emit)
+}
+
+fn eval_or_null(expr: Option<&Expr>, input: &Value, env: &Env) -> Result<Vec<Value>> {
+    match expr {
+        Some(expr) => eval(expr, input, env),
+        None => Ok(vec![Value::Null]),
+    }
+}
+
+fn each_if(
+    branches: &[(Expr, Expr)],
+    otherwise: Option<&Expr>,
+    input: &Value,
+    env: &Env,
+    emit: &mut Emit,
+) -> Result<bool> {
+    let Some(((cond, then), rest)) = branches.split_first() else {
+        return match otherwise {
+            Some(expr) => each(expr, input, env, emit),
+            None => emit(input.clone()),
+        };
+    };
+    each(cond, input, env, &mut |c| {
+        if truthy(&c) {
+            each(then, input, env, emit)
+        } else {
+            each_if(rest, otherwise, input, env, emit)
+        }
+    })
+}
+
+// 参数是过滤器的函数在这里处理，其余的函数对参数的每个输出（多个参数时取笛卡尔积）调用一次
+fn call(name: &str, args: &[Expr], input: &Value, env: &Env, emit: &mut Emit) -> Result<bool> {
+    let values = match (name, args) {
+        ("empty", []) => Vec::new(),
+        ("select", [f]) => {
+            let mut selected = false;
+            each(f, input, env, &mut |v| {
+                selected = truthy(&v);
+                Ok(!selected)
+            })?;
+            keep(input, selected)
+        }
+        ("map", [f]) => {
+            let mut out = Vec::new();
+            for item in iterate(input)? {
+                out.extend(eval(f, &item, env)?);
+            }
+            vec![Value::Array(out)]
+        }
+        ("map_values", [f]) => {
+            let first =
+                |v: &Value| -> Result<Option<Value>> { Ok(eval(f, v, env)?.into_iter().next()) };
+            match input {
+                Value::Object(map) => {
+                    let mut out = Map::new();
+                    for (k, v) in map {
+                        if let Some(v) = first(v)? {
+                            out.insert(k.clone(), v);
+                        }
+                    }
+                    vec![Value::Object(out)]
+                }
+                Value::Array(items) => {
+                    let mut out = Vec::new();
+                    for v in items {
+                        out.extend(first(v)?);
+                    }
+                    vec![Value::Array(out)]
+                }
+                v => anyhow::bail!("Cannot iterate over {}", describe_value(v)),
+            }
+        }
+        ("recurse", []) => {
+            let mut out = Vec::new();
+            descendants(input, &mut out);
+            out
+        }
+        ("recurse", [f]) => {
+            let mut out = Vec::new();
+            recurse_with(input, f, env, &mut out)?;
+            out
+        }
+        ("any" | "all", [f]) => {
+            let is_any = name == "any";
+            let mut result = !is_any;
+            for item in iterate(input)? {
+                if eval(f, &item, env)?.iter().any(truthy) == is_any {
+                    result = is_any;
+                    break;
+                }
+            }
+            vec![Value::Bool(result)]
+        }
+        ("sort_by", [f]) => {
+            let keyed = keyed_items(input, f, env)?;
+            vec![Value::Array(keyed.into_iter().map(|(_, v)| v).collect())]
+        }
+        ("group_by" | "unique_by", [f]) => {
+            let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
+            for (key, item) in keyed_items(input, f, env)? {
+                match groups.last_mut() {
+                    Some((last, group)) if compare(last, &key) == Ordering::Equal => {
+                        group.push(item)
+                    }
+                    _ => groups.push((key, vec![item])),
+                }
+            }
+            let groups = groups.into_iter().map(|(_, group)| group);
+            if name == "group_by" {
+                vec![Value::Array(groups.map(Value::Array).collect())]
+            } else {
+                vec![Value::Array(
+                    groups.filter_map(|g| g.into_iter().next()).collect(),
+                )]
+            }
+        }
+        ("min_by" | "max_by", [f]) => {
+            let keyed = keyed_items(input, f, env)?;
+            // 排序是稳定的，max_by 取最后一个最大值，和 jq 一致
+            let item = if name == "min_by" {
+                keyed.into_iter().next()
+            } else {
+                keyed.into_iter().last()
+            };
+            vec![item.map_or(Value::Null, |(_, v)| v)]
+        }
+        ("with_entries", [f]) => {
+            let mut entries = Vec::new();
+            for entry in iterate(&to_entries(input)?)? {
+                entries.extend(eval(f, &entry, env)?);
+            }
+            vec![from_entries(&Value::Array(entries))?]
+        }
+        ("first", [f]) => {
+            let mut first = None;
+            each(f, input, env, &mut |v| {
+                first = Some(v);
+                Ok(false)
+            })?;
+            first.into_iter().collect()
+        }
+        ("last", [f]) => {
+            let mut last = None;
+            each(f, input, env, &mut |v| {
+                last = Some(v);
+                Ok(true)
+            })?;
+            last.into_iter().collect()
+        }
+        ("limit", [n, f]) => {
+            for n in eval(n, input, env)? {
+                let n = as_f64(&n)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid limit {}", describe_value(&n)))?;
+                if n < 1.0 {
+                    continue;
+                }
+                // 取够 n 个时停止 f 的求值，下游停止时整个 limit 也停止
+                let (n, mut count, mut stopped) = (n as usize, 0, false);
+                each(f, input, env, &mut |v| {
+                    count += 1;
+                    stopped = !emit(v)?;
+                    Ok(!stopped && count < n)
+                })?;
+                if stopped {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
+        ("walk", [f]) => vec![walk(input, f, env)?],
+        ("sub" | "gsub", [re, replacement, flags @ ..]) => {
+            let Value::String(text) = input else {
+                anyhow::bail!(
+                    "{} cannot be matched, as it is not a string",
+                    describe_value(input)
+                );
+            };
+            let flags = match flags {
+                [flags] => Some(first_value(flags, input, env)?),
+                _ => None,
+            };
+            let (re, global) = build_regex(&first_value(re, input, env)?, flags.as_ref())?;
+            vec![substitute(
+                text,
+                &re,
+                global || name == "gsub",
+                replacement,
+                env,
+            )?]
+        }
+        _ => {
+            let mut combos: Vec<Vec<Value>> = vec![Vec::new()];
+            for arg in args {
+                let values = eval(arg, input, env)?;
+                combos = combos
+                    .iter()
+                    .flat_map(|combo| {
+                        values.iter().map(move |v| {
+                            let mut combo = combo.clone();
+                            combo.push(v.clone());
+                            combo
+                        })
+                    })
+                    .collect();
+            }
+            for combo in combos {
+                let more = match name {
+                    "range" => emit_range(&combo, emit)?,
+                    _ => emit_all(call_with_values(name, &combo, input)?, emit)?,
+                };
+                if !more {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
+    };
+    emit_all(values, emit)
+}
+
+// range 的结果逐个生成，和 first / limit 一起使用时不会生成整个范围
+fn emit_range(args: &[Value], emit: &mut Emit) -> Result<bool> {
+    let bounds = args
+        .iter()
+        .map(|v| {
+            as_f64(v).ok_or_else(|| {
+                anyhow::anyhow!("Range bounds must be numeric, got {}", describe_value(v))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (mut from, to) = match bounds.as_slice() {
+        [to] => (0.0, *to),
+        [from, to] => (*from, *to),
+        _ => unreachable!(),
+    };
+    while from < to {
+        if !emit(to_number(from))? {
+            return Ok(false);
+        }
+        // 超过 2^53 之后加 1 不再改变浮点数的值
+        if from + 1.0 == from {
+            anyhow::bail!("Range bound {} is too large", from);
+        }
+        from += 1.0;
+    }
+    Ok(true)
+}
+
+fn call_with_values(name: &str, args: &[Value], input: &Value) -> Result<Vec<Value>> {
+    let value = match (name, args) {
+        ("error", []) => anyhow::bail!("{}", to_text(input)),
+        ("error", [msg]) => anyhow::bail!("{}", to_text(msg)),
+        ("not", []) => Value::Bool(!truthy(input)),
+        ("length", []) => match input {
+            Value::Null => Value::from(0),
+            Value::Bool(_) => anyhow::bail!("{} has no length", describe_value(input)),
+            Value::Number(n) => to_number(n.as_f64().unwrap_or_default().abs()),
+            Value::String(s) => Value::from(s.chars().count()),
+            Value::Array(items) => Value::from(items.len()),
+            Value::Object(map) => Value::from(map.len()),
+        },
+        ("keys" | "keys_unsorted", []) => match input {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                if name == "keys" {
+                    keys.sort();
+                }
+                Value::Array(keys.into_iter().map(|k| Value::String(k.clone())).collect())
+            }
+            Value::Array(items) => Value::Array((0..items.len()).map(Value::from).collect()),
+            v => anyhow::bail!("{} has no keys", describe_value(v)),
+        },
+        ("has", [key]) => Value::Bool(has(input, key)?),
+        ("in", [target]) => Value::Bool(has(target, input)?),
+        ("contains", [other]) => Value::Bool(contains(input, other)?),
+        ("inside", [other]) => Value::Bool(contains(other, input)?),
+        ("type", []) => Value::String(type_name(input).to_string()),
+        ("add", []) => {
+            let mut acc = Value::Null;
+            for item in iterate_or_empty(input)? {
+                acc = binary(BinOp::Add, &acc, &item)?;
+            }
+            acc
+        }
+        ("any" | "all", []) => {
+            let items = iterate(input)?;
+            if name == "any" {
+                Value::Bool(items.iter().any(truthy))
+            } else {
+                Value::Bool(items.iter().all(truthy))
+            }
+        }
+        ("flatten", []) => Value::Array(flatten(iterate_array(input)?, usize::MAX)),
+        ("flatten", [depth]) => match as_f64(depth) {
+            Some(d) if d >= 0.0 => Value::Array(flatten(iterate_array(input)?, d as usize)),
+            _ => anyhow::bail!("flatten depth must not be negative"),
+        },
+        ("floor" | "ceil" | "round" | "sqrt" | "abs", []) => {
+            let n = as_f64(input)
+                .ok_or_else(|| anyhow::anyhow!("{} number required", describe_value(input)))?;
+            to_number(match name {
+                "floor" => n.floor(),
+                "ceil" => n.ceil(),
+                "round" => n.round(),
+                "sqrt" => n.sqrt(),
+                _ => n.abs(),
+            })
+        }
+        ("tostring", []) => Value::String(to_text(input)),
+        ("tojson", []) => Value::String(serde_json::to_string(input)?),
+        ("tonumber", []) => match input {
+            Value::Number(_) => input.clone(),
+            Value::String(s) => match s.trim().parse::<f64>() {
+                Ok(n) => to_number(n),
+                Err(_) => anyhow::bail!("Cannot parse '{}' as number", s),
+            },
+            v => anyhow::bail!("{} cannot be parsed as a number", describe_value(v)),
+        },
+        ("fromjson", []) => serde_json::from_str(expect_str(input)?)?,
+        ("ascii_downcase", []) => Value::String(expect_str(input)?.to_ascii_lowercase()),
+        ("ascii_upcase", []) => Value::String(expect_str(input)?.to_ascii_uppercase()),
+        ("trim", []) => Value::String(expect_str(input)?.trim().to_string()),
+        ("ltrim", []) => Value::String(expect_str(input)?.trim_start().to_string()),
+        ("rtrim", []) => Value::String(expect_str(input)?.trim_end().to_string()),
+        // 输入不是字符串时原样返回
+        ("ltrimstr" | "rtrimstr", [affix]) => match (input, affix) {
+            (Value::String(s), Value::String(affix)) => {
+                let trimmed = if name == "ltrimstr" {
+                    s.strip_prefix(affix.as_str())
+                } else {
+                    s.strip_suffix(affix.as_str())
+                };
+                Value::String(trimmed.unwrap_or(s).to_string())
+            }
+            _ => input.clone(),
+        },
+        ("startswith" | "endswith", [affix]) => match (input, affix) {
+            (Value::String(s), Value::String(affix)) if name == "startswith" => {
+                Value::Bool(s.starts_with(affix.as_str()))
+            }
+            (Value::String(s), Value::String(affix)) => Value::Bool(s.ends_with(affix.as_str())),
+            _ => anyhow::bail!("{}() requires string inputs", name),
+        },
+        ("split", [sep]) => binary(BinOp::Div, input, sep)?,
+        ("join", [sep]) => {
+            let sep = expect_str(sep)?;
+            let parts = iterate_array(input)?
+                .iter()
+                .map(|item| match item {
+                    Value::Null => Ok(String::new()),
+                    Value::String(s) => Ok(s.clone()),
+                    Value::Number(_) | Value::Bool(_) => Ok(item.to_string()),
+                    v => anyhow::bail!("Cannot join with {}", describe_value(v)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Value::String(parts.join(sep))
+        }
+        ("test", [re, flags @ ..]) => {
+            let (re, _) = build_regex(re, flags.first())?;
+            Value::Bool(re.is_match(expect_str(input)?))
+        }
+        // 没有匹配时没有输出
+        ("capture", [re, flags @ ..]) => {
+            let (re, _) = build_regex(re, flags.first())?;
+            return Ok(re
+                .captures(expect_str(input)?)
+                .map(|caps| capture_object(&re, &caps))
+                .into_iter()
+                .collect());
+        }
+        ("sort", []) => {
+            let mut items = iterate_array(input)?;
+            items.sort_by(compare);
+            Value::Array(items)
+        }
+        ("unique", []) => {
+            let mut items = iterate_array(input)?;
+            items.sort_by(compare);
+            items.dedup_by(|a, b| compare(a, b) == Ordering::Equal);
+            Value::Array(items)
+        }
+        ("min" | "max", []) => {
+            let items = iterate_array(input)?;
+            let item = if name == "min" {
+                items.into_iter().min_by(compare)
+            } else {
+                items.into_iter().max_by(compare)
+            };
+            item.unwrap_or(Value::Null)
+        }
+        ("reverse", []) => match input {
+            Value::Null => Value::Array(Vec::new()),
+            Value::String(s) => Value::String(s.chars().rev().collect()),
+            v => Value::Array(iterate_array(v)?.into_iter().rev().collect()),
+        },
+        ("to_entries", []) => to_entries(input)?,
+        ("from_entries", []) => from_entries(input)?,
+        ("first", []) => index(input, &Value::from(0))?,
+        ("last", []) => index(input, &Value::from(-1))?,
+        ("values", []) => return Ok(keep(input, !input.is_null())),
+        ("objects", []) => return Ok(keep(input, input.is_object())),
+        ("arrays", []) => return Ok(keep(input, input.is_array())),
+        ("strings", []) => return Ok(keep(input, input.is_string())),
+        ("numbers", []) => return Ok(keep(input, input.is_number())),
+        ("booleans", []) => return Ok(keep(input, input.is_boolean())),
+        ("nulls", []) => return Ok(keep(input, input.is_null())),
+        ("iterables", []) => return Ok(keep(input, input.is_array() || input.is_object())),
+        ("scalars", []) => return Ok(keep(input, !input.is_array() && !input.is_object())),
+        _ => anyhow::bail!("Unknown function {}/{}", name, args.len()),
+    };
+    Ok(vec![value])
+}
+
+fn keep(input: &Value, keep: bool) -> Vec<Value> {
+    if keep {
+        vec![input.clone()]
+    } else {
+        Vec::new()
+    }
+}
+
+fn first_value(expr: &Expr, input: &Value, env: &Env) -> Result<Value> {
+    eval(expr, input, env)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Argument produced no value"))
+}
+
+// 按 f 的输出排序，返回 (key, item)，key 是 f 所有输出组成的数组
+fn keyed_items(input: &Value, f: &Expr, env: &Env) -> Result<Vec<(Value, Value)>> {
+    let mut keyed = iterate_array(input)?
+        .into_iter()
+        .map(|item| Ok((Value::Array(eval(f, &item, env)?), item)))
+        .collect::<Result<Vec<_>>>()?;
+    keyed.sort_by(|(a, _), (b, _)| compare(a, b));
+    Ok(keyed)
+}
+
+fn descendants(value: &Value, out: &mut Vec<Value>) {
+    out.push(value.clone());
+    match value {
+        Value::Array(items) => items.iter().for_each(|v| descendants(v, out)),
+        Value::Object(map) => map.values().for_each(|v| descendants(v, out)),
+        _ => {}
+    }
+}
+
+fn recurse_with(value: &Value, f: &Expr, env: &Env, out: &mut Vec<Value>) -> Result<()> {
+    out.push(value.clone());
+    for child in eval(f, value, env)? {
+        recurse_with(&child, f, env, out)?;
+    }
+    Ok(())
+}
+
+// 先处理子节点，再对当前节点应用 f
+fn walk(value: &Value, f: &Expr, env: &Env) -> Result<Value> {
+    let value = match value {
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|v| walk(v, f, env))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), walk(v, f, env)?)))
+                .collect::<Result<_>>()?,
+        ),
+        v => v.clone(),
+    };
+    Ok(eval(f, &value, env)?
+        .into_iter()
+        .next()
+        .unwrap_or(Value::Null))
+}
+
+fn flatten(items: Vec<Value>, depth: usize) -> Vec<Value> {
+    let mut out = Vec::new();
+    for item in items {
+        match item {
+            Value::Array(inner) if depth > 0 => out.extend(flatten(inner, depth - 1)),
+            item => out.push(item),
+        }
+    }
+    out
+}
+
+fn to_entries(input: &Value) -> Result<Value> {
+    match input {
+        Value::Object(map) => Ok(Value::Array(
+            map.iter()
+                .map(|(k, v)| {
+                    let mut entry = Map::new();
+                    entry.insert("key".to_string(), Value::String(k.clone()));
+                    entry.insert("value".to_string(), v.clone());
+                    Value::Object(entry)
+                })
+                .collect(),
+        )),
+        v => anyhow::bail!("{} has no keys", describe_value(v)),
+    }
+}
+
+// 和 jq 一样接受 key / k / name 和 value / v 这几种写法
+fn from_entries(input: &Value) -> Result<Value> {
+    let mut map = Map::new();
+    for entry in iterate_array(input)? {
+        let field = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| entry.get(name).filter(|v| !v.is_null()))
+                .cloned()
+        };
+        let key = match field(&["key", "k", "name", "Name", "Key", "K"]) {
+            Some(Value::String(s)) => s,
+            Some(v @ (Value::Number(_) | Value::Bool(_))) => v.to_string(),
+            Some(v) => anyhow::bail!("Cannot use {} as object key", describe_value(&v)),
+            None => "null".to_string(),
+        };
+        let value = field(&["value", "v", "Value", "V"]).unwrap_or(Value::Null);
+        map.insert(key, value);
+    }
+    Ok(Value::Object(map))
+}
+
+fn build_regex(pattern: &Value, flags: Option<&Value>) -> Result<(Regex, bool)> {
+    let pattern = expect_str(pattern)?;
+    let mut builder = RegexBuilder::new(pattern);
+    let mut global = false;
+    if let Some(flags) = flags.filter(|f| !f.is_null()) {
+        for flag in expect_str(flags)?.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => {
+                    builder.case_insensitive(true);
+                }
+                'x' => {
+                    builder.ignore_whitespace(true);
+                }
+                's' => {
+                    builder.dot_matches_new_line(true);
+                }
+                'n' => {}
+                _ => anyhow::bail!("{} is not a valid modifier string", to_text(flags)),
+            }
+        }
+    }
+    let re = builder
+        .build()
+        .map_err(|e| anyhow::anyhow!("Invalid regex {}: {}", pattern, e))?;
+    Ok((re, global))
+}
+
+fn capture_object(re: &Regex, caps: &Captures) -> Value {
+    let map = re
+        .capture_names()
+        .flatten()
+        .map(|name| {
+            let value = caps
+                .name(name)
+                .map_or(Value::Null, |m| Value::String(m.as_str().to_string()));
+            (name.to_string(), value)
+        })
+        .collect();
+    Value::Object(map)
+}
+
+// 替换的内容是一个过滤器，输入是命名捕获组组成的对象，例如 sub("(?<n>\\d+)"; "<\(.n)>")
+fn substitute(
+    text: &str,
+    re: &Regex,
+    global: bool,
+    replacement: &Expr,
+    env: &Env,
+) -> Result<Value> {
+    let mut out = String::new();
+    let mut last = 0;
+    for caps in re.captures_iter(text) {
+        let m = caps.get(0).expect("group 0 always matches");
+        out.push_str(&text[last..m.start()]);
+        let captures = capture_object(re, &caps);
+        match first_value(replacement, &captures, env)? {
+            Value::String(s) => out.push_str(&s),
+            v => anyhow::bail!("Replacement must be a string, got {}", describe_value(&v)),
+        }
+        last = m.end();
+        if !global {
+            break;
+        }
+    }
+    out.push_str(&text[last..]);
+    Ok(Value::String(out))
+}
+
+fn format_value(name: &str, input: &Value) -> Result<Value> {
+    let text = match name {
+        "csv" | "tsv" => {
+            let cells = iterate_array(input)?
+                .iter()
+                .map(|item| match item {
+                    Value::Null => Ok(String::new()),
+                    Value::Number(_) | Value::Bool(_) => Ok(item.to_string()),
+                    Value::String(s) if name == "csv" => {
+                        Ok(format!("\"{}\"", s.replace('"', "\"\"")))
+                    }
+                    Value::String(s) => Ok(s
+                        .replace('\\', "\\\\")
+                        .replace('\t', "\\t")
+                        .replace('\n', "\\n")
+                        .replace('\r', "\\r")),
+                    v => anyhow::bail!("{} is not valid in a {} row", describe_value(v), name),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            cells.join(if name == "csv" { "," } else { "\t" })
+        }
+        "json" => serde_json::to_string(input)?,
+        "base64" => STANDARD.encode(to_text(input)),
+        "base64d" => {
+            let text = to_text(input);
+            let bytes = STANDARD
+                .decode(text.trim())
+                .or_else(|_| STANDARD_NO_PAD.decode(text.trim()))?;
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        "html" => to_text(input)
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('\'', "&#39;")
+            .replace('"', "&quot;"),
+        "uri" => to_text(input)
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (b as char).to_string()
+                }
+                b => format!("%{:02X}", b),
+            })
+            .collect(),
+        _ => to_text(input),
+    };
+    Ok(Value::String(text))
+}
+
+fn index(target: &Value, key: &Value) -> Result<Value> {
+    let value = match (target, key) {
+        (Value::Object(map), Value::String(k)) => map.get(k).cloned().unwrap_or(Value::Null),
+        (Value::Array(items), Value::Number(n)) => {
+            let i = n.as_f64().unwrap_or_default().floor() as i64;
+            let i = if i < 0 { i + items.len() as i64 } else { i };
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| items.get(i))
+                .cloned()
+                .unwrap_or(Value::Null)
+        }
+        (Value::Null, Value::String(_) | Value::Number(_)) => Value::Null,
+        (t, Value::String(k)) => anyhow::bail!("Cannot index {} with \"{}\"", type_name(t), k),
+        (t, k) => anyhow::bail!("Cannot index {} with {}", type_name(t), type_name(k)),
+    };
+    Ok(value)
+}
+
+fn slice(target: &Value, from: &Value, to: &Value) -> Result<Value> {
+    let len = match target {
+        Value::Null => return Ok(Value::Null),
+        Value::Array(items) => items.len(),
+        Value::String(s) => s.chars().count(),
+        t => anyhow::bail!("Cannot index {} with object", type_name(t)),
+    };
+    let resolve = |v: &Value, default: usize| -> Result<usize> {
+        match v {
+            Value::Null => Ok(default),
+            v => {
+                let i = as_f64(v)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Start and end indices of a slice must be numbers")
+                    })?
+                    .floor() as i64;
+                let i = if i < 0 { i + len as i64 } else { i };
+                Ok(i.clamp(0, len as i64) as usize)
+            }
+        }
+    };
+    let from = resolve(from, 0)?;
+    let to = resolve(to, len)?.max(from);
+    let value = match target {
+        Value::Array(items) => Value::Array(items[from..to].to_vec()),
+        Value::String(s) => Value::String(s.chars().skip(from).take(to - from).collect()),
+        _ => unreachable!(),
+    };
+    Ok(value)
+}
+
+fn iterate(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Array(items) => Ok(items.clone()),
+        Value::Object(map) => Ok(map.values().cloned().collect()),
+        v => anyhow::bail!("Cannot iterate over {}", describe_value(v)),
+    }
+}
+
+fn iterate_or_empty(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Null => Ok(Vec::new()),
+        v => iterate(v),
+    }
+}
+
+fn iterate_array(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Array(items) => Ok(items.clone()),
+        v => anyhow::bail!("{} is not an array", describe_value(v)),
+    }
+}
+
+fn has(target: &Value, key: &Value) -> Result<bool> {
+    match (target, key) {
+        (Value::Object(map), Value::String(k)) => Ok(map.contains_key(k)),
+        (Value::Array(items), Value::Number(n)) => {
+            let i = n.as_f64().unwrap_or(-1.0);
+            Ok(i >= 0.0 && (i as usize) < items.len())
+        }
+        (t, k) => anyhow::bail!(
+            "Cannot check whether {} has a {} key",
+            type_name(t),
+            type_name(k)
+        ),
+    }
+}
+
+fn contains(a: &Value, b: &Value) -> Result<bool> {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (k, bv) in b {
+                match a.get(k) {
+                    Some(av) if contains(av, bv)? => {}
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for bv in b {
+                let mut found = false;
+                for av in a {
+                    if contains(av, bv)? {
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (Value::String(a), Value::String(b)) => Ok(a.contains(b.as_str())),
+        (a, b) if type_name(a) == type_name(b) => Ok(compare(a, b) == Ordering::Equal),
+        (a, b) => anyhow::bail!(
+            "{} and {} cannot have their containment checked",
+            describe_value(a),
+            describe_value(b)
+        ),
+    }
+}
+
+fn binary(op: BinOp, l: &Value, r: &Value) -> Result<Value> {
+    let value = match op {
+        BinOp::Eq => Value::Bool(compare(l, r) == Ordering::Equal),
+        BinOp::Ne => Value::Bool(compare(l, r) != Ordering::Equal),
+        BinOp::Lt => Value::Bool(compare(l, r) == Ordering::Less),
+        BinOp::Le => Value::Bool(compare(l, r) != Ordering::Greater),
+        BinOp::Gt => Value::Bool(compare(l, r) == Ordering::Greater),
+        BinOp::Ge => Value::Bool(compare(l, r) != Ordering::Less),
+        _ => arithmetic(op, l, r)?,
+    };
+    Ok(value)
+}
+
+fn arithmetic(op: BinOp, l: &Value, r: &Value) -> Result<Value> {
+    if let (Some(a), Some(b)) = (as_f64(l), as_f64(r)) {
+        let n = match op {
+            BinOp::Add => a + b,
+            BinOp::Sub => a - b,
+            BinOp::Mul => a * b,
+            BinOp::Div if b == 0.0 => anyhow::bail!(
+                "{} and {} cannot be divided because the divisor is zero",
+                describe_value(l),
+                describe_value(r)
+            ),
+            BinOp::Div => a / b,
+            BinOp::Mod if b as i64 == 0 => anyhow::bail!(
+                "{} and {} cannot be divided because the divisor is zero",
+                describe_value(l),
+                describe_value(r)
+            ),
+            BinOp::Mod => ((a as i64) % (b as i64)) as f64,
+            _ => unreachable!(),
+        };
+        return Ok(to_number(n));
+    }
+    let value = match (op, l, r) {
+        (BinOp::Add, Value::Null, v) | (BinOp::Add, v, Value::Null) => v.clone(),
+        (BinOp::Add, Value::String(a), Value::String(b)) => {
+            check_string_len(a.len() as f64 + b.len() as f64)?;
+            Value::String(format!("{}{}", a, b))
+        }
+        (BinOp::Add, Value::Array(a), Value::Array(b)) => {
+            Value::Array(a.iter().chain(b).cloned().collect())
+        }
+        (BinOp::Add, Value::Object(a), Value::Object(b)) => {
+            let mut map = a.clone();
+            map.extend(b.clone());
+            Value::Object(map)
+        }
+        (BinOp::Sub, Value::Array(a), Value::Array(b)) => Value::Array(
+            a.iter()
+                .filter(|x| !b.iter().any(|y| compare(x, y) == Ordering::Equal))
+                .cloned()
+                .collect(),
+        ),
+        (BinOp::Mul, Value::String(s), Value::Number(n))
+        | (BinOp::Mul, Value::Number(n), Value::String(s)) => {
+            let n = n.as_f64().unwrap_or_default();
+            if n > 0.0 {
+                check_string_len(s.len() as f64 * n.ceil())?;
+                Value::String(s.repeat(n.ceil() as usize))
+            } else {
+                Value::Null
+            }
+        }
+        (BinOp::Mul, Value::Object(_), Value::Object(_)) => deep_merge(l, r),
+        (BinOp::Div, Value::String(a), Value::String(sep)) => {
+            let parts: Vec<Value> = if a.is_empty() {
+                Vec::new()
+            } else if sep.is_empty() {
+                a.chars().map(|c| Value::String(c.to_string())).collect()
+            } else {
+                a.split(sep.as_str())
+                    .map(|s| Value::String(s.to_string()))
+                    .collect()
+            };
+            Value::Array(parts)
+        }
+        _ => {
+            let verb = match op {
+                BinOp::Add => "added",
+                BinOp::Sub => "subtracted",
+                BinOp::Mul => "multiplied",
+                _ => "divided",
+            };
+            anyhow::bail!(
+                "{} and {} cannot be {}",
+                describe_value(l),
+                describe_value(r),
+                verb
+            )
+        }
+    };
+    Ok(value)
+}
+
+// 先检查结果的长度，避免 "ab" * 100000000000 这样的表达式分配内存失败
+fn check_string_len(len: f64) -> Result<()> {
+    if len > MAX_STRING_BYTES as f64 {
+        anyhow::bail!(
+            "String of {} bytes exceeds the limit of {} bytes",
+            len,
+            MAX_STRING_BYTES
+        );
+    }
+    Ok(())
+}
+
+fn deep_merge(a: &Value, b: &Value) -> Value {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut map = a.clone();
+            for (k, bv) in b {
+                let merged = match map.get(k) {
+                    Some(av) => deep_merge(av, bv),
+                    None => bv.clone(),
+                };
+                map.insert(k.clone(), merged);
+            }
+            Value::Object(map)
+        }
+        (_, b) => b.clone(),
+    }
+}
+
+// jq 的排序规则：null < false < true < 数字 < 字符串 < 数组 < 对象
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x
+            .as_f64()
+            .partial_cmp(&y.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Array(x), Value::Array(y)) => {
+            for (x, y) in x.iter().zip(y) {
+                match compare(x, y) {
+                    Ordering::Equal => continue,
+                    ordering => return ordering,
+                }
+            }
+            x.len().cmp(&y.len())
+        }
+        // 先比较排序后的 key，再按 key 的顺序比较值
+        (Value::Object(x), Value::Object(y)) => {
+            let mut xk: Vec<&String> = x.keys().collect();
+            let mut yk: Vec<&String> = y.keys().collect();
+            xk.sort();
+            yk.sort();
+            xk.cmp(&yk).then_with(|| {
+                xk.iter()
+                    .map(|k| compare(&x[*k], &y[*k]))
+                    .find(|o| *o != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn truthy(v: &Value) -> bool {
+    !matches!(v, Value::Null | Value::Bool(false))
+}
+
+fn as_f64(v: &Value) -> Option<f64> {
+    v.as_f64()
+}
+
+// 整数结果输出为整数，例如 1 + 2 输出 3 而不是 3.0
+fn to_number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < MAX_SAFE_INTEGER {
+        Value::from(n as i64)
+    } else {
+        Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+fn to_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn expect_str(v: &Value) -> Result<&str> {
+    match v {
+        Value::String(s) => Ok(s),
+        v => anyhow::bail!("{} is not a string", describe_value(v)),
+    }
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// 错误信息里带上值，过长时截断，例如 string ("hello wor...)
+fn describe_value(v: &Value) -> String {
+    let text = v.to_string();
+    let text = if text.chars().count() > 30 {
+        format!("{}...", text.chars().take(27).collect::<String>())
+    } else {
+        text
+    };
+    format!("{} ({})", type_name(v), text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn players() -> Value {
+        json!({
+            "team": "Juventus",
+            "players": [
+                {"name": "Buffon", "kit": 1, "nationality": "Italy"},
+                {"name": "Dybala", "kit": 10, "nationality": "Argentina"},
+                {"name": "Chiellini", "kit": 3, "nationality": "Italy"},
+                {"name": "Pjanic", "kit": 5, "nationality": "Bosnia"},
+                {"name": "Higuain", "kit": 9, "nationality": "Argentina"}
+            ]
+        })
+    }
+
+    fn query(filter: &str, input: &Value) -> Vec<Value> {
+        JsonQuery::compile(filter)
+            .unwrap()
+            .run(input, &[("min".to_string(), json!(4))])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_query_paths_and_pipes() {
+        let data = players();
+        assert_eq!(
+            query(".players[] | select(.kit > 5) | .name", &data),
+            vec![json!("Dybala"), json!("Higuain")]
+        );
+        assert_eq!(query(".players[-1].name", &data), vec![json!("Higuain")]);
+        assert_eq!(query(".\"team\"", &data), vec![json!("Juventus")]);
+        assert_eq!(
+            query(".players[1:3] | map(.kit)", &data),
+            vec![json!([10, 3])]
+        );
+        assert_eq!(query(".team[:4]", &data), vec![json!("Juve")]);
+        assert_eq!(query(".missing.field", &data), vec![json!(null)]);
+        assert_eq!(
+            query(".team, .players[0].kit", &data),
+            vec![json!("Juventus"), json!(1)]
+        );
+        assert_eq!(query(".team[]?", &data), Vec::<Value>::new());
+        assert_eq!(query("[.. | numbers] | add", &data), vec![json!(28)]);
+        assert_eq!(
+            query(".players | map(select(.kit >= $min)) | length", &data),
+            vec![json!(3)]
+        );
+    }
+
+    #[test]
+    fn test_query_construction_and_functions() {
+        let data = players();
+        assert_eq!(
+            query(
+                ".players[0] | {name, number: .kit * 2, (.nationality): true}",
+                &data
+            ),
+            vec![json!({"name": "Buffon", "number": 2, "Italy": true})]
+        );
+        assert_eq!(
+            query(
+                ".players | group_by(.nationality) | map({nationality: .[0].nationality, count: length})",
+                &data
+            ),
+            vec![json!([
+                {"nationality": "Argentina", "count": 2},
+                {"nationality": "Bosnia", "count": 1},
+                {"nationality": "Italy", "count": 2}
+            ])]
+        );
+        assert_eq!(
+            query(".players | sort_by(.kit) | reverse | first.name", &data),
+            vec![json!("Dybala")]
+        );
+        assert_eq!(
+            query(".players | max_by(.kit) | \"\\(.name) #\\(.kit)\"", &data),
+            vec![json!("Dybala #10")]
+        );
+        assert_eq!(
+            query("reduce .players[] as $p (0; . + $p.kit)", &data),
+            vec![json!(28)]
+        );
+        assert_eq!(
+            query(".players[] | .kit as $k | if $k > 9 then \"big\" elif $k == 1 then \"keeper\" else empty end", &data),
+            vec![json!("keeper"), json!("big")]
+        );
+        assert_eq!(
+            query(".coach // \"unknown\"", &data),
+            vec![json!("unknown")]
+        );
+        assert_eq!(
+            query(
+                "{a: 1, b: 2} | to_entries | map(.value) | join(\",\")",
+                &data
+            ),
+            vec![json!("1,2")]
+        );
+        assert_eq!(
+            query(
+                "{a: 1, b: 2} | with_entries(select(.value > 1)) | keys",
+                &data
+            ),
+            vec![json!(["b"])]
+        );
+        assert_eq!(
+            query("[3, 1, 3, 2] | unique, (. - [3]), any(. > 2)", &data),
+            vec![json!([1, 2, 3]), json!([1, 2]), json!(true)]
+        );
+    }
+
+    #[test]
+    fn test_query_strings_and_formats() {
+        let data = json!("Paulo Dybala 10");
+        assert_eq!(query("split(\" \") | length", &data), vec![json!(3)]);
+        assert_eq!(query("test(\"^paulo\"; \"i\")", &data), vec![json!(true)]);
+        assert_eq!(
+            query("capture(\"(?<first>\\\\w+) (?<last>\\\\w+)\")", &data),
+            vec![json!({"first": "Paulo", "last": "Dybala"})]
+        );
+        assert_eq!(
+            query("gsub(\"(?<d>[0-9])\"; \"<\\(.d)>\")", &data),
+            vec![json!("Paulo Dybala <1><0>")]
+        );
+        assert_eq!(
+            query("ascii_upcase | ltrimstr(\"PAULO \")", &data),
+            vec![json!("DYBALA 10")]
+        );
+        assert_eq!(
+            query("[\"a,b\", 1, null, true] | @csv", &data),
+            vec![json!("\"a,b\",1,,true")]
+        );
+        assert_eq!(query("@base64 | @base64d", &data), vec![data.clone()]);
+        assert_eq!(query("\"a b&c\" | @uri", &data), vec![json!("a%20b%26c")]);
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let err = |filter: &str| JsonQuery::compile(filter).unwrap_err().to_string();
+        assert_eq!(
+            err(".a |"),
+            "Parse error: expected an expression, found end of expression"
+        );
+        assert_eq!(err("foo(1)"), "Unknown function foo/1 at position 1");
+        assert_eq!(
+            err(".a = 1"),
+            "Parse error at position 4: assignment operators are not supported"
+        );
+        assert_eq!(err(".a )"), "Parse error at position 4: unexpected `)`");
+        assert_eq!(
+            err("try .a catch 1"),
+            "Parse error at position 1: `try` is not supported"
+        );
+        assert_eq!(
+            err("del(.a)"),
+            "Parse error at position 1: `del` is not supported"
+        );
+
+        let run = |filter: &str, input: Value| {
+            JsonQuery::compile(filter)
+                .unwrap()
+                .run(&input, &[])
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(run(".[]", json!(3)), "Cannot iterate over number (3)");
+        assert_eq!(
+            run(".[0]", json!({"a": 1})),
+            "Cannot index object with number"
+        );
+        assert_eq!(
+            run(". + 1", json!("a")),
+            "string (\"a\") and number (1) cannot be added"
+        );
+        assert_eq!(run("$missing", json!(null)), "$missing is not defined");
+        assert_eq!(
+            run("\"ab\" * 100000000000", json!(null)),
+            "String of 200000000000 bytes exceeds the limit of 268435456 bytes"
+        );
+        assert_eq!(
+            run("range(1e17; 1e18)", json!(null)),
+            "Range bound 100000000000000000 is too large"
+        );
+    }
+
+    #[test]
+    fn test_query_stops_early() {
+        let data = json!([1, 2, 3]);
+        assert_eq!(query("first(range(100000000000))", &data), vec![json!(0)]);
+        assert_eq!(
+            query("[limit(3; range(100000000000) | . * 2)]", &data),
+            vec![json!([0, 2, 4])]
+        );
+        assert_eq!(
+            query(
+                "first(range(100000000000) as $i | select($i > 5) | $i)",
+                &data
+            ),
+            vec![json!(6)]
+        );
+        assert_eq!(
+            query("first(.[] | select(. > 1)), limit(0; .[])", &data),
+            vec![json!(2)]
+        );
+        assert_eq!(query("[limit(2; .[], error)]", &data), vec![json!([1, 2])]);
+        assert_eq!(
+            query(
+                "reduce limit(4; range(100000000000)) as $i (0; . + $i)",
+                &data
+            ),
+            vec![json!(6)]
+        );
+    }
+
+    #[test]
+    fn test_process_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("names.txt");
+
+        let opt = JsonQueryOpt {
+            from: None,
+            to: DocFormat::Json,
+            raw_output: true,
+            compact: false,
+            vars: Vec::new(),
+        };
+        process_query(
+            "fixtures/squad.yaml",
+            output.to_str().unwrap(),
+            ".players[] | select(.kit > 9) | .name",
+            opt,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "Dybala\nBonucci\n"
+        );
+
+        let opt = JsonQueryOpt {
+            from: None,
+            to: DocFormat::Ndjson,
+            raw_output: false,
+            compact: false,
+            vars: Vec::new(),
+        };
+        process_query(
+            "fixtures/squad.yaml",
+            output.to_str().unwrap(),
+            ".players[] | {name}",
+            opt,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "{\"name\":\"Dybala\"}\n{\"name\":\"Higuain\"}\n{\"name\":\"Bonucci\"}\n"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::convert::{load_document, load_document_with_format, to_document};
+use crate::{cli::DocFormat, get_writer, ReportFormat};
+
+const DRAFT_2020_12: &str = "https://json-schema.org/draft/2020-12/schema";
+
+// 类型按这个顺序输出，同时出现 integer 和 number 时只保留 number
+const TYPE_ORDER: &[&str] = &[
+    "null", "boolean", "integer", "number", "string", "array", "object",
+];
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SchemaError {
+    // 出错位置的 JSON pointer，ndjson 输入时以行号（从 0 开始）开头
+    pub pointer: String,
+    // 失败的关键字，例如 type、required、minimum
+    pub keyword: String,
+    pub schema_path: String,
+    pub message: String,
+}
+
+// 推断过程中每个位置上见过的值的汇总
+#[derive(Debug, Default)]
+struct Shape {
+    types: Vec<&'static str>,
+    // None 表示还没有见过字符串，Some(None) 表示字符串的格式不一致
+    format: Option<Option<&'static str>>,
+    items: Option<Box<Shape>>,
+    properties: Vec<(String, Shape, usize)>,
+    objects: usize,
+}
+
+pub fn process_schema_validate(
+    input: &str,
+    schema: &str,
+    output: &str,
+    format: ReportFormat,
+    from: Option<DocFormat>,
+) -> Result<()> {
+    let errors = validate_document(input, schema, from)?;
+    let mut writer = get_writer(output)?;
+    match format {
+        ReportFormat::Text => {
+            for e in &errors {
+                writeln!(
+                    writer,
+                    "pointer {:?}, keyword {:?}: {}",
+                    e.pointer, e.keyword, e.message
+                )?;
+            }
+            if errors.is_empty() {
+                writeln!(writer, "{} is valid", input)?;
+            }
+        }
+        ReportFormat::Json => {
+            let report = serde_json::json!({
+                "valid": errors.is_empty(),
+                "errors": errors,
+            });
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writeln!(writer)?;
+        }
+    }
+    writer.finish()?;
+
+    if !errors.is_empty() {
+        anyhow::bail!("Validation failed: {} errors", errors.len());
+    }
+    Ok(())
+}
+
+// 没有声明 $schema 时按 draft 2020-12 校验，同时校验 format
+pub fn validate_document(
+    input: &str,
+    schema: &str,
+    from: Option<DocFormat>,
+) -> Result<Vec<SchemaError>> {
+    let schema = load_document(schema, None)?;
+    let validator = jsonschema::options()
+        .should_validate_formats(true)
+        .build(&schema)
+        .map_err(|e| anyhow::anyhow!("Invalid schema: {}", e))?;
+
+    // ndjson 的每一行是一个独立的文档
+    let (value, format) = load_document_with_format(input, from)?;
+    let instances = match (format, value) {
+        (DocFormat::Ndjson, Value::Array(items)) => items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| (format!("/{}", i), item))
+            .collect(),
+        (_, value) => vec![(String::new(), value)],
+    };
+
+    let mut errors = Vec::new();
+    for (prefix, instance) in &instances {
+        for e in validator.iter_errors(instance) {
+            errors.push(SchemaError {
+                pointer: format!("{}{}", prefix, e.instance_path().as_str()),
+                keyword: e.kind().keyword().to_string(),
+                schema_path: e.schema_path().as_str().to_string(),
+                message: e.to_string(),
+            });
+        }
+    }
+    Ok(errors)
+}
+
+pub fn process_schema_infer(
+    input: &str,
+    output: &str,
+    from: Option<DocFormat>,
+    to: DocFormat,
+) -> Result<()> {
+    // ndjson 的每一行作为一个样本，其他格式整个文档是一个样本
+    let samples = match load_document_with_format(input, from)? {
+        (Value::Array(items), DocFormat::Ndjson) => items,
+        (value, _) => vec![value],
+    };
+    let schema = infer_schema(&samples);
+    let mut writer = get_writer(output)?;
+    writer.write_all(to_document(&schema, to, true)?.as_bytes())?;
+    writer.finish()?;
+    Ok(())
+}
+
+// 根据样本推断 draft 2020-12 的 schema，所有样本都有的字段才是 required
+pub fn infer_schema(samples: &[Value]) -> Value {
+    let mut shape = Shape::default();
+    for sample in samples {
+        shape.add(sample);
+    }
+    let mut schema = Map::new();
+    schema.insert(
+        "$schema".to_string(),
+        Value::String(DRAFT_2020_12.to_string()),
+    );
+    if let Value::Object(inferred) = shape.to_schema() {
+        schema.extend(inferred);
+    }
+    Value::Object(schema)
+}
+
+impl Shape {
+    fn add(&mut self, value: &Value) {
+        let ty = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        if !self.types.contains(&ty) {
+            self.types.push(ty);
+        }
+
+        match value {
+            Value::String(s) => {
+                let format = string_format(s);
+                self.format = match self.format {
+                    None => Some(format),
+                    Some(seen) if seen == format => Some(seen),
+                    Some(_) => Some(None),
+                };
+            }
+            Value::Array(items) => {
+                let shape = self.items.get_or_insert_with(Default::default);
+                for item in items {
+                    shape.add(item);
+                }
+            }
+            Value::Object(map) => {
+                self.objects += 1;
+                for (key, value) in map {
+                    match self.properties.iter_mut().find(|(k, _, _)| k == key) {
+                        Some((_, shape, count)) => {
+                            shape.add(value);
+                            *count += 1;
+                        }
+                        None => {
+                            let mut shape = Shape::default();
+                            shape.add(value);
+                            self.properties.push((key.clone(), shape, 1));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn to_schema(&self) -> Value {
+        let mut schema = Map::new();
+        let types: Vec<&str> = TYPE_ORDER
+            .iter()
+            .filter(|t| self.types.contains(t))
+            .filter(|t| **t != "integer" || !self.types.contains(&"number"))
+            .copied()
+            .collect();
+        match types.as_slice() {
+            // 没有任何样本时不做限制
+            [] => return Value::Object(schema),
+            [ty] => schema.insert("type".to_string(), Value::from(*ty)),
+            types => schema.insert("type".to_string(), Value::from(types.to_vec())),
+        };
+        if let Some(Some(format)) = self.format {
+            schema.insert("format".to_string(), Value::from(format));
+        }
+        if let Some(items) = &self.items {
+            schema.insert("items".to_string(), items.to_schema());
+        }
+        if self.objects > 0 {
+            let properties: Map<String, Value> = self
+                .properties
+                .iter()
+                .map(|(key, shape, _)| (key.clone(), shape.to_schema()))
+                .collect();
+            let required: Vec<Value> = self
+                .properties
+                .iter()
+                .filter(|(_, _, count)| *count == self.objects)
+                .map(|(key, _, _)| Value::from(key.as_str()))
+                .collect();
+            schema.insert("properties".to_string(), Value::Object(properties));
+            if !required.is_empty() {
+                schema.insert("required".to_string(), Value::Array(required));
+            }
+        }
+        Value::Object(schema)
+    }
+}
+
+// 只识别 RFC 3339 的日期和日期时间，其他字符串不加 format
+fn string_format(s: &str) -> Option<&'static str> {
+    if s.len() == 10 && chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok() {
+        Some("date")
+    } else if chrono::DateTime::parse_from_rfc3339(s).is_ok() {
+        Some("date-time")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_infer_schema() {
+        let samples = vec![
+            json!({"name": "Dybala", "kit": 10, "dob": "1993-11-15", "tags": ["fw"]}),
+            json!({"name": "Buffon", "kit": 1.5, "dob": "1978-01-28", "captain": true}),
+        ];
+        assert_eq!(
+            infer_schema(&samples),
+            json!({
+                "$schema": DRAFT_2020_12,
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string"},
+                    "kit": {"type": "number"},
+                    "dob": {"type": "string", "format": "date"},
+                    "tags": {"type": "array", "items": {"type": "string"}},
+                    "captain": {"type": "boolean"}
+                },
+                "required": ["name", "kit", "dob"]
+            })
+        );
+        assert_eq!(
+            infer_schema(&[json!([1, null, "2024-01-01T00:00:00Z"])])["items"],
+            json!({"type": ["null", "integer", "string"], "format": "date-time"})
+        );
+    }
+
+    #[test]
+    fn test_validate_document() {
+        let schema = "fixtures/player_schema.yaml";
+        assert!(validate_document("fixtures/player.toml", schema, None)
+            .unwrap()
+            .is_empty());
+
+        let errors = validate_document("fixtures/invalid_players.ndjson", schema, None).unwrap();
+        let failed: Vec<(&str, &str)> = errors
+            .iter()
+            .map(|e| (e.pointer.as_str(), e.keyword.as_str()))
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                ("/1/kit", "minimum"),
+                ("/1/dob", "format"),
+                ("/2", "required"),
+                ("/2/kit", "type"),
+            ]
+        );
+        assert_eq!(errors[3].message, "\"7\" is not of type \"integer\"");
+    }
+}
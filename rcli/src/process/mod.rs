@@ -1,16 +1,67 @@
 mod b64;
+mod columnar;
+mod convert;
 mod csv_convert;
+mod csv_diff;
+mod csv_filter;
+mod csv_from_json;
+mod csv_nest;
+mod csv_ops;
+mod csv_query;
+mod csv_sort;
+mod csv_stats;
+mod csv_types;
+mod csv_validate;
+mod doc_diff;
+mod encoding;
+mod fake;
 mod gen_pass;
 mod http_serve;
+mod json_query;
+mod json_schema;
 mod jwt;
+mod record_writer;
+mod spreadsheet;
+mod table;
 mod text;
+mod time;
 
-pub use b64::{process_decode, process_encode};
-pub use csv_convert::process_csv;
+pub use b64::{
+    decode, encode, hexdump, process_decode, process_encode, write_decoded, write_encoded,
+};
+pub use columnar::process_csv_from_parquet;
+pub use convert::{
+    detect_format, load_document, load_document_with_format, parse_document, process_convert,
+    sort_keys, to_document, ConvertOpt,
+};
+pub use csv_convert::{process_csv, CsvConvertOpt, CsvReaderOpt};
+pub use csv_diff::{diff_csv, process_csv_diff, CsvDiff, RowDiff};
+pub use csv_filter::RowFilter;
+pub use csv_from_json::{process_csv_from_json, CsvFromJsonOpt};
+pub use csv_ops::{process_csv_cat, process_csv_dedupe, process_csv_split, SplitMode};
+pub use csv_query::{process_csv_query, query_csv, CsvQueryOpt, QueryResult};
+pub use csv_sort::{process_csv_sort, CsvSortOpt, SortKey};
+pub use csv_stats::{csv_stats, process_csv_stats, CsvStatsOpt};
+pub use csv_types::{ColumnRule, ColumnSpec, ColumnType, CsvSchema};
+pub use csv_validate::{process_csv_validate, validate_csv, Violation};
+pub use doc_diff::{
+    apply_patch, diff_documents, process_diff, process_patch, DocChange, DocDiff, DocDiffOpt,
+    PatchOpt, PathSegment,
+};
+pub use encoding::decode_reader;
+pub use fake::{fake_rows, process_fake, FakeOpt, FakeSchema, FieldKind, FieldRule, FieldSpec};
 pub use gen_pass::{process_genpass, GenPassOpt};
+pub use json_query::{process_query, JsonQuery, JsonQueryOpt};
+pub use json_schema::{
+    infer_schema, process_schema_infer, process_schema_validate, validate_document, SchemaError,
+};
 pub use jwt::{process_jwt_sign, process_jwt_verify};
+pub use record_writer::{new_record_writer, RecordWriter, WriterOpt};
+pub use spreadsheet::{is_spreadsheet, process_csv_to_xlsx};
+pub use table::{render_html, render_markdown, render_table};
 pub use text::{
     process_decrypt, process_encrypt, process_text_generate, process_text_sign, process_text_verify,
 };
+pub use time::{process_time_convert, process_time_now};
 
 pub use http_serve::process_http_serve;
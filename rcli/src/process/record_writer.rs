@@ -0,0 +1,399 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use super::{
+    columnar::ColumnarWriter,
+    csv_from_json::cell_to_string,
+    table::{render_html, render_markdown, render_table, truncate},
+};
+use crate::{
+    cli::{Compression, OutputFormat},
+    finish_csv, get_writer, OutputWriter,
+};
+
+// 逐条写出记录，避免把所有数据都放在内存里
+pub trait RecordWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()>;
+
+    // 写出格式的结尾（比如 json 数组的 `]`），结束压缩流并 flush
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct WriterOpt {
+    // 输出为 toml 时，数据放在 [[<toml-key>]] 下面
+    pub toml_key: String,
+    // table / markdown / html 里单元格的最大显示宽度，超出部分截断
+    pub max_width: Option<usize>,
+    // table / markdown / html 第一列显示行号
+    pub row_numbers: bool,
+    // parquet / arrow 的压缩算法，None 表示使用格式的默认值
+    pub compression: Option<Compression>,
+    // parquet / arrow 每个 row group 的行数
+    pub row_group_size: usize,
+}
+
+struct JsonWriter {
+    writer: OutputWriter,
+    count: usize,
+}
+
+struct NdjsonWriter {
+    writer: OutputWriter,
+}
+
+struct YamlWriter {
+    writer: OutputWriter,
+    count: usize,
+}
+
+struct TomlWriter {
+    writer: OutputWriter,
+    key: String,
+    count: usize,
+}
+
+// 第一条记录的 key 作为表头，之后的记录按表头的顺序输出，嵌套的对象和数组写成 json
+struct CsvRecordWriter {
+    writer: csv::Writer<OutputWriter>,
+    headers: Option<Vec<String>>,
+}
+
+// 表格需要知道每一列的宽度，所以先缓存所有记录，finish 时再一起渲染
+struct TableWriter {
+    writer: OutputWriter,
+    format: OutputFormat,
+    opt: WriterOpt,
+    records: Vec<Value>,
+}
+
+impl Default for WriterOpt {
+    fn default() -> Self {
+        Self {
+            toml_key: "rows".to_string(),
+            max_width: None,
+            row_numbers: false,
+            compression: None,
+            row_group_size: 65536,
+        }
+    }
+}
+
+pub fn new_record_writer(
+    format: OutputFormat,
+    output: &str,
+    opt: &WriterOpt,
+) -> Result<Box<dyn RecordWriter>> {
+    let writer = get_writer(output)?;
+    let writer: Box<dyn RecordWriter> = match format {
+        OutputFormat::Json => Box::new(JsonWriter { writer, count: 0 }),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter { writer }),
+        OutputFormat::Csv => Box::new(CsvRecordWriter {
+            writer: csv::Writer::from_writer(writer),
+            headers: None,
+        }),
+        OutputFormat::Yaml => Box::new(YamlWriter { writer, count: 0 }),
+        OutputFormat::Toml => Box::new(TomlWriter {
+            writer,
+            key: opt.toml_key.clone(),
+            count: 0,
+        }),
+        // 没有声明列类型时，根据第一个 row group 推断
+        OutputFormat::Parquet | OutputFormat::Arrow => {
+            Box::new(ColumnarWriter::new(format, writer, None, opt))
+        }
+        OutputFormat::Table | OutputFormat::Markdown | OutputFormat::Html => {
+            Box::new(TableWriter {
+                writer,
+                format,
+                opt: opt.clone(),
+                records: Vec::new(),
+            })
+        }
+    };
+    Ok(writer)
+}
+
+impl RecordWriter for JsonWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        let sep = if self.count == 0 { "[\n" } else { ",\n" };
+        self.writer.write_all(sep.as_bytes())?;
+        // 保持和 serde_json::to_string_pretty 整个数组时一样的缩进
+        let pretty = serde_json::to_string_pretty(record)?;
+        for (i, line) in pretty.lines().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b"\n")?;
+            }
+            write!(self.writer, "  {}", line)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let end = if self.count == 0 { "[]" } else { "\n]" };
+        self.writer.write_all(end.as_bytes())?;
+        self.writer.finish()
+    }
+}
+
+impl RecordWriter for NdjsonWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+impl RecordWriter for CsvRecordWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        let Value::Object(map) = record else {
+            anyhow::bail!("Expect an object, got: {}", record);
+        };
+        let headers = match &self.headers {
+            Some(headers) => headers,
+            None => {
+                let headers: Vec<String> = map.keys().cloned().collect();
+                self.writer.write_record(&headers)?;
+                self.headers.insert(headers)
+            }
+        };
+        let row = headers
+            .iter()
+            .map(|header| map.get(header).map(cell_to_string).unwrap_or_default());
+        self.writer.write_record(row)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        finish_csv(self.writer)
+    }
+}
+
+impl RecordWriter for YamlWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        // 单个元素的序列，拼接起来就是完整的 yaml 序列
+        let item = serde_yaml::to_string(&[record])?;
+        self.writer.write_all(item.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.count == 0 {
+            self.writer.write_all(b"[]\n")?;
+        }
+        self.writer.finish()
+    }
+}
+
+impl RecordWriter for TomlWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        if self.count > 0 {
+            self.writer.write_all(b"\n")?;
+        }
+        let table = to_toml(&self.key, vec![record.clone()])?;
+        self.writer.write_all(table.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+impl RecordWriter for TableWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        self.records.push(record.clone());
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        // 所有记录的 key 的并集作为表头，嵌套的对象和数组显示为 json
+        let mut headers: Vec<String> = Vec::new();
+        for record in &self.records {
+            if let Value::Object(map) = record {
+                for key in map.keys() {
+                    if !headers.contains(key) {
+                        headers.push(key.clone());
+                    }
+                }
+            }
+        }
+        let mut rows: Vec<Vec<String>> = self
+            .records
+            .iter()
+            .map(|record| {
+                headers
+                    .iter()
+                    .map(|header| {
+                        let cell = record.get(header).map(cell_to_string).unwrap_or_default();
+                        match self.opt.max_width {
+                            Some(max_width) => truncate(&cell, max_width),
+                            None => cell,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        if self.opt.row_numbers {
+            headers.insert(0, "#".to_string());
+            for (i, row) in rows.iter_mut().enumerate() {
+                row.insert(0, (i + 1).to_string());
+            }
+        }
+
+        let content = match self.format {
+            OutputFormat::Markdown => render_markdown(&headers, &rows),
+            OutputFormat::Html => render_html(&headers, &rows),
+            _ => render_table(&headers, &rows),
+        };
+        let mut writer = self.writer;
+        writer.write_all(content.as_bytes())?;
+        writer.finish()
+    }
+}
+
+// TOML 的顶层必须是 table，所以把所有行放到 key 下面，序列化成 [[key]]
+fn to_toml(key: &str, rows: Vec<Value>) -> Result<String> {
+    let rows = rows.into_iter().map(strip_nulls).collect();
+    let mut doc = serde_json::Map::new();
+    doc.insert(key.to_string(), Value::Array(rows));
+    Ok(toml::to_string(&doc)?)
+}
+
+// TOML 没有 null，值为 null 的字段和数组元素直接省略
+pub(crate) fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, strip_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .filter(|v| !v.is_null())
+                .map(strip_nulls)
+                .collect(),
+        ),
+        v => v,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn write_all(format: OutputFormat, rows: &[Value]) -> String {
+        write_with(format, rows, &WriterOpt::default())
+    }
+
+    fn write_with(format: OutputFormat, rows: &[Value], opt: &WriterOpt) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("rows.{}", format));
+        let output = path.to_str().unwrap();
+        let mut writer = new_record_writer(format, output, opt).unwrap();
+        for row in rows {
+            writer.write_record(row).unwrap();
+        }
+        writer.finish().unwrap();
+        std::fs::read_to_string(&path).unwrap()
+    }
+
+    #[test]
+    fn test_streaming_writers_match_batch_output() {
+        let rows = vec![
+            json!({"name": "Alice", "kit": 10, "tags": ["a", "b"]}),
+            json!({"name": "Bob", "kit": null, "tags": []}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Json, &rows),
+            serde_json::to_string_pretty(&rows).unwrap()
+        );
+        assert_eq!(
+            write_all(OutputFormat::Yaml, &rows),
+            serde_yaml::to_string(&rows).unwrap()
+        );
+        assert_eq!(
+            write_all(OutputFormat::Toml, &rows),
+            to_toml("rows", rows.clone()).unwrap()
+        );
+        assert_eq!(
+            write_all(OutputFormat::Ndjson, &rows),
+            "{\"name\":\"Alice\",\"kit\":10,\"tags\":[\"a\",\"b\"]}\n{\"name\":\"Bob\",\"kit\":null,\"tags\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_writer() {
+        let rows = vec![
+            json!({"name": "Alice", "kit": 10, "tags": ["a", "b"]}),
+            json!({"kit": null, "name": "Bob, Jr."}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Csv, &rows),
+            "name,kit,tags\nAlice,10,\"[\"\"a\"\",\"\"b\"\"]\"\n\"Bob, Jr.\",,\n"
+        );
+    }
+
+    #[test]
+    fn test_streaming_writers_empty() {
+        assert_eq!(write_all(OutputFormat::Json, &[]), "[]");
+        assert_eq!(write_all(OutputFormat::Yaml, &[]), "[]\n");
+    }
+
+    #[test]
+    fn test_table_writers() {
+        let rows = vec![
+            json!({"name": "Alice", "kit": 10}),
+            json!({"name": "Bob", "nationality": "Italy"}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Markdown, &rows),
+            "| name  | kit | nationality |\n| ----- | --: | ----------- |\n| Alice |  10 |             |\n| Bob   |     | Italy       |\n"
+        );
+        assert!(write_all(OutputFormat::Html, &rows).contains("<th>nationality</th>"));
+    }
+
+    #[test]
+    fn test_table_writer_options() {
+        let opt = WriterOpt {
+            max_width: Some(4),
+            row_numbers: true,
+            ..Default::default()
+        };
+        let content = write_with(OutputFormat::Markdown, &[json!({"name": "Alice"})], &opt);
+        assert_eq!(content, "|   # | name |\n| --: | ---- |\n|   1 | Ali… |\n");
+    }
+
+    #[test]
+    fn test_to_toml() {
+        let rows = vec![
+            json!({"Name": "Alice", "Kit Number": "10"}),
+            json!({"Name": "Bob", "Kit Number": "7"}),
+        ];
+        let ret = to_toml("players", rows).unwrap();
+        assert_eq!(ret.matches("[[players]]").count(), 2);
+        assert!(ret.contains("\"Kit Number\" = \"10\""));
+
+        let doc: toml::Table = ret.parse().unwrap();
+        assert_eq!(doc["players"].as_array().unwrap().len(), 2);
+
+        let ret = to_toml("rows", vec![json!({"a": 1, "b": null})]).unwrap();
+        assert_eq!(ret, "[[rows]]\na = 1\n");
+
+        let ret = to_toml("rows", vec![json!({"a": {"b": null, "c": [1, null]}})]).unwrap();
+        assert_eq!(ret, "[[rows]]\n\n[rows.a]\nc = [1]\n");
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+use calamine::{open_workbook_auto, Data, Range, Reader};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+
+use super::{
+    csv_convert::{build_reader, resolve_headers},
+    csv_types::{infer_column_types, ColumnType},
+    CsvReaderOpt,
+};
+use crate::get_writer;
+
+const SPREADSHEET_EXTENSIONS: &[&str] = &["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+// Excel 里整数也是以浮点数保存的，超过这个范围的浮点数不再转换为整数
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+// xlsx 工作表的大小限制，行数包括表头
+const MAX_XLSX_ROWS: usize = 1_048_576;
+const MAX_XLSX_COLUMNS: usize = 16_384;
+
+pub fn is_spreadsheet(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SPREADSHEET_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// 读取工作表并转换为 csv，这样后面可以和普通的 csv 文件走同样的处理流程
+// 同时根据单元格本身的类型（数字、布尔值、日期）得到每一列的类型
+// 转换出的 csv 使用默认的格式，读取时要用 sheet_reader_builder，不能用用户指定的分隔符、注释和转义字符
+pub(crate) fn read_sheet(
+    path: &str,
+    opt: &CsvReaderOpt,
+) -> Result<(Box<dyn Read>, Vec<ColumnType>)> {
+    let range = load_range(path, opt.sheet.as_deref())?;
+    let (_, width) = range.get_size();
+
+    let mut types: Vec<Option<ColumnType>> = vec![None; width];
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    for (i, row) in range.rows().enumerate() {
+        let mut record = StringRecord::new();
+        for (ty, cell) in types.iter_mut().zip(row) {
+            let (text, cell_type) = cell_to_string(cell);
+            // 表头不参与类型的判断
+            if let (Some(cell_type), false) = (cell_type, i == 0 && opt.header) {
+                *ty = Some(ty.map_or(cell_type, |t| t.merge(cell_type)));
+            }
+            record.push_field(&text);
+        }
+        writer.write_record(&record)?;
+    }
+    let data = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?;
+    let types = types
+        .into_iter()
+        .map(|t| t.unwrap_or(ColumnType::String))
+        .collect();
+    Ok((Box::new(std::io::Cursor::new(data)), types))
+}
+
+// 和 read_sheet 写出的 csv 格式一致，只保留是否有表头的设置
+pub(crate) fn sheet_reader_builder(opt: &CsvReaderOpt) -> ReaderBuilder {
+    let mut builder = ReaderBuilder::new();
+    builder.has_headers(opt.header);
+    builder
+}
+
+// 不指定 sheet 时读取第一个工作表
+fn load_range(path: &str, sheet: Option<&str>) -> Result<Range<Data>> {
+    let mut workbook = open_workbook_auto(path)?;
+    let names = workbook.sheet_names();
+    let name = match sheet {
+        Some(sheet) => names
+            .iter()
+            .find(|name| name.as_str() == sheet)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Sheet not found: {}, available sheets: {}",
+                    sheet,
+                    names.join(", ")
+                )
+            })?
+            .clone(),
+        None => names
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("No sheet found in {}", path))?
+            .clone(),
+    };
+    Ok(workbook.worksheet_range(&name)?)
+}
+
+// 单元格转换为文本和对应的类型，空单元格没有类型
+fn cell_to_string(cell: &Data) -> (String, Option<ColumnType>) {
+    match cell {
+        Data::Empty => (String::new(), None),
+        Data::Int(i) => (i.to_string(), Some(ColumnType::Integer)),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < MAX_SAFE_INTEGER => {
+            ((*f as i64).to_string(), Some(ColumnType::Integer))
+        }
+        Data::Float(f) => (f.to_string(), Some(ColumnType::Float)),
+        Data::Bool(b) => (b.to_string(), Some(ColumnType::Boolean)),
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(dt) if dt.time() == chrono::NaiveTime::MIN => {
+                (dt.format("%Y-%m-%d").to_string(), Some(ColumnType::Date))
+            }
+            Some(dt) => (
+                dt.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                Some(ColumnType::Date),
+            ),
+            None => (dt.as_f64().to_string(), Some(ColumnType::Float)),
+        },
+        Data::DateTimeIso(s) => (s.clone(), Some(ColumnType::Date)),
+        Data::String(s) | Data::DurationIso(s) => (s.clone(), Some(ColumnType::String)),
+        Data::Error(e) => (e.to_string(), Some(ColumnType::String)),
+    }
+}
+
+// 把 csv 写成 xlsx，数字、布尔值和日期按推断的类型写成对应的单元格类型
+pub fn process_csv_to_xlsx(
+    input: &str,
+    output: &str,
+    sheet: &str,
+    infer_sample: usize,
+    opt: CsvReaderOpt,
+) -> Result<()> {
+    let mut reader = build_reader(input, &opt)?;
+    let headers = resolve_headers(&mut reader, &opt)?;
+    check_xlsx_size(1, headers.len())?;
+    // 只有推断类型的样本放在内存里，其余的行边读边写
+    let mut records = reader.into_records();
+    let sample = records
+        .by_ref()
+        .take(infer_sample)
+        .collect::<Result<Vec<_>, _>>()?;
+    let types = infer_column_types(headers.len(), &sample);
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet)?;
+    let bold = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("yyyy-mm-dd");
+    let datetime_format = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+    for (col, header) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, header, &bold)?;
+    }
+    for (i, record) in sample.into_iter().map(Ok).chain(records).enumerate() {
+        let record = record?;
+        // 第 i 行数据写在第 i + 1 行，加上表头一共 i + 2 行
+        check_xlsx_size(i + 2, headers.len())?;
+        let row = i as u32 + 1;
+        for (col, (cell, ty)) in record.iter().zip(&types).enumerate() {
+            let col = col as u16;
+            let trimmed = cell.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            // 样本之外的行无法按类型解析时写成字符串
+            match ty {
+                ColumnType::Integer | ColumnType::Float => match trimmed.parse::<f64>() {
+                    Ok(n) => worksheet.write_number(row, col, n)?,
+                    Err(_) => worksheet.write_string(row, col, cell)?,
+                },
+                ColumnType::Boolean => match trimmed.to_lowercase().parse::<bool>() {
+                    Ok(b) => worksheet.write_boolean(row, col, b)?,
+                    Err(_) => worksheet.write_string(row, col, cell)?,
+                },
+                ColumnType::Date => {
+                    let format = if trimmed.len() > 10 {
+                        &datetime_format
+                    } else {
+                        &date_format
+                    };
+                    match ExcelDateTime::parse_from_str(&trimmed.replace('/', "-")) {
+                        Ok(dt) => worksheet.write_datetime_with_format(row, col, &dt, format)?,
+                        Err(_) => worksheet.write_string(row, col, cell)?,
+                    }
+                }
+                ColumnType::String => worksheet.write_string(row, col, cell)?,
+            };
+        }
+    }
+    worksheet.autofit();
+
+    let buffer = workbook.save_to_buffer()?;
+    let mut writer = get_writer(output)?;
+    writer.write_all(&buffer)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// 超出限制时行号和列号转换为 u32 / u16 会溢出，写入之前先检查
+fn check_xlsx_size(rows: usize, columns: usize) -> Result<()> {
+    if rows > MAX_XLSX_ROWS {
+        anyhow::bail!(
+            "Too many rows for xlsx: {} (including header), the limit is {}",
+            rows,
+            MAX_XLSX_ROWS
+        );
+    }
+    if columns > MAX_XLSX_COLUMNS {
+        anyhow::bail!(
+            "Too many columns for xlsx: {}, the limit is {}",
+            columns,
+            MAX_XLSX_COLUMNS
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xlsx_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = "fixtures/players_types.csv";
+        let xlsx = dir.path().join("players.xlsx");
+
+        process_csv_to_xlsx(
+            input,
+            xlsx.to_str().unwrap(),
+            "Players",
+            1000,
+            CsvReaderOpt::default(),
+        )
+        .unwrap();
+        assert!(is_spreadsheet(xlsx.to_str().unwrap()));
+        assert!(!is_spreadsheet(input));
+
+        let opt = CsvReaderOpt {
+            sheet: Some("Players".to_string()),
+            ..Default::default()
+        };
+        let (mut reader, types) = read_sheet(xlsx.to_str().unwrap(), &opt).unwrap();
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(
+            content,
+            "name,kit,height,active,dob,code\nAlice,10,1.72,true,1990-04-18,007\nBob,7,1.8,false,1992-11-10,010\n"
+        );
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::String,
+                ColumnType::Integer,
+                ColumnType::Float,
+                ColumnType::Boolean,
+                ColumnType::Date,
+                ColumnType::String
+            ]
+        );
+
+        let opt = CsvReaderOpt {
+            sheet: Some("Missing".to_string()),
+            ..Default::default()
+        };
+        assert!(read_sheet(xlsx.to_str().unwrap(), &opt).is_err());
+    }
+
+    #[test]
+    fn test_sheet_ignores_csv_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let xlsx = dir.path().join("notes.xlsx");
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        for (row, cells) in [["id", "note"], ["#1", "say \\\"hi\\\""], ["2", "a;b"]]
+            .iter()
+            .enumerate()
+        {
+            for (col, cell) in cells.iter().enumerate() {
+                worksheet
+                    .write_string(row as u32, col as u16, *cell)
+                    .unwrap();
+            }
+        }
+        workbook.save(&xlsx).unwrap();
+
+        // 注释、转义和分隔符只对 csv 输入有效，不会影响工作表里的内容
+        let opt = CsvReaderOpt {
+            delimiter: ';',
+            comment: Some('#'),
+            escape: Some('\\'),
+            ..Default::default()
+        };
+        let mut reader = build_reader(xlsx.to_str().unwrap(), &opt).unwrap();
+        let headers = resolve_headers(&mut reader, &opt).unwrap();
+        assert_eq!(headers, vec!["id", "note"]);
+        let records: Vec<Vec<String>> = reader
+            .records()
+            .map(|r| r.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(
+            records,
+            vec![vec!["#1", "say \\\"hi\\\""], vec!["2", "a;b"]]
+        );
+    }
+
+    #[test]
+    fn test_xlsx_size_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("wide.csv");
+        let header: Vec<String> = (0..=MAX_XLSX_COLUMNS).map(|i| format!("c{}", i)).collect();
+        std::fs::write(&input, header.join(",") + "\n").unwrap();
+        let output = dir.path().join("wide.xlsx");
+        let err = process_csv_to_xlsx(
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "Sheet1",
+            1000,
+            CsvReaderOpt::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Too many columns for xlsx: 16385, the limit is 16384"
+        );
+        assert!(!output.exists());
+
+        assert!(check_xlsx_size(MAX_XLSX_ROWS, MAX_XLSX_COLUMNS).is_ok());
+        let err = check_xlsx_size(MAX_XLSX_ROWS + 1, 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Too many rows for xlsx: 1048577 (including header), the limit is 1048576"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use unicode_width::UnicodeWidthStr;
+
+// 把表格渲染为终端里对齐的文本表格，按显示宽度对齐（中文、emoji 占两列）
+//
+// ┌──────┬─────┐
+// │ Name │ Kit │
+// ├──────┼─────┤
+// │ Bob  │   7 │
+// └──────┴─────┘
+pub fn render_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    // 换行会破坏表格的对齐，显示为空格
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.replace(['\r', '\n'], " "))
+                .collect()
+        })
+        .collect();
+    let widths = column_widths(headers, &rows);
+
+    let mut out = String::new();
+    out.push_str(&border(&widths, '┌', '┬', '┐'));
+    out.push_str(&line(headers, &widths, false));
+    out.push_str(&border(&widths, '├', '┼', '┤'));
+    for row in &rows {
+        out.push_str(&line(row, &widths, true));
+    }
+    out.push_str(&border(&widths, '└', '┴', '┘'));
+    out
+}
+
+// GitHub 风格的 markdown 表格，数字列右对齐
+//
+// | Name | Kit |
+// | ---- | --: |
+// | Bob  |   7 |
+pub fn render_markdown(headers: &[String], rows: &[Vec<String>]) -> String {
+    let headers: Vec<String> = headers.iter().map(|h| escape_markdown(h)).collect();
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| escape_markdown(cell)).collect())
+        .collect();
+    let widths: Vec<usize> = column_widths(&headers, &rows)
+        .into_iter()
+        .map(|w| w.max(3))
+        .collect();
+    let numeric: Vec<bool> = (0..headers.len())
+        .map(|i| {
+            let mut cells = rows
+                .iter()
+                .filter_map(|row| row.get(i))
+                .filter(|c| !c.is_empty());
+            cells.clone().next().is_some() && cells.all(|c| is_number(c))
+        })
+        .collect();
+
+    let mut out = String::new();
+    out.push_str(&markdown_line(&headers, &widths, &numeric));
+    let separators: Vec<String> = widths
+        .iter()
+        .zip(&numeric)
+        .map(|(width, numeric)| {
+            if *numeric {
+                format!("{}:", "-".repeat(width - 1))
+            } else {
+                "-".repeat(*width)
+            }
+        })
+        .collect();
+    out.push_str(&format!("| {} |\n", separators.join(" | ")));
+    for row in &rows {
+        out.push_str(&markdown_line(row, &widths, &numeric));
+    }
+    out
+}
+
+pub fn render_html(headers: &[String], rows: &[Vec<String>]) -> String {
+    let mut out = String::from("<table>\n  <thead>\n    <tr>");
+    for header in headers {
+        out.push_str(&format!("<th>{}</th>", escape_html(header)));
+    }
+    out.push_str("</tr>\n  </thead>\n  <tbody>\n");
+    for row in rows {
+        out.push_str("    <tr>");
+        for cell in row {
+            out.push_str(&format!("<td>{}</td>", escape_html(cell)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("  </tbody>\n</table>\n");
+    out
+}
+
+// 超过最大显示宽度的单元格截断，末尾加上 …
+pub fn truncate(cell: &str, max_width: usize) -> String {
+    if cell.width() <= max_width {
+        return cell.to_string();
+    }
+    let mut out = String::new();
+    let mut width = 0;
+    for c in cell.chars() {
+        let w = c.to_string().width();
+        if width + w + 1 > max_width {
+            break;
+        }
+        out.push(c);
+        width += w;
+    }
+    out.push('…');
+    out
+}
+
+fn column_widths(headers: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    widths
+}
+
+fn border(widths: &[usize], left: char, mid: char, right: char) -> String {
+    let parts: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+    format!("{}{}{}\n", left, parts.join(&mid.to_string()), right)
+}
+
+fn line(cells: &[String], widths: &[usize], align_numbers: bool) -> String {
+    let parts: Vec<String> = widths
+        .iter()
+        .enumerate()
+        .map(|(i, width)| {
+            let cell = cells.get(i).map(String::as_str).unwrap_or_default();
+            // 数字右对齐，其他左对齐
+            format!(" {} ", pad(cell, *width, align_numbers && is_number(cell)))
+        })
+        .collect();
+    format!("│{}│\n", parts.join("│"))
+}
+
+fn markdown_line(cells: &[String], widths: &[usize], numeric: &[bool]) -> String {
+    let parts: Vec<String> = widths
+        .iter()
+        .enumerate()
+        .map(|(i, width)| {
+            let cell = cells.get(i).map(String::as_str).unwrap_or_default();
+            pad(cell, *width, numeric[i])
+        })
+        .collect();
+    format!("| {} |\n", parts.join(" | "))
+}
+
+fn pad(cell: &str, width: usize, right: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(cell.width()));
+    if right {
+        format!("{}{}", padding, cell)
+    } else {
+        format!("{}{}", cell, padding)
+    }
+}
+
+fn is_number(cell: &str) -> bool {
+    cell.parse::<f64>().is_ok()
+}
+
+fn escape_markdown(cell: &str) -> String {
+    cell.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+fn escape_html(cell: &str) -> String {
+    cell.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (Vec<String>, Vec<Vec<String>>) {
+        let headers = vec!["Name".to_string(), "Kit".to_string()];
+        let rows = vec![
+            vec!["Bob".to_string(), "7".to_string()],
+            vec!["Alice".to_string(), "10".to_string()],
+        ];
+        (headers, rows)
+    }
+
+    #[test]
+    fn test_render_table() {
+        let (headers, rows) = sample();
+        let expected = "\
+┌───────┬─────┐
+│ Name  │ Kit │
+├───────┼─────┤
+│ Bob   │   7 │
+│ Alice │  10 │
+└───────┴─────┘
+";
+        assert_eq!(render_table(&headers, &rows), expected);
+    }
+
+    #[test]
+    fn test_render_table_unicode_width() {
+        let headers = vec!["城市".to_string(), "n".to_string()];
+        let rows = vec![vec!["Turin".to_string(), "1".to_string()]];
+        let expected = "\
+┌───────┬───┐
+│ 城市  │ n │
+├───────┼───┤
+│ Turin │ 1 │
+└───────┴───┘
+";
+        assert_eq!(render_table(&headers, &rows), expected);
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let (headers, mut rows) = sample();
+        rows[0][0] = "B|b".to_string();
+        let expected = "\
+| Name  | Kit |
+| ----- | --: |
+| B\\|b  |   7 |
+| Alice |  10 |
+";
+        assert_eq!(render_markdown(&headers, &rows), expected);
+    }
+
+    #[test]
+    fn test_render_html() {
+        let headers = vec!["Name".to_string()];
+        let rows = vec![vec!["<b>&</b>".to_string()]];
+        assert_eq!(
+            render_html(&headers, &rows),
+            "<table>\n  <thead>\n    <tr><th>Name</th></tr>\n  </thead>\n  <tbody>\n    <tr><td>&lt;b&gt;&amp;&lt;/b&gt;</td></tr>\n  </tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Turin", 10), "Turin");
+        assert_eq!(truncate("Cristiano Ronaldo", 10), "Cristiano…");
+        assert_eq!(truncate("中华人民共和国", 6), "中华…");
+    }
+}
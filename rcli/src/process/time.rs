@@ -0,0 +1,256 @@
+use core::fmt;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::{
+    cli::{TimeFormat, TimeZoneSpec},
+    parse_relative_duration,
+};
+
+const NAIVE_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"];
+
+pub fn process_time_now(format: TimeFormat, tz: TimeZoneSpec) -> Result<String> {
+    let now = Utc::now();
+    format_time(now, now, format, tz)
+}
+
+pub fn process_time_convert(
+    input: &str,
+    from: Option<TimeFormat>,
+    format: TimeFormat,
+    tz: TimeZoneSpec,
+) -> Result<String> {
+    let now = Utc::now();
+    let time = parse_time(input, from, tz, now)?;
+    format_time(time, now, format, tz)
+}
+
+fn parse_time(
+    input: &str,
+    from: Option<TimeFormat>,
+    tz: TimeZoneSpec,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(value) = input.parse::<i64>() {
+        let unit = from.unwrap_or_else(|| guess_unix_unit(value));
+        return from_unix(value, unit);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc2822(input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Some(naive) = parse_naive(input) {
+        return from_naive(naive, tz);
+    }
+    let offset =
+        parse_relative_duration(input).with_context(|| format!("Unsupported time: {}", input))?;
+    now.checked_add_signed(offset)
+        .ok_or_else(|| anyhow::anyhow!("Time out of range: {}", input))
+}
+
+// 根据数字的大小猜测时间戳的单位：秒 / 毫秒 / 微秒 / 纳秒
+fn guess_unix_unit(value: i64) -> TimeFormat {
+    match value.unsigned_abs() {
+        0..=99_999_999_999 => TimeFormat::Unix,
+        100_000_000_000..=99_999_999_999_999 => TimeFormat::UnixMs,
+        100_000_000_000_000..=99_999_999_999_999_999 => TimeFormat::UnixUs,
+        _ => TimeFormat::UnixNs,
+    }
+}
+
+fn from_unix(value: i64, unit: TimeFormat) -> Result<DateTime<Utc>> {
+    let time = match unit {
+        TimeFormat::Unix => DateTime::from_timestamp(value, 0),
+        TimeFormat::UnixMs => DateTime::from_timestamp_millis(value),
+        TimeFormat::UnixUs => DateTime::from_timestamp_micros(value),
+        TimeFormat::UnixNs => Some(DateTime::from_timestamp_nanos(value)),
+        v => anyhow::bail!("Numeric input can not be read as {}", v),
+    };
+    time.ok_or_else(|| anyhow::anyhow!("Timestamp out of range: {}", value))
+}
+
+fn parse_naive(input: &str) -> Option<NaiveDateTime> {
+    NAIVE_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(input, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+// 没有时区信息的时间按照 --tz 指定的时区来解释
+fn from_naive(naive: NaiveDateTime, tz: TimeZoneSpec) -> Result<DateTime<Utc>> {
+    let time = match tz {
+        TimeZoneSpec::Local => Local
+            .from_local_datetime(&naive)
+            .single()
+            .map(|t| t.with_timezone(&Utc)),
+        TimeZoneSpec::Utc => Some(Utc.from_utc_datetime(&naive)),
+        TimeZoneSpec::Fixed(offset) => offset
+            .from_local_datetime(&naive)
+            .single()
+            .map(|t| t.with_timezone(&Utc)),
+        TimeZoneSpec::Named(zone) => zone
+            .from_local_datetime(&naive)
+            .single()
+            .map(|t| t.with_timezone(&Utc)),
+    };
+    time.ok_or_else(|| anyhow::anyhow!("Ambiguous or invalid local time: {} in {}", naive, tz))
+}
+
+fn format_time(
+    time: DateTime<Utc>,
+    now: DateTime<Utc>,
+    format: TimeFormat,
+    tz: TimeZoneSpec,
+) -> Result<String> {
+    let formatted = match format {
+        TimeFormat::Unix => time.timestamp().to_string(),
+        TimeFormat::UnixMs => time.timestamp_millis().to_string(),
+        TimeFormat::UnixUs => time.timestamp_micros().to_string(),
+        TimeFormat::UnixNs => time
+            .timestamp_nanos_opt()
+            .ok_or_else(|| anyhow::anyhow!("Time out of range for nanoseconds: {}", time))?
+            .to_string(),
+        TimeFormat::Rfc3339 | TimeFormat::Rfc2822 => match tz {
+            TimeZoneSpec::Local => render(time.with_timezone(&Local), format),
+            TimeZoneSpec::Utc => render(time, format),
+            TimeZoneSpec::Fixed(offset) => render(time.with_timezone(&offset), format),
+            TimeZoneSpec::Named(zone) => render(time.with_timezone(&zone), format),
+        },
+        TimeFormat::Relative => format_relative(time - now),
+    };
+    Ok(formatted)
+}
+
+fn render<Tz: TimeZone>(time: DateTime<Tz>, format: TimeFormat) -> String
+where
+    Tz::Offset: fmt::Display,
+{
+    match format {
+        TimeFormat::Rfc2822 => time.to_rfc2822(),
+        _ => time.to_rfc3339(),
+    }
+}
+
+fn format_relative(delta: Duration) -> String {
+    let secs = delta.num_seconds();
+    let abs = secs.unsigned_abs();
+    if abs < 5 {
+        return "now".to_string();
+    }
+
+    let (amount, unit) = match abs {
+        0..=59 => (abs, "second"),
+        60..=3_599 => (abs / 60, "minute"),
+        3_600..=86_399 => (abs / 3_600, "hour"),
+        86_400..=2_591_999 => (abs / 86_400, "day"),
+        2_592_000..=31_535_999 => (abs / 2_592_000, "month"),
+        _ => (abs / 31_536_000, "year"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    if secs > 0 {
+        format!("in {} {}{}", amount, unit, plural)
+    } else {
+        format!("{} {}{} ago", amount, unit, plural)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_unix_to_rfc3339() {
+        let ret = process_time_convert("1714521600", None, TimeFormat::Rfc3339, TimeZoneSpec::Utc)
+            .unwrap();
+        assert_eq!(ret, "2024-05-01T00:00:00+00:00");
+
+        let ret = process_time_convert(
+            "1714521600000",
+            None,
+            TimeFormat::Rfc3339,
+            "Asia/Shanghai".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(ret, "2024-05-01T08:00:00+08:00");
+    }
+
+    #[test]
+    fn test_convert_to_unix() {
+        let ret = process_time_convert(
+            "2024-05-01T08:00:00+08:00",
+            None,
+            TimeFormat::UnixMs,
+            TimeZoneSpec::Utc,
+        )
+        .unwrap();
+        assert_eq!(ret, "1714521600000");
+
+        let ret = process_time_convert(
+            "2024-05-01 08:00:00",
+            None,
+            TimeFormat::Unix,
+            "+08:00".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(ret, "1714521600");
+
+        let ret = process_time_convert(
+            "Wed, 01 May 2024 00:00:00 +0000",
+            None,
+            TimeFormat::Unix,
+            TimeZoneSpec::Local,
+        )
+        .unwrap();
+        assert_eq!(ret, "1714521600");
+    }
+
+    #[test]
+    fn test_convert_with_explicit_unit() {
+        let ret = process_time_convert(
+            "1714521600",
+            Some(TimeFormat::UnixMs),
+            TimeFormat::Rfc3339,
+            TimeZoneSpec::Utc,
+        )
+        .unwrap();
+        assert_eq!(ret, "1970-01-20T20:15:21.600+00:00");
+
+        assert!(process_time_convert(
+            "1714521600",
+            Some(TimeFormat::Relative),
+            TimeFormat::Unix,
+            TimeZoneSpec::Utc,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let now = Utc::now();
+        let time = parse_time("in 3 hours", None, TimeZoneSpec::Utc, now).unwrap();
+        assert_eq!(time - now, Duration::hours(3));
+        assert_eq!(format_relative(time - now), "in 3 hours");
+        assert_eq!(format_relative(Duration::days(-2)), "2 days ago");
+        assert_eq!(format_relative(Duration::minutes(-1)), "1 minute ago");
+        assert_eq!(format_relative(Duration::seconds(1)), "now");
+
+        // relative 的输出可以再作为输入读回来
+        for delta in [Duration::days(60), Duration::days(-730)] {
+            let text = format_relative(delta);
+            assert_eq!(
+                parse_time(&text, None, TimeZoneSpec::Utc, now).unwrap() - now,
+                delta
+            );
+        }
+        assert!(parse_time("in 999999999999 days", None, TimeZoneSpec::Utc, now).is_err());
+        assert!(parse_time("in 100000000 weeks", None, TimeZoneSpec::Utc, now).is_err());
+    }
+}
@@ -1,7 +1,42 @@
 use anyhow::Result;
+use bzip2::{read::MultiBzDecoder, write::BzEncoder};
 use chrono::{Duration, Local};
-use std::{fs::File, io::Read};
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Cursor, Read, Stdout, Write},
+    path::Path,
+};
+use xz2::{read::XzDecoder, write::XzEncoder};
 
+// 压缩格式，读取时根据开头的 magic bytes 或者扩展名判断，写入时根据扩展名判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+// bzip2 的头部是 "BZh" 加上块大小 1-9，之后是第一个块或者流结尾（空文件）的 magic
+const MAGIC_LEN: usize = 10;
+const BZIP2_BLOCK_MAGIC: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+const BZIP2_EOS_MAGIC: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+
+// get_writer 返回的输出，写完之后必须调用 finish
+// flush 不会写入压缩格式的结尾，只有 finish 会结束压缩流，并把写入时的错误返回给调用者
+pub struct OutputWriter(Output);
+
+enum Output {
+    Stdout(BufWriter<Stdout>),
+    File(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+    Bzip2(BzEncoder<BufWriter<File>>),
+    Xz(XzEncoder<BufWriter<File>>),
+}
+
+// "-" 表示从 stdin 读取，原样返回输入的字节，base64、签名等需要处理原始数据的命令使用
 pub fn get_reader(input: &str) -> anyhow::Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
         Box::new(std::io::stdin())
@@ -11,45 +46,276 @@ pub fn get_reader(input: &str) -> anyhow::Result<Box<dyn Read>> {
     Ok(reader)
 }
 
-pub fn current_timestamp_sec() -> i64 {
-    Local::now().timestamp()
+// 和 get_reader 一样，但压缩过的输入会自动解压，csv / 文档类的命令使用
+pub fn get_data_reader(input: &str) -> anyhow::Result<Box<dyn Read>> {
+    let mut reader = get_reader(input)?;
+    // 先读出开头的几个字节判断格式，再放回去
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    reader
+        .by_ref()
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+    let codec = Codec::from_magic(&magic).or_else(|| Codec::from_path(input));
+    let reader: Box<dyn Read> = Box::new(Cursor::new(magic).chain(reader));
+    let reader: Box<dyn Read> = match codec {
+        None => reader,
+        Some(Codec::Gzip) => Box::new(MultiGzDecoder::new(reader)),
+        Some(Codec::Zstd) => Box::new(zstd::Decoder::with_buffer(BufReader::new(reader))?),
+        Some(Codec::Bzip2) => Box::new(MultiBzDecoder::new(reader)),
+        Some(Codec::Xz) => Box::new(XzDecoder::new_multi_decoder(reader)),
+    };
+    Ok(reader)
 }
 
-pub fn parse_str_to_timestamp(duration: &str) -> Result<i64> {
-    let Some(end) = duration.chars().last() else {
-        anyhow::bail!("Empty duration");
+// "-" 表示输出到 stdout，文件以 .gz / .zst / .bz2 / .xz 结尾时压缩后写入
+pub fn get_writer(output: &str) -> anyhow::Result<OutputWriter> {
+    if output == "-" {
+        return Ok(OutputWriter(Output::Stdout(BufWriter::new(
+            std::io::stdout(),
+        ))));
+    }
+    let file = BufWriter::new(File::create(output)?);
+    let output = match Codec::from_path(output) {
+        None => Output::File(file),
+        Some(Codec::Gzip) => Output::Gzip(GzEncoder::new(file, flate2::Compression::default())),
+        Some(Codec::Zstd) => Output::Zstd(zstd::Encoder::new(file, 0)?),
+        Some(Codec::Bzip2) => Output::Bzip2(BzEncoder::new(file, bzip2::Compression::default())),
+        Some(Codec::Xz) => Output::Xz(XzEncoder::new(file, 6)),
     };
+    Ok(OutputWriter(output))
+}
 
-    match end {
-        'd' => {
-            let days = duration.trim_end_matches('d').parse::<i64>()?;
-            let timestamp = Local::now() + Duration::days(days);
-            Ok(timestamp.timestamp())
+impl OutputWriter {
+    // 结束压缩流并 flush 到文件
+    pub fn finish(self) -> anyhow::Result<()> {
+        let mut file = match self.0 {
+            Output::Stdout(mut stdout) => {
+                stdout.flush()?;
+                return Ok(());
+            }
+            Output::File(file) => file,
+            Output::Gzip(encoder) => encoder.finish()?,
+            Output::Zstd(encoder) => encoder.finish()?,
+            Output::Bzip2(encoder) => encoder.finish()?,
+            Output::Xz(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+
+// 取出 csv writer 缓冲的内容后结束输出
+pub fn finish_csv(writer: csv::Writer<OutputWriter>) -> anyhow::Result<()> {
+    writer.into_inner().map_err(|e| e.into_error())?.finish()
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            Output::Stdout(w) => w.write(buf),
+            Output::File(w) => w.write(buf),
+            Output::Gzip(w) => w.write(buf),
+            Output::Zstd(w) => w.write(buf),
+            Output::Bzip2(w) => w.write(buf),
+            Output::Xz(w) => w.write(buf),
         }
-        'h' => {
-            let hours = duration.trim_end_matches('h').parse::<i64>()?;
-            let timestamp = Local::now() + Duration::hours(hours);
-            Ok(timestamp.timestamp())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.0 {
+            Output::Stdout(w) => w.flush(),
+            Output::File(w) => w.flush(),
+            Output::Gzip(w) => w.flush(),
+            Output::Zstd(w) => w.flush(),
+            Output::Bzip2(w) => w.flush(),
+            Output::Xz(w) => w.flush(),
         }
-        'm' => {
-            let minutes = duration.trim_end_matches('m').parse::<i64>()?;
-            let timestamp = Local::now() + Duration::minutes(minutes);
-            Ok(timestamp.timestamp())
+    }
+}
+
+// 去掉压缩格式的扩展名，如 players.csv.gz => players.csv，用于根据扩展名判断数据格式
+pub fn strip_compression_ext(path: &str) -> &str {
+    match Codec::from_path(path) {
+        Some(_) => path.rsplit_once('.').map_or(path, |(stem, _)| stem),
+        None => path,
+    }
+}
+
+impl Codec {
+    fn from_magic(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Codec::Gzip)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Codec::Zstd)
+        } else if is_bzip2(magic) {
+            Some(Codec::Bzip2)
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Codec::Xz)
+        } else {
+            None
         }
-        's' => {
-            let seconds = duration.trim_end_matches('s').parse::<i64>()?;
-            let timestamp = Local::now() + Duration::seconds(seconds);
-            Ok(timestamp.timestamp())
+    }
+
+    fn from_path(path: &str) -> Option<Self> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "gz" | "gzip" => Some(Codec::Gzip),
+            "zst" | "zstd" => Some(Codec::Zstd),
+            "bz2" => Some(Codec::Bzip2),
+            "xz" => Some(Codec::Xz),
+            _ => None,
         }
+    }
+}
+
+// 只检查 "BZh" 的话，以 BZh 开头的普通文本也会被当成 bzip2
+fn is_bzip2(magic: &[u8]) -> bool {
+    match magic {
+        [b'B', b'Z', b'h', b'1'..=b'9', rest @ ..] => {
+            rest == BZIP2_BLOCK_MAGIC || rest == BZIP2_EOS_MAGIC
+        }
+        _ => false,
+    }
+}
+
+pub fn current_timestamp_sec() -> i64 {
+    Local::now().timestamp()
+}
+
+pub fn parse_str_to_timestamp(duration: &str) -> Result<i64> {
+    let offset = parse_str_to_duration(duration)?;
+    let timestamp = Local::now()
+        .checked_add_signed(offset)
+        .ok_or_else(|| anyhow::anyhow!("Timestamp out of range: {}", duration))?;
+    Ok(timestamp.timestamp())
+}
+
+// 解析 "5d", "3h", "10m", "60s" 这类简写的时长
+pub fn parse_str_to_duration(duration: &str) -> Result<Duration> {
+    let Some(end) = duration.chars().last() else {
+        anyhow::bail!("Empty duration");
+    };
 
+    let unit = match end {
+        'd' => Duration::try_days,
+        'h' => Duration::try_hours,
+        'm' => Duration::try_minutes,
+        's' => Duration::try_seconds,
         v => anyhow::bail!("Unsupported format: {}", v),
+    };
+    let amount = duration[..duration.len() - 1].parse::<i64>()?;
+    unit(amount).ok_or_else(|| anyhow::anyhow!("Duration out of range: {}", duration))
+}
+
+// 解析人类可读的相对时间，如 "in 3 hours", "2 days ago", "+5d", "-30m"
+// 返回相对于当前时间的偏移量
+pub fn parse_relative_duration(input: &str) -> Result<Duration> {
+    let input = input.trim().to_lowercase();
+    if input == "now" {
+        return Ok(Duration::zero());
+    }
+    if let Some(rest) = input.strip_prefix('+') {
+        return parse_str_to_duration(rest);
     }
+    if let Some(rest) = input.strip_prefix('-') {
+        return Ok(-parse_str_to_duration(rest)?);
+    }
+
+    let (body, negative) = if let Some(rest) = input.strip_prefix("in ") {
+        (rest, false)
+    } else if let Some(rest) = input.strip_suffix(" ago") {
+        (rest, true)
+    } else {
+        anyhow::bail!("Unsupported relative time: {}", input);
+    };
+
+    let mut parts = body.split_whitespace();
+    let (Some(amount), Some(unit), None) = (parts.next(), parts.next(), parts.next()) else {
+        anyhow::bail!("Unsupported relative time: {}", input);
+    };
+    let amount = match amount {
+        "a" | "an" => 1,
+        v => v.parse::<i64>()?,
+    };
+    // month 和 year 按 30 天和 365 天计算，和 time convert -f relative 的输出一致
+    let duration = match unit.trim_end_matches('s') {
+        "second" | "sec" => Duration::try_seconds(amount),
+        "minute" | "min" => Duration::try_minutes(amount),
+        "hour" => Duration::try_hours(amount),
+        "day" => Duration::try_days(amount),
+        "week" => Duration::try_weeks(amount),
+        "month" => amount.checked_mul(30).and_then(Duration::try_days),
+        "year" => amount.checked_mul(365).and_then(Duration::try_days),
+        v => anyhow::bail!("Unsupported time unit: {}", v),
+    };
+    let duration =
+        duration.ok_or_else(|| anyhow::anyhow!("Duration out of range: {} {}", amount, unit))?;
+    Ok(if negative { -duration } else { duration })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_compressed_reader_and_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = "name,age\nAlice,30\n".repeat(100);
+        for ext in ["csv.gz", "csv.zst", "csv.bz2", "csv.xz"] {
+            let path = dir.path().join(format!("data.{}", ext));
+            let path = path.to_str().unwrap();
+            let mut writer = get_writer(path).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+            writer.finish().unwrap();
+            assert_ne!(std::fs::read(path).unwrap(), content.as_bytes());
+
+            let mut ret = String::new();
+            get_data_reader(path)
+                .unwrap()
+                .read_to_string(&mut ret)
+                .unwrap();
+            assert_eq!(ret, content, "{}", ext);
+
+            // get_reader 返回原始的字节
+            let mut raw = Vec::new();
+            get_reader(path).unwrap().read_to_end(&mut raw).unwrap();
+            assert_eq!(raw, std::fs::read(path).unwrap(), "{}", ext);
+
+            // 没有扩展名时根据 magic bytes 判断
+            let renamed = dir.path().join("data.bin");
+            std::fs::rename(path, &renamed).unwrap();
+            let mut ret = String::new();
+            get_data_reader(renamed.to_str().unwrap())
+                .unwrap()
+                .read_to_string(&mut ret)
+                .unwrap();
+            assert_eq!(ret, content, "{}", ext);
+        }
+    }
+
+    #[test]
+    fn test_text_starting_with_bzh() {
+        for path in ["fixtures/bzh.b64", "fixtures/bzh.csv"] {
+            let mut ret = String::new();
+            get_data_reader(path)
+                .unwrap()
+                .read_to_string(&mut ret)
+                .unwrap();
+            assert_eq!(ret, std::fs::read_to_string(path).unwrap(), "{}", path);
+        }
+        assert!(!is_bzip2(b"BZh91AY,co"));
+        assert!(is_bzip2(&[
+            b'B', b'Z', b'h', b'9', 0x31, 0x41, 0x59, 0x26, 0x53, 0x59
+        ]));
+    }
+
+    #[test]
+    fn test_strip_compression_ext() {
+        assert_eq!(strip_compression_ext("players.csv.gz"), "players.csv");
+        assert_eq!(strip_compression_ext("data.json.zst"), "data.json");
+        assert_eq!(strip_compression_ext("players.csv"), "players.csv");
+    }
+
     #[test]
     fn test_parse_str_to_timestamp_should_works() {
         let timestamp1 = parse_str_to_timestamp("60s").unwrap();
@@ -81,4 +347,54 @@ mod tests {
         };
         assert_eq!(err.to_string(), "Empty duration")
     }
+
+    #[test]
+    fn test_parse_relative_duration_should_works() {
+        assert_eq!(
+            parse_relative_duration("in 3 hours").unwrap(),
+            Duration::hours(3)
+        );
+        assert_eq!(
+            parse_relative_duration("2 days ago").unwrap(),
+            Duration::days(-2)
+        );
+        assert_eq!(
+            parse_relative_duration("an hour ago").unwrap(),
+            Duration::hours(-1)
+        );
+        assert_eq!(parse_relative_duration("+5d").unwrap(), Duration::days(5));
+        assert_eq!(
+            parse_relative_duration("-30m").unwrap(),
+            Duration::minutes(-30)
+        );
+        assert_eq!(parse_relative_duration("now").unwrap(), Duration::zero());
+    }
+
+    #[test]
+    fn test_parse_relative_duration_should_err() {
+        let Err(err) = parse_relative_duration("in 3 fortnights") else {
+            panic!(r#"parse_relative_duration("in 3 fortnights") should error"#)
+        };
+        assert_eq!(err.to_string(), "Unsupported time unit: fortnight");
+        assert!(parse_relative_duration("yesterday").is_err());
+
+        let Err(err) = parse_relative_duration("in 999999999999 days") else {
+            panic!(r#"parse_relative_duration("in 999999999999 days") should error"#)
+        };
+        assert_eq!(err.to_string(), "Duration out of range: 999999999999 days");
+        assert!(parse_relative_duration("+9223372036854775807s").is_err());
+        assert!(parse_str_to_timestamp("99999999999d").is_err());
+    }
+
+    #[test]
+    fn test_parse_relative_months_and_years() {
+        assert_eq!(
+            parse_relative_duration("in 2 months").unwrap(),
+            Duration::days(60)
+        );
+        assert_eq!(
+            parse_relative_duration("a year ago").unwrap(),
+            Duration::days(-365)
+        );
+    }
 }